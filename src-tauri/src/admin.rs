@@ -1,53 +1,261 @@
 
+use crate::admin_keys;
+use crate::consent::{self, ConsentPayload, ConsentReceipt};
+use crate::logs::{self, AuditEvent};
+use crate::nonce::{self, NonceStore, Purpose};
+use crate::policy;
 use crate::security::AppState;
+use crate::session;
+use crate::users;
 use ed25519_dalek::{Verifier, VerifyingKey, Signature};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::{Duration, Instant};
 
-fn load_admin_pubkey() -> Result<VerifyingKey, String> {
-    // Expect PEM in resources as admin_public.pem
-    let path = tauri::api::path::resource_dir().unwrap().join("admin_public.pem");
-    let pem = std::fs::read_to_string(path).map_err(|e| format!("admin_public.pem missing: {}", e))?;
-    let der = pem.lines().filter(|l| !l.starts_with("-----")).collect::<String>();
-    let bytes = base64::decode(der).map_err(|e| e.to_string())?;
-    VerifyingKey::from_public_key_der(&bytes).map_err(|e| e.to_string())
+const ADMIN_UNLOCK: &str = "admin_unlock";
+
+/// What an unlocked admin session may do. The scope list is part of the signed unlock
+/// challenge, so a signature for a proctor session cannot be turned into a full one.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Scope {
+    #[serde(rename = "logs.read")]       LogsRead,
+    #[serde(rename = "logs.checkpoint")] LogsCheckpoint,
+    #[serde(rename = "consent.manage")]  ConsentManage,
+    #[serde(rename = "history.manage")]  HistoryManage,
+    #[serde(rename = "policy.manage")]   PolicyManage,
+    #[serde(rename = "keys.manage")]     KeysManage,
+}
+
+impl Scope {
+    const ALL: [Scope; 6] = [Scope::LogsRead, Scope::LogsCheckpoint, Scope::ConsentManage, Scope::HistoryManage, Scope::PolicyManage, Scope::KeysManage];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::LogsRead       => "logs.read",
+            Scope::LogsCheckpoint => "logs.checkpoint",
+            Scope::ConsentManage  => "consent.manage",
+            Scope::HistoryManage  => "history.manage",
+            Scope::PolicyManage   => "policy.manage",
+            Scope::KeysManage     => "keys.manage",
+        }
+    }
+
+    /// The policy capability that must also allow the command.
+    fn capability(self) -> &'static str {
+        match self {
+            Scope::LogsRead => policy::LOGS_READ,
+            _               => policy::ADMIN_ACTION,
+        }
+    }
+
+    /// Expand scope names and role presets (`admin`, `proctor`) into a sorted, de-duplicated list.
+    pub fn resolve(names: &[String]) -> Result<Vec<Scope>, String> {
+        let mut out = Vec::new();
+        for name in names.iter().map(|n| n.trim()) {
+            match name {
+                "admin"   => out.extend(Scope::ALL),
+                "proctor" => out.extend([Scope::LogsRead, Scope::ConsentManage]),
+                _ => out.push(*Scope::ALL.iter().find(|s| s.as_str() == name)
+                    .ok_or_else(|| format!("unknown admin scope \"{name}\""))?),
+            }
+        }
+        out.sort();
+        out.dedup();
+        if out.is_empty() { return Err("at least one admin scope is required".into()); }
+        Ok(out)
+    }
+}
+
+pub fn scope_list(scopes: &[Scope]) -> String {
+    scopes.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(",")
+}
+
+pub struct AdminSession {
+    /// Registry id of the key that signed the unlock challenge.
+    key_id:    String,
+    scopes:    Vec<Scope>,
+    last_used: Instant,
+}
+
+fn idle_timeout(state: &AppState) -> Duration {
+    Duration::from_secs(state.config.lock().unwrap().session.admin_idle_timeout_secs)
+}
+
+/// Drop the admin session if it has been idle too long. Call with the admin lock held.
+fn expire(slot: &mut Option<AdminSession>, timeout: Duration) {
+    if slot.as_ref().is_some_and(|s| s.last_used.elapsed() >= timeout) {
+        *slot = None;
+        let _ = logs::record(&AuditEvent::AdminLocked { reason: "idle timeout".into() });
+    }
+}
+
+/// Whether an admin session is live. Does not count as activity.
+pub fn is_unlocked(state: &AppState) -> bool {
+    let timeout = idle_timeout(state);
+    let mut slot = state.admin.lock().unwrap();
+    expire(&mut slot, timeout);
+    slot.is_some()
 }
 
+/// Guard for admin-only commands: `admin::require(&state, Scope::LogsRead)?;`
+/// Needs a live admin session holding `scope`, then the matching policy capability.
+/// Resets the idle timer; denials are recorded in the audit log.
+pub fn require(state: &AppState, scope: Scope) -> Result<(), String> {
+    let timeout = idle_timeout(state);
+    let denied = {
+        let mut slot = state.admin.lock().unwrap();
+        expire(&mut slot, timeout);
+        match slot.as_mut() {
+            None => Some("admin mode is locked".to_string()),
+            Some(s) if !s.scopes.contains(&scope) => Some(format!("this admin session does not have the {} scope", scope.as_str())),
+            Some(s) => { s.last_used = Instant::now(); None }
+        }
+    };
+    if let Some(reason) = denied {
+        let _ = logs::record(&AuditEvent::PolicyDenial {
+            capability: scope.as_str().into(), reason: reason.clone(), user_id: session::current_user(state),
+        });
+        return Err(format!("Admin denied {}: {reason}", scope.as_str()));
+    }
+    policy::enforce(state, scope.capability())?;
+    Ok(())
+}
+
+/// The exact bytes the admin signs to unlock.
+fn challenge(nonce: &str, scopes: &str, expires_at: u64) -> String {
+    format!("promptpilot-admin-unlock:v1:{nonce}:{scopes}:{expires_at}")
+}
+
+#[derive(Serialize)]
+pub struct AdminChallenge {
+    pub scopes:     Vec<Scope>,
+    pub expires_at: u64,
+    /// Sign these bytes with the admin key (Ed25519, base64 signature).
+    pub challenge:  String,
+}
+
+/// Start an unlock for `scopes` (scope names or the `admin` / `proctor` presets; default `admin`).
+#[tauri::command]
+pub fn admin_get_nonce(scopes: Option<Vec<String>>, state: tauri::State<AppState>) -> Result<AdminChallenge, String> {
+    let scopes = Scope::resolve(&scopes.unwrap_or_else(|| vec!["admin".into()]))?;
+    let list = scope_list(&scopes);
+    let mut nonces = state.nonces.lock().unwrap();
+    nonces.blocked(Purpose::AdminUnlock, None, ADMIN_UNLOCK)?;
+    let n = nonces.issue(Purpose::AdminUnlock, None, &list, "", nonce::ADMIN_UNLOCK_TTL);
+    Ok(AdminChallenge { challenge: challenge(&n.value, &list, n.expires_at), expires_at: n.expires_at, scopes })
+}
+
+/// Verify the admin's signature over the outstanding challenge and open an admin session
+/// with its scopes. The nonce is consumed by this call whatever the outcome; a failed
+/// attempt needs a fresh `admin_get_nonce`.
 #[tauri::command]
-pub fn admin_get_nonce(state: tauri::State<AppState>) -> Result<String, String> {
-    let n = nanoid::nanoid!(24);
-    *state.admin_nonce.lock().unwrap() = Some(n.clone());
-    Ok(n)
+pub fn admin_unlock(signature_b64: String, state: tauri::State<AppState>) -> Result<AdminStatus, String> {
+    let mut nonces = state.nonces.lock().unwrap();
+    let reject = |nonces: &mut NonceStore, e: String| nonce::reject(nonces, Purpose::AdminUnlock, None, ADMIN_UNLOCK, e);
+    nonces.blocked(Purpose::AdminUnlock, None, ADMIN_UNLOCK)?;
+    let n = nonces.consume(Purpose::AdminUnlock, None, None).map_err(|e| reject(&mut nonces, e))?;
+    let signed = challenge(&n.value, &n.action, n.expires_at);
+    // The scopes come from what was issued and signed, never from this call
+    let scopes = Scope::resolve(&n.action.split(',').map(str::to_string).collect::<Vec<_>>())?;
+    let key = admin_keys::find_signer(signed.as_bytes(), &signature_b64).and_then(|key| {
+        match scopes.iter().find(|s| !key.scopes.contains(s)) {
+            Some(s) => Err(format!("admin key \"{}\" may not unlock the {} scope", key.label, s.as_str())),
+            None    => Ok(key),
+        }
+    });
+    let key = match key { Ok(k) => k, Err(e) => return Err(reject(&mut nonces, e)) };
+    nonces.succeeded(Purpose::AdminUnlock, None);
+    drop(nonces);
+
+    *state.admin.lock().unwrap() = Some(AdminSession { key_id: key.id.clone(), scopes: scopes.clone(), last_used: Instant::now() });
+    logs::record(&AuditEvent::AdminUnlocked { scopes: scopes.iter().map(|s| s.as_str().to_string()).collect(), key_id: key.id })?;
+    admin_status(state)
+}
+
+/// End the admin session if it was unlocked with `key_id` (e.g. that key was just revoked).
+pub fn end_sessions_of(state: &AppState, key_id: &str) {
+    let mut slot = state.admin.lock().unwrap();
+    if slot.as_ref().is_some_and(|s| s.key_id == key_id) {
+        *slot = None;
+        let _ = logs::record(&AuditEvent::AdminLocked { reason: format!("key {key_id} revoked") });
+    }
 }
 
+/// End the admin session now.
 #[tauri::command]
-pub fn admin_unlock(signature_b64: String, state: tauri::State<AppState>) -> Result<bool, String> {
-    let nonce = state.admin_nonce.lock().unwrap().clone().ok_or("no nonce")?;
-    let sig_bytes = base64::decode(signature_b64).map_err(|e| e.to_string())?;
-    let sig = Signature::from_bytes(&sig_bytes).map_err(|e| e.to_string())?;
-    let pk = load_admin_pubkey()?;
-    pk.verify(nonce.as_bytes(), &sig).map_err(|e| e.to_string())?;
-    *state.admin_unlocked.lock().unwrap() = true;
-    Ok(true)
+pub fn admin_lock(state: tauri::State<AppState>) -> Result<(), String> {
+    if state.admin.lock().unwrap().take().is_some() {
+        logs::record(&AuditEvent::AdminLocked { reason: "locked by admin".into() })?;
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+pub struct AdminStatus {
+    pub unlocked:    bool,
+    pub scopes:      Vec<Scope>,
+    /// Seconds until the session locks for inactivity.
+    pub idle_lock_in: u64,
 }
 
 #[tauri::command]
-pub fn admin_status(state: tauri::State<AppState>) -> Result<bool, String> {
-    Ok(*state.admin_unlocked.lock().unwrap())
+pub fn admin_status(state: tauri::State<AppState>) -> Result<AdminStatus, String> {
+    let timeout = idle_timeout(&state);
+    let mut slot = state.admin.lock().unwrap();
+    expire(&mut slot, timeout);
+    Ok(match slot.as_ref() {
+        Some(s) => AdminStatus {
+            unlocked: true,
+            scopes: s.scopes.clone(),
+            idle_lock_in: timeout.saturating_sub(s.last_used.elapsed()).as_secs(),
+        },
+        None => AdminStatus { unlocked: false, scopes: Vec::new(), idle_lock_in: 0 },
+    })
+}
+
+#[derive(Serialize)]
+pub struct ConsentRequest {
+    pub user_id: String,
+    pub nonce:   String,
+    pub payload: ConsentPayload,
+    /// The canonical payload text the user's key must sign.
+    pub message: String,
 }
 
+/// Ask `user_id` to consent to `action` with `params`. Both are bound into the payload
+/// the user signs, so the resulting receipt is only good for this exact action.
 #[tauri::command]
-pub fn request_user_consent(user_id: String, action: String, state: tauri::State<AppState>) -> Result<(String,String), String> {
-    let nonce = nanoid::nanoid!(20);
-    *state.pending_user_consent.lock().unwrap() = Some((user_id.clone(), nonce.clone()));
-    Ok((user_id, nonce))
+pub fn request_user_consent(user_id: String, action: String, params: Option<Value>, state: tauri::State<AppState>) -> Result<ConsentRequest, String> {
+    require(&state, Scope::ConsentManage)?;
+    let mut nonces = state.nonces.lock().unwrap();
+    let hash = consent::params_hash(params.as_ref());
+    nonces.blocked(Purpose::UserConsent, Some(&user_id), &action)?;
+    let n = nonces.issue(Purpose::UserConsent, Some(&user_id), &action, &hash, nonce::USER_CONSENT_TTL);
+    logs::record(&AuditEvent::ConsentRequested { user_id: user_id.clone(), action })?;
+    let payload = ConsentPayload::for_nonce(&n);
+    Ok(ConsentRequest { user_id, nonce: n.value, message: payload.canonical(), payload })
 }
 
+/// Check the user's signature over the consent payload and store a receipt for it. Like
+/// `admin_unlock`, the nonce is single-use: any attempt, good or bad, consumes it.
 #[tauri::command]
-pub fn verify_user_consent_and_authorize(user_id:String, consent_nonce:String, user_signature_b64:String, user_pubkey_b64:String, state: tauri::State<AppState>) -> Result<bool,String> {
-    let pending = state.pending_user_consent.lock().unwrap().clone().ok_or("no pending consent")?;
-    if pending.0 != user_id || pending.1 != consent_nonce { return Err("nonce mismatch".into()); }
-    let user_pk = base64::decode(user_pubkey_b64).map_err(|e| e.to_string())?;
-    let vk = ed25519_dalek::VerifyingKey::from_bytes(&user_pk.try_into().map_err(|_|"pk size")?).map_err(|e|e.to_string())?;
-    let sig = ed25519_dalek::Signature::from_bytes(&base64::decode(user_signature_b64).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
-    vk.verify(consent_nonce.as_bytes(), &sig).map_err(|e| e.to_string())?;
-    Ok(true)
+pub fn verify_user_consent_and_authorize(user_id:String, consent_nonce:String, user_signature_b64:String, user_pubkey_b64:String, state: tauri::State<AppState>) -> Result<ConsentReceipt,String> {
+    require(&state, Scope::ConsentManage)?;
+    let mut nonces = state.nonces.lock().unwrap();
+    let subject = Some(user_id.as_str());
+    nonces.blocked(Purpose::UserConsent, subject, "")?;
+    let n = nonces.consume(Purpose::UserConsent, subject, Some(&consent_nonce))
+        .map_err(|e| nonce::reject(&mut nonces, Purpose::UserConsent, subject, "", e))?;
+    // Rebuilt from what we issued, never taken from the caller
+    let payload = ConsentPayload::for_nonce(&n);
+    let verified = (|| -> Result<(), String> {
+        let user_pk = base64::decode(&user_pubkey_b64).map_err(|e| e.to_string())?;
+        if !users::is_known_key(&user_id, &user_pk, false)? { return Err("public key is not this user's current key".into()); }
+        let vk = VerifyingKey::from_bytes(&user_pk.try_into().map_err(|_| "pk size")?).map_err(|e| e.to_string())?;
+        let sig = Signature::from_slice(&base64::decode(&user_signature_b64).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+        vk.verify(payload.canonical().as_bytes(), &sig).map_err(|_| "signature does not verify".to_string())
+    })();
+    if let Err(e) = verified { return Err(nonce::reject(&mut nonces, Purpose::UserConsent, subject, &n.action, e)); }
+    nonces.succeeded(Purpose::UserConsent, subject);
+    consent::issue_receipt(&payload, &user_signature_b64, &user_pubkey_b64)
 }
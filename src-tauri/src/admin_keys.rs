@@ -0,0 +1,365 @@
+
+//! Admin key registry and m-of-n approvals.
+//!
+//! Every admin (or proctor) holds their own Ed25519 key. The registry (`admin_keys` in
+//! admin_logs.db) records each key with a label, the scopes it may unlock, a validity
+//! window and an optional revocation. The bundled `admin_public.pem` seeds it as the
+//! `root` key on first use. An admin signature is accepted if any key that is active now
+//! verifies it.
+//!
+//! Destructive actions (`ApprovalAction`) additionally need `quorum` distinct keys to sign
+//! an approval statement once the quorum is raised above 1: `admin_approval_request` issues
+//! the statement, key holders sign it offline, and `admin_approval_submit` collects them.
+
+use crate::admin::{self, Scope};
+use crate::consent;
+use crate::logs::{self, AuditEvent};
+use crate::nonce::now_secs;
+use crate::security::{open_db, AppState};
+use ed25519_dalek::pkcs8::DecodePublicKey;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use once_cell::sync::OnceCell;
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+
+const APPROVAL_TTL_SECS: u64 = 900;
+const ROOT_KEY_ID: &str = "root";
+
+static RESOURCE_DIR: OnceCell<PathBuf> = OnceCell::new();
+
+/// Called from `setup` with the app's resource directory (`app.path().resource_dir()`).
+pub fn init(resource_dir: PathBuf) {
+    let _ = RESOURCE_DIR.set(resource_dir);
+}
+
+/// The key shipped as `admin_public.pem` (SPKI PEM) in the resource dir, if any.
+fn bundled_key() -> Result<VerifyingKey, String> {
+    let dir = RESOURCE_DIR.get().ok_or("resource directory not initialised")?;
+    let pem = std::fs::read_to_string(dir.join("admin_public.pem")).map_err(|e| format!("admin_public.pem missing: {}", e))?;
+    let der = pem.lines().filter(|l| !l.starts_with("-----")).collect::<String>();
+    let bytes = base64::decode(der).map_err(|e| e.to_string())?;
+    VerifyingKey::from_public_key_der(&bytes).map_err(|e| e.to_string())
+}
+
+fn parse_pubkey(b64: &str) -> Result<VerifyingKey, String> {
+    let bytes = base64::decode(b64.trim()).map_err(|e| e.to_string())?;
+    let raw: [u8; 32] = bytes.as_slice().try_into().map_err(|_| "admin public key must be 32 bytes")?;
+    VerifyingKey::from_bytes(&raw).map_err(|e| e.to_string())
+}
+
+/// Short stable id: the first 16 hex digits of SHA-256 over the raw key.
+fn fingerprint(key: &VerifyingKey) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))[..16].to_string()
+}
+
+#[derive(Serialize, Clone)]
+pub struct AdminKey {
+    pub id:            String,
+    pub label:         String,
+    pub pubkey_b64:    String,
+    /// Scopes an admin session unlocked with this key may hold.
+    pub scopes:        Vec<Scope>,
+    pub valid_from:    u64,
+    pub valid_until:   Option<u64>,
+    pub revoked_at:    Option<u64>,
+    pub revoke_reason: Option<String>,
+}
+
+impl AdminKey {
+    pub fn active_at(&self, t: u64) -> bool {
+        self.revoked_at.is_none() && t >= self.valid_from && self.valid_until.is_none_or(|u| t < u)
+    }
+
+    fn verifies(&self, message: &[u8], sig: &Signature) -> bool {
+        parse_pubkey(&self.pubkey_b64).is_ok_and(|k| k.verify(message, sig).is_ok())
+    }
+}
+
+pub fn ensure_keys_table(conn: &rusqlite::Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS admin_keys(id TEXT PRIMARY KEY, label TEXT NOT NULL, pubkey TEXT NOT NULL UNIQUE, scopes TEXT NOT NULL, valid_from INTEGER NOT NULL, valid_until INTEGER, revoked_at INTEGER, revoke_reason TEXT, created_at DATETIME DEFAULT CURRENT_TIMESTAMP);
+         CREATE TABLE IF NOT EXISTS admin_key_settings(name TEXT PRIMARY KEY, value INTEGER NOT NULL);"
+    ).map_err(|e| e.to_string())?;
+    let empty: bool = conn.query_row("SELECT COUNT(*) = 0 FROM admin_keys", [], |r| r.get(0)).map_err(|e| e.to_string())?;
+    if empty {
+        // First run: trust the bundled key with every scope
+        if let Ok(key) = bundled_key() {
+            conn.execute(
+                "INSERT INTO admin_keys(id, label, pubkey, scopes, valid_from) VALUES(?1, 'Bundled admin key', ?2, ?3, 0)",
+                params![ROOT_KEY_ID, base64::encode(key.as_bytes()), admin::scope_list(&Scope::resolve(&["admin".into()])?)],
+            ).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+fn row_to_key(r: &rusqlite::Row) -> rusqlite::Result<(AdminKey, String)> {
+    Ok((AdminKey {
+        id: r.get(0)?, label: r.get(1)?, pubkey_b64: r.get(2)?, scopes: Vec::new(),
+        valid_from: r.get::<_, i64>(4)? as u64,
+        valid_until: r.get::<_, Option<i64>>(5)?.map(|v| v as u64),
+        revoked_at: r.get::<_, Option<i64>>(6)?.map(|v| v as u64),
+        revoke_reason: r.get(7)?,
+    }, r.get(3)?))
+}
+
+pub fn list(conn: &rusqlite::Connection) -> Result<Vec<AdminKey>, String> {
+    ensure_keys_table(conn)?;
+    let mut stmt = conn.prepare(
+        "SELECT id, label, pubkey, scopes, valid_from, valid_until, revoked_at, revoke_reason FROM admin_keys ORDER BY created_at, id"
+    ).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([], row_to_key).map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())?;
+    rows.into_iter().map(|(mut key, scopes)| {
+        key.scopes = Scope::resolve(&scopes.split(',').map(str::to_string).collect::<Vec<_>>())?;
+        Ok(key)
+    }).collect()
+}
+
+/// The active key whose signature over `message` verifies, if any.
+pub fn find_signer(message: &[u8], signature_b64: &str) -> Result<AdminKey, String> {
+    let sig_bytes = base64::decode(signature_b64.trim()).map_err(|e| e.to_string())?;
+    let sig = Signature::from_slice(&sig_bytes).map_err(|e| e.to_string())?;
+    let conn = open_db("admin_logs.db");
+    let keys = list(&conn)?;
+    if keys.is_empty() { return Err("no admin keys are registered (admin_public.pem missing?)".into()); }
+    let now = now_secs();
+    match keys.into_iter().find(|k| k.verifies(message, &sig)) {
+        Some(k) if k.active_at(now) => Ok(k),
+        Some(k) => Err(format!("admin key \"{}\" is revoked or outside its validity window", k.label)),
+        None    => Err("signature does not verify with any registered admin key".into()),
+    }
+}
+
+/// Keys whose past signatures (e.g. on log checkpoints) are still trusted: all but revoked ones.
+pub fn trusted_keys() -> Result<Vec<VerifyingKey>, String> {
+    let conn = open_db("admin_logs.db");
+    let keys = list(&conn)?;
+    if keys.is_empty() { return Err("no admin keys are registered (admin_public.pem missing?)".into()); }
+    keys.iter().filter(|k| k.revoked_at.is_none()).map(|k| parse_pubkey(&k.pubkey_b64)).collect()
+}
+
+fn quorum(conn: &rusqlite::Connection) -> Result<u32, String> {
+    ensure_keys_table(conn)?;
+    let q: Option<i64> = conn.query_row("SELECT value FROM admin_key_settings WHERE name='quorum'", [], |r| r.get(0))
+        .optional().map_err(|e| e.to_string())?;
+    Ok(q.unwrap_or(1).max(1) as u32)
+}
+
+/// Scopes an approval can need; the quorum applies to each of them.
+const APPROVAL_SCOPES: [Scope; 2] = [Scope::HistoryManage, Scope::KeysManage];
+
+/// Keys active at `now` that hold `scope`, other than `except`.
+fn holders(keys: &[AdminKey], scope: Scope, now: u64, except: Option<&str>) -> u32 {
+    keys.iter().filter(|k| Some(k.id.as_str()) != except && k.active_at(now) && k.scopes.contains(&scope)).count() as u32
+}
+
+/// Signatures an approval for `action` needs. When keys have expired past `valid_until`
+/// and fewer holders of its scope remain than the quorum, the action is refused rather
+/// than approved by fewer keys; only `SetQuorum` is capped at the holders left, so the
+/// quorum can still be lowered, and that change is audited like any other.
+fn effective_quorum(conn: &rusqlite::Connection, action: ApprovalAction) -> Result<u32, String> {
+    let required = quorum(conn)?;
+    let available = holders(&list(conn)?, action.scope(), now_secs(), None);
+    if required <= available { return Ok(required); }
+    if action == ApprovalAction::SetQuorum { return Ok(available.max(1)); }
+    Err(format!(
+        "quorum unreachable: {} needs {required} approvals but only {available} active keys hold {}; lower the quorum first",
+        action.as_str(), action.scope().as_str(),
+    ))
+}
+
+// ── Approvals ────────────────────────────────────────────────────────────────
+/// Actions that need `quorum` distinct admin keys once the quorum is above 1.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalAction { ClearUserHistory, ClearAllHistory, AddKey, RevokeKey, SetQuorum }
+
+impl ApprovalAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ApprovalAction::ClearUserHistory => "clear_user_history",
+            ApprovalAction::ClearAllHistory  => "clear_all_history",
+            ApprovalAction::AddKey           => "add_key",
+            ApprovalAction::RevokeKey        => "revoke_key",
+            ApprovalAction::SetQuorum        => "set_quorum",
+        }
+    }
+
+    /// The scope an approving key must carry.
+    fn scope(self) -> Scope {
+        match self {
+            ApprovalAction::ClearUserHistory | ApprovalAction::ClearAllHistory => Scope::HistoryManage,
+            ApprovalAction::AddKey | ApprovalAction::RevokeKey | ApprovalAction::SetQuorum => Scope::KeysManage,
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct PendingApproval {
+    pub id:          String,
+    pub action:      ApprovalAction,
+    pub params_hash: String,
+    pub expires_at:  u64,
+    /// Each approver signs these bytes with their admin key.
+    pub statement:   String,
+    pub required:    u32,
+    /// Ids of the keys that have signed so far.
+    pub approved_by: Vec<String>,
+}
+
+/// Start collecting approvals for `action` with `params` (the same JSON the action is later
+/// called with). Needs a live admin session.
+#[tauri::command]
+pub fn admin_approval_request(action: ApprovalAction, params: Option<Value>, state: tauri::State<AppState>) -> Result<PendingApproval, String> {
+    admin::require(&state, action.scope())?;
+    let conn = open_db("admin_logs.db");
+    let id = nanoid::nanoid!(16);
+    let params_hash = consent::params_hash(params.as_ref());
+    let expires_at = now_secs() + APPROVAL_TTL_SECS;
+    let approval = PendingApproval {
+        statement: format!("promptpilot-admin-approval:v1:{id}:{}:{params_hash}:{expires_at}", action.as_str()),
+        id, action, params_hash, expires_at,
+        required: effective_quorum(&conn, action)?,
+        approved_by: Vec::new(),
+    };
+    let mut pending = state.approvals.lock().unwrap();
+    pending.retain(|_, a| a.expires_at > now_secs());
+    pending.insert(approval.id.clone(), approval.clone());
+    Ok(approval)
+}
+
+/// Add one key holder's signature over the approval statement.
+#[tauri::command]
+pub fn admin_approval_submit(id: String, signature_b64: String, state: tauri::State<AppState>) -> Result<PendingApproval, String> {
+    let mut pending = state.approvals.lock().unwrap();
+    let approval = pending.get_mut(&id).ok_or_else(|| format!("no pending approval {id}"))?;
+    if now_secs() >= approval.expires_at { return Err("approval request has expired".into()); }
+    let key = find_signer(approval.statement.as_bytes(), &signature_b64)?;
+    if !key.scopes.contains(&approval.action.scope()) {
+        return Err(format!("admin key \"{}\" lacks the {} scope", key.label, approval.action.scope().as_str()));
+    }
+    if approval.approved_by.contains(&key.id) { return Err(format!("admin key \"{}\" has already approved", key.label)); }
+    approval.approved_by.push(key.id.clone());
+    logs::record(&AuditEvent::ApprovalGranted { approval_id: id, action: approval.action.as_str().into(), key_id: key.id })?;
+    Ok(approval.clone())
+}
+
+/// Guard for destructive actions. With an effective quorum of 1 the admin session alone
+/// suffices; otherwise `approval_id` must name a completed approval for exactly `action`
+/// and `params`. The approval is used up either way.
+pub fn require_approval(state: &AppState, approval_id: Option<&str>, action: ApprovalAction, params: Option<&Value>) -> Result<(), String> {
+    let conn = open_db("admin_logs.db");
+    let required = effective_quorum(&conn, action)?;
+    if required <= 1 && approval_id.is_none() { return Ok(()); }
+    let id = approval_id.ok_or_else(|| format!("{} needs approval from {required} admin keys", action.as_str()))?;
+    let approval = state.approvals.lock().unwrap().remove(id).ok_or_else(|| format!("no pending approval {id}"))?;
+    if approval.action != action { return Err(format!("approval {id} is for {}, not {}", approval.action.as_str(), action.as_str())); }
+    if approval.params_hash != consent::params_hash(params) { return Err(format!("approval {id} was signed for different parameters")); }
+    if now_secs() >= approval.expires_at { return Err(format!("approval {id} has expired")); }
+    if (approval.approved_by.len() as u32) < required {
+        return Err(format!("approval {id} has {} of {required} required signatures", approval.approved_by.len()));
+    }
+    Ok(())
+}
+
+// ── Registry commands ────────────────────────────────────────────────────────
+#[tauri::command]
+pub fn admin_keys_list(state: tauri::State<AppState>) -> Result<Vec<AdminKey>, String> {
+    admin::require(&state, Scope::KeysManage)?;
+    list(&open_db("admin_logs.db"))
+}
+
+#[derive(Serialize)]
+pub struct RegistryStatus {
+    pub keys:   Vec<AdminKey>,
+    pub quorum: u32,
+}
+
+#[tauri::command]
+pub fn admin_keys_status(state: tauri::State<AppState>) -> Result<RegistryStatus, String> {
+    admin::require(&state, Scope::KeysManage)?;
+    let conn = open_db("admin_logs.db");
+    Ok(RegistryStatus { keys: list(&conn)?, quorum: quorum(&conn)? })
+}
+
+/// Register another admin key. `scopes` takes scope names or the `admin` / `proctor` presets.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub fn admin_keys_add(label: String, pubkey_b64: String, scopes: Vec<String>, valid_from: Option<u64>, valid_until: Option<u64>, approval_id: Option<String>, state: tauri::State<AppState>) -> Result<AdminKey, String> {
+    admin::require(&state, Scope::KeysManage)?;
+    let params = serde_json::json!({ "label": label, "pubkey_b64": pubkey_b64, "scopes": scopes, "valid_from": valid_from, "valid_until": valid_until });
+    // Validate before the approval is used up, so a bad request does not waste its signatures
+    if label.trim().is_empty() { return Err("label is required".into()); }
+    let key = parse_pubkey(&pubkey_b64)?;
+    let scopes = Scope::resolve(&scopes)?;
+    let valid_from = valid_from.unwrap_or_else(now_secs);
+    if valid_until.is_some_and(|u| u <= valid_from) { return Err("valid_until must be after valid_from".into()); }
+    let conn = open_db("admin_logs.db");
+    let id = fingerprint(&key);
+    if list(&conn)?.iter().any(|k| k.id == id || k.pubkey_b64 == base64::encode(key.as_bytes())) {
+        return Err("this key is already registered".into());
+    }
+    require_approval(&state, approval_id.as_deref(), ApprovalAction::AddKey, Some(&params))?;
+
+    conn.execute(
+        "INSERT INTO admin_keys(id, label, pubkey, scopes, valid_from, valid_until) VALUES(?1, ?2, ?3, ?4, ?5, ?6)",
+        params![id, label.trim(), base64::encode(key.as_bytes()), admin::scope_list(&scopes), valid_from as i64, valid_until.map(|u| u as i64)],
+    ).map_err(|e| if e.to_string().contains("UNIQUE") { "this key is already registered".to_string() } else { e.to_string() })?;
+    logs::record(&AuditEvent::AdminKeyAdded { key_id: id.clone(), label: label.trim().into(), scopes: admin::scope_list(&scopes) })?;
+    list(&conn)?.into_iter().find(|k| k.id == id).ok_or_else(|| "key vanished after insert".into())
+}
+
+/// Revoke a key. The last active key holding `keys.manage` cannot be revoked, nor one that
+/// would leave fewer active holders of `history.manage` or `keys.manage` than the quorum
+/// (lower the quorum first).
+#[tauri::command]
+pub fn admin_keys_revoke(key_id: String, reason: String, approval_id: Option<String>, state: tauri::State<AppState>) -> Result<(), String> {
+    admin::require(&state, Scope::KeysManage)?;
+    let params = serde_json::json!({ "key_id": key_id, "reason": reason });
+    let conn = open_db("admin_logs.db");
+    let now = now_secs();
+    let keys = list(&conn)?;
+    if !keys.iter().any(|k| k.id == key_id && k.revoked_at.is_none()) { return Err(format!("no active admin key {key_id}")); }
+    if holders(&keys, Scope::KeysManage, now, Some(&key_id)) == 0 {
+        return Err("cannot revoke the last key that can manage admin keys".into());
+    }
+    let required = quorum(&conn)?;
+    for scope in APPROVAL_SCOPES {
+        let remaining = holders(&keys, scope, now, Some(&key_id));
+        if remaining < required && holders(&keys, scope, now, None) > remaining {
+            return Err(format!(
+                "revoking this key would leave {remaining} active keys with {}, below the quorum of {required}; lower the quorum first",
+                scope.as_str(),
+            ));
+        }
+    }
+    // Checked first so a refused revocation does not use up the approval
+    require_approval(&state, approval_id.as_deref(), ApprovalAction::RevokeKey, Some(&params))?;
+    conn.execute("UPDATE admin_keys SET revoked_at=?2, revoke_reason=?3 WHERE id=?1", params![key_id, now as i64, reason])
+        .map_err(|e| e.to_string())?;
+    // A session unlocked with the revoked key ends now
+    admin::end_sessions_of(&state, &key_id);
+    logs::record(&AuditEvent::AdminKeyRevoked { key_id, reason })?;
+    Ok(())
+}
+
+/// Number of distinct admin keys that must approve destructive actions (1 = no approval step).
+#[tauri::command]
+pub fn admin_keys_set_quorum(required: u32, approval_id: Option<String>, state: tauri::State<AppState>) -> Result<u32, String> {
+    admin::require(&state, Scope::KeysManage)?;
+    let conn = open_db("admin_logs.db");
+    let (keys, now) = (list(&conn)?, now_secs());
+    let available = APPROVAL_SCOPES.iter().map(|&s| holders(&keys, s, now, None)).min().unwrap_or(0);
+    if required == 0 || required > available {
+        return Err(format!("quorum must be between 1 and {available}, the fewest active keys holding history.manage or keys.manage"));
+    }
+    require_approval(&state, approval_id.as_deref(), ApprovalAction::SetQuorum, Some(&serde_json::json!({ "required": required })))?;
+    conn.execute("INSERT OR REPLACE INTO admin_key_settings(name, value) VALUES('quorum', ?1)", params![required])
+        .map_err(|e| e.to_string())?;
+    logs::record(&AuditEvent::ApprovalQuorumChanged { required })?;
+    Ok(required)
+}
@@ -0,0 +1,198 @@
+
+//! Signed audit-log checkpoints.
+//!
+//! A checkpoint anchors the hash chain: an admin signs `(entry id, head hash, timestamp)`
+//! with a registered admin key (see `admin_keys.rs`), so rewriting the log up to that entry
+//! would also require forging the signature. The flow mirrors `admin_get_nonce`/`admin_unlock`:
+//! `logs_checkpoint_prepare` returns the statement, the admin signs it offline, and
+//! `logs_checkpoint_submit` verifies and stores it in `log_checkpoints`.
+
+use crate::admin::{self, Scope};
+use crate::admin_keys;
+use crate::logs;
+use crate::security::{open_db, AppState};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+/// The exact bytes the admin signs. Plain text so any Ed25519 tool can sign/verify it.
+pub fn statement(entry_id: i64, head_hash: &str, ts: u64) -> String {
+    format!("promptpilot-log-checkpoint:v1:{entry_id}:{head_hash}:{ts}")
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Checkpoint {
+    pub entry_id:  i64,
+    pub head_hash: String,
+    pub ts:        u64,
+}
+
+impl Checkpoint {
+    pub fn statement(&self) -> String { statement(self.entry_id, &self.head_hash, self.ts) }
+}
+
+/// A stored checkpoint, self-contained so an outside party can check it against an exported log.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SignedCheckpoint {
+    pub id:            i64,
+    pub entry_id:      i64,
+    pub head_hash:     String,
+    pub ts:            u64,
+    pub statement:     String,
+    pub signature_b64: String,
+    pub pubkey_b64:    String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct CheckpointCheck {
+    pub id:       i64,
+    pub entry_id: i64,
+    pub ok:       bool,
+    pub reason:   Option<String>,
+}
+
+pub fn ensure_checkpoints_table(conn: &rusqlite::Connection) -> Result<(), String> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS log_checkpoints(id INTEGER PRIMARY KEY AUTOINCREMENT, entry_id INTEGER NOT NULL, head_hash TEXT NOT NULL, ts INTEGER NOT NULL, signature TEXT NOT NULL, pubkey TEXT NOT NULL)",
+        []
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn verify_signature(pubkey: &VerifyingKey, statement: &str, signature_b64: &str) -> Result<(), String> {
+    let sig_bytes = base64::decode(signature_b64).map_err(|e| e.to_string())?;
+    let sig = Signature::from_slice(&sig_bytes).map_err(|e| e.to_string())?;
+    pubkey.verify(statement.as_bytes(), &sig).map_err(|_| "signature does not verify".to_string())
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+#[derive(Serialize)]
+pub struct CheckpointRequest {
+    pub checkpoint:         Checkpoint,
+    /// Sign these bytes with the admin key (Ed25519, base64 signature).
+    pub statement:          String,
+    pub entries_since_last: i64,
+}
+
+/// Snapshot the current head for the admin to sign.
+#[tauri::command]
+pub fn logs_checkpoint_prepare(state: tauri::State<AppState>) -> Result<CheckpointRequest, String> {
+    admin::require(&state, Scope::LogsCheckpoint)?;
+    let conn = open_db("admin_logs.db");
+    logs::ensure_logs_table(&conn)?;
+    ensure_checkpoints_table(&conn)?;
+    let (entry_id, head_hash): (i64, String) = conn.query_row(
+        "SELECT id, hash_curr FROM logs ORDER BY id DESC LIMIT 1", [], |r| Ok((r.get(0)?, r.get(1)?))
+    ).map_err(|_| "the audit log is empty".to_string())?;
+    let last: i64 = conn.query_row("SELECT COALESCE(MAX(entry_id), 0) FROM log_checkpoints", [], |r| r.get(0))
+        .map_err(|e| e.to_string())?;
+    let checkpoint = Checkpoint { entry_id, head_hash, ts: now_secs() };
+    let statement = checkpoint.statement();
+    *state.pending_checkpoint.lock().unwrap() = Some(checkpoint.clone());
+    Ok(CheckpointRequest { checkpoint, statement, entries_since_last: entry_id - last })
+}
+
+/// Verify the admin's signature over the prepared statement and store the checkpoint.
+#[tauri::command]
+pub fn logs_checkpoint_submit(signature_b64: String, state: tauri::State<AppState>) -> Result<SignedCheckpoint, String> {
+    admin::require(&state, Scope::LogsCheckpoint)?;
+    let checkpoint = state.pending_checkpoint.lock().unwrap().clone().ok_or("no pending checkpoint")?;
+    let statement = checkpoint.statement();
+    let key = admin_keys::find_signer(statement.as_bytes(), &signature_b64)?;
+    if !key.scopes.contains(&Scope::LogsCheckpoint) {
+        return Err(format!("admin key \"{}\" may not sign checkpoints", key.label));
+    }
+
+    let conn = open_db("admin_logs.db");
+    ensure_checkpoints_table(&conn)?;
+    let pubkey_b64 = key.pubkey_b64;
+    conn.execute(
+        "INSERT INTO log_checkpoints(entry_id, head_hash, ts, signature, pubkey) VALUES(?1, ?2, ?3, ?4, ?5)",
+        params![checkpoint.entry_id, checkpoint.head_hash, checkpoint.ts as i64, signature_b64, pubkey_b64],
+    ).map_err(|e| e.to_string())?;
+    *state.pending_checkpoint.lock().unwrap() = None;
+    Ok(SignedCheckpoint {
+        id: conn.last_insert_rowid(),
+        entry_id: checkpoint.entry_id,
+        head_hash: checkpoint.head_hash,
+        ts: checkpoint.ts,
+        statement,
+        signature_b64,
+        pubkey_b64,
+    })
+}
+
+pub fn list(conn: &rusqlite::Connection, from: i64, to: i64) -> Result<Vec<SignedCheckpoint>, String> {
+    ensure_checkpoints_table(conn)?;
+    let mut stmt = conn.prepare(
+        "SELECT id, entry_id, head_hash, ts, signature, pubkey FROM log_checkpoints WHERE entry_id BETWEEN ?1 AND ?2 ORDER BY entry_id"
+    ).map_err(|e| e.to_string())?;
+    let rows: Result<Vec<SignedCheckpoint>, _> = stmt.query_map(params![from, to], |r| {
+        let (entry_id, head_hash, ts): (i64, String, i64) = (r.get(1)?, r.get(2)?, r.get(3)?);
+        Ok(SignedCheckpoint {
+            id: r.get(0)?,
+            statement: statement(entry_id, &head_hash, ts as u64),
+            entry_id, head_hash, ts: ts as u64,
+            signature_b64: r.get(4)?,
+            pubkey_b64: r.get(5)?,
+        })
+    }).map_err(|e| e.to_string())?
+      .collect::<Result<Vec<_>, _>>();
+    rows.map_err(|e| e.to_string())
+}
+
+/// Check one checkpoint: the signature must verify with one of the `trusted` admin keys and
+/// `stored_head` — the log's `hash_curr` at `cp.entry_id` — must equal the signed head.
+pub fn check(cp: &SignedCheckpoint, trusted: &Result<Vec<VerifyingKey>, String>, stored_head: Option<&str>) -> CheckpointCheck {
+    // Rebuild the statement from the fields rather than trusting the stored/exported text
+    let statement = statement(cp.entry_id, &cp.head_hash, cp.ts);
+    let signer = trusted.as_ref().map(|keys| keys.iter().find(|k| base64::encode(k.as_bytes()) == cp.pubkey_b64));
+    let reason = match signer {
+        Err(e) => Some(format!("cannot check signature: {e}")),
+        Ok(None) => Some("signed by a key that is not a trusted admin key".into()),
+        Ok(Some(key)) => match verify_signature(key, &statement, &cp.signature_b64) {
+            Err(e) => Some(e),
+            Ok(()) => match stored_head {
+                None => Some(format!("entry #{} is missing", cp.entry_id)),
+                Some(h) if h != cp.head_hash => Some(format!("entry #{} no longer matches the signed head", cp.entry_id)),
+                Some(_) => None,
+            },
+        },
+    };
+    CheckpointCheck { id: cp.id, entry_id: cp.entry_id, ok: reason.is_none(), reason }
+}
+
+/// Check every checkpoint whose entry falls in `from..=to` against the live log.
+pub fn verify_range(conn: &rusqlite::Connection, from: i64, to: i64) -> Result<Vec<CheckpointCheck>, String> {
+    let admin_keys = admin_keys::trusted_keys();
+    let mut out = Vec::new();
+    for cp in list(conn, from, to)? {
+        let stored: Option<String> = conn.query_row(
+            "SELECT hash_curr FROM logs WHERE id=?1", params![cp.entry_id], |r| r.get(0)
+        ).ok();
+        out.push(check(&cp, &admin_keys, stored.as_deref()));
+    }
+    Ok(out)
+}
+
+#[tauri::command]
+pub fn logs_checkpoints(state: tauri::State<AppState>) -> Result<Vec<SignedCheckpoint>, String> {
+    admin::require(&state, Scope::LogsRead)?;
+    let conn = open_db("admin_logs.db");
+    list(&conn, i64::MIN, i64::MAX)
+}
+
+/// Export one checkpoint as JSON. Together with the log entries `1..=entry_id`, it lets an
+/// outside party confirm that prefix is untouched: verify the signature over `statement`
+/// with `pubkey_b64`, then recompute the chain from `GENESIS` and compare with `head_hash`.
+#[tauri::command]
+pub fn logs_export_checkpoint(id: i64, state: tauri::State<AppState>) -> Result<String, String> {
+    admin::require(&state, Scope::LogsRead)?;
+    let conn = open_db("admin_logs.db");
+    let cp = list(&conn, i64::MIN, i64::MAX)?.into_iter().find(|c| c.id == id)
+        .ok_or_else(|| format!("no checkpoint with id {id}"))?;
+    serde_json::to_string_pretty(&cp).map_err(|e| e.to_string())
+}
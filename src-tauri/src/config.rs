@@ -0,0 +1,361 @@
+
+//! App configuration — `app.yaml` in the data dir, validated against `SCHEMA`.
+//! A commented default file is written on first start; `config/app.yaml` in the repo mirrors it.
+
+use crate::admin::{self, Scope};
+use crate::envelope::KdfParams;
+use crate::llm::{self, BackendKind};
+use crate::rag::{ChunkStrategy, RetrievalMode, SearchMode};
+use crate::logs::{self, AuditEvent};
+use crate::session;
+use crate::security::{data_dir, AppState};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use yaml_rust::{Yaml, YamlLoader};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LlmConfig {
+    pub backend:            BackendKind,
+    pub base_url:           String,
+    pub api_key:            Option<String>,
+    pub gen_timeout_secs:   u64,
+    pub embed_timeout_secs: u64,
+    pub gen_model:          String,
+    pub embed_model:        String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RagConfig {
+    pub chunk_strategy: ChunkStrategy,
+    /// Token budget per chunk for the `structured` and `sentence` strategies.
+    pub chunk_tokens:   usize,
+    /// Window and overlap in characters for the `fixed` strategy.
+    pub chunk_size:     usize,
+    pub chunk_overlap:  usize,
+    pub top_k:          u32,
+    /// `ann` searches the HNSW index, `exact` scans every embedding.
+    pub search:         SearchMode,
+    /// HNSW search beam; larger is slower and closer to exact.
+    pub ef_search:      usize,
+    /// `hybrid` fuses vector and BM25 rankings; `vector` / `lexical` use one of them.
+    pub retrieval:      RetrievalMode,
+    /// Weights of the vector and BM25 rankings in hybrid fusion.
+    pub vector_weight:  f32,
+    pub lexical_weight: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SessionConfig {
+    /// Unlocked profile keys are dropped after this long without use.
+    pub idle_timeout_secs: u64,
+    /// Admin sessions lock after this long without an admin command.
+    pub admin_idle_timeout_secs: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AppConfig {
+    pub privacy_mode: bool,
+    pub llm:          LlmConfig,
+    pub rag:          RagConfig,
+    /// Argon2id cost for new/changed profile passphrases.
+    pub kdf:          KdfParams,
+    pub session:      SessionConfig,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            privacy_mode: true,
+            llm: LlmConfig {
+                backend:            BackendKind::Ollama,
+                base_url:           BackendKind::Ollama.default_url().into(),
+                api_key:            None,
+                gen_timeout_secs:   300,
+                embed_timeout_secs: 30,
+                gen_model:          "mistral".into(),
+                embed_model:        "nomic-embed-text".into(),
+            },
+            rag: RagConfig {
+                chunk_strategy: ChunkStrategy::Structured,
+                chunk_tokens:   200,
+                chunk_size:     500,
+                chunk_overlap:  80,
+                top_k:          5,
+                search:         SearchMode::Ann,
+                ef_search:      64,
+                retrieval:      RetrievalMode::Hybrid,
+                vector_weight:  1.0,
+                lexical_weight: 1.0,
+            },
+            kdf: KdfParams::default(),
+            session: SessionConfig { idle_timeout_secs: 900, admin_idle_timeout_secs: 600 },
+        }
+    }
+}
+
+// ── Schema ───────────────────────────────────────────────────────────────────
+enum Kind {
+    Bool,
+    Str,
+    OptStr,
+    Url,
+    Enum(&'static [&'static str]),
+    Int { min: i64, max: i64 },
+    Float { min: f64, max: f64 },
+}
+
+struct Field { path: &'static str, kind: Kind }
+
+const SCHEMA: &[Field] = &[
+    Field { path: "privacy_mode",           kind: Kind::Bool },
+    Field { path: "llm.backend",            kind: Kind::Enum(&["ollama", "llamacpp", "openai"]) },
+    Field { path: "llm.base_url",           kind: Kind::Url },
+    Field { path: "llm.api_key",            kind: Kind::OptStr },
+    Field { path: "llm.gen_timeout_secs",   kind: Kind::Int { min: 5, max: 3600 } },
+    Field { path: "llm.embed_timeout_secs", kind: Kind::Int { min: 1, max: 600 } },
+    Field { path: "llm.gen_model",          kind: Kind::Str },
+    Field { path: "llm.embed_model",        kind: Kind::Str },
+    Field { path: "rag.chunk_strategy",     kind: Kind::Enum(&["structured", "sentence", "fixed"]) },
+    Field { path: "rag.chunk_tokens",       kind: Kind::Int { min: 32, max: 4096 } },
+    Field { path: "rag.chunk_size",         kind: Kind::Int { min: 100, max: 8000 } },
+    Field { path: "rag.chunk_overlap",      kind: Kind::Int { min: 0, max: 2000 } },
+    Field { path: "rag.top_k",              kind: Kind::Int { min: 1, max: 20 } },
+    Field { path: "rag.search",             kind: Kind::Enum(&["ann", "exact"]) },
+    Field { path: "rag.ef_search",          kind: Kind::Int { min: 10, max: 2000 } },
+    Field { path: "rag.retrieval",          kind: Kind::Enum(&["hybrid", "vector", "lexical"]) },
+    Field { path: "rag.vector_weight",      kind: Kind::Float { min: 0.0, max: 10.0 } },
+    Field { path: "rag.lexical_weight",     kind: Kind::Float { min: 0.0, max: 10.0 } },
+    Field { path: "kdf.memory_kib",         kind: Kind::Int { min: 8192, max: 1048576 } },
+    Field { path: "kdf.iterations",         kind: Kind::Int { min: 1, max: 20 } },
+    Field { path: "kdf.parallelism",        kind: Kind::Int { min: 1, max: 16 } },
+    Field { path: "session.idle_timeout_secs", kind: Kind::Int { min: 60, max: 86400 } },
+    Field { path: "session.admin_idle_timeout_secs", kind: Kind::Int { min: 60, max: 86400 } },
+];
+
+fn lookup<'a>(doc: &'a Yaml, path: &str) -> &'a Yaml {
+    path.split('.').fold(doc, |node, key| &node[key])
+}
+
+fn check_field(value: &Yaml, field: &Field) -> Result<(), String> {
+    let p = field.path;
+    match (&field.kind, value) {
+        (Kind::Bool, Yaml::Boolean(_)) => Ok(()),
+        (Kind::Str, Yaml::String(s)) if !s.trim().is_empty() => Ok(()),
+        (Kind::OptStr, Yaml::String(_) | Yaml::Null) => Ok(()),
+        (Kind::Url, Yaml::String(s)) if s.starts_with("http://") || s.starts_with("https://") => Ok(()),
+        (Kind::Url, _) => Err(format!("{p}: must be an http:// or https:// URL")),
+        (Kind::Enum(opts), Yaml::String(s)) if opts.contains(&s.as_str()) => Ok(()),
+        (Kind::Enum(opts), _) => Err(format!("{p}: must be one of {}", opts.join(", "))),
+        (Kind::Int { min, max }, Yaml::Integer(n)) if n >= min && n <= max => Ok(()),
+        (Kind::Int { min, max }, _) => Err(format!("{p}: must be an integer in {min}..={max}")),
+        (Kind::Float { min, max }, v) if v.as_f64().or(v.as_i64().map(|n| n as f64)).is_some_and(|x| x >= *min && x <= *max) => Ok(()),
+        (Kind::Float { min, max }, _) => Err(format!("{p}: must be a number in {min}..={max}")),
+        (Kind::Bool, _) => Err(format!("{p}: must be true or false")),
+        (Kind::Str, _) => Err(format!("{p}: must be a non-empty string")),
+        (Kind::OptStr, _) => Err(format!("{p}: must be a string or null")),
+    }
+}
+
+/// Keys present in the document but absent from `SCHEMA` (catches typos like `llm.base_ulr`).
+fn unknown_keys(node: &Yaml, prefix: &str, out: &mut Vec<String>) {
+    if let Yaml::Hash(map) = node {
+        for (k, v) in map {
+            let Some(k) = k.as_str() else { continue };
+            let path = if prefix.is_empty() { k.to_string() } else { format!("{prefix}.{k}") };
+            let known_leaf   = SCHEMA.iter().any(|f| f.path == path);
+            let known_parent = SCHEMA.iter().any(|f| f.path.starts_with(&format!("{path}.")));
+            if known_parent { unknown_keys(v, &path, out); }
+            else if !known_leaf { out.push(format!("{path}: unknown key")); }
+        }
+    }
+}
+
+/// Validate a parsed document. Missing keys fall back to defaults; present ones must match the schema.
+fn validate(doc: &Yaml) -> Result<(), String> {
+    let mut errors = Vec::new();
+    for field in SCHEMA {
+        let value = lookup(doc, field.path);
+        if matches!(value, Yaml::BadValue) { continue; }
+        if let Err(e) = check_field(value, field) { errors.push(e); }
+    }
+    unknown_keys(doc, "", &mut errors);
+    if errors.is_empty() { Ok(()) } else { Err(format!("Invalid config: {}", errors.join("; "))) }
+}
+
+impl AppConfig {
+    /// Build from a validated document, taking defaults for anything missing.
+    fn from_yaml(doc: &Yaml) -> Result<AppConfig, String> {
+        validate(doc)?;
+        let mut c = AppConfig::default();
+        let str_at = |p: &str| lookup(doc, p).as_str().map(str::to_string);
+        let int_at = |p: &str| lookup(doc, p).as_i64();
+        let num_at = |p: &str| { let v = lookup(doc, p); v.as_f64().or(v.as_i64().map(|n| n as f64)) };
+
+        if let Some(b) = lookup(doc, "privacy_mode").as_bool() { c.privacy_mode = b; }
+        if let Some(b) = str_at("llm.backend") {
+            c.llm.backend = serde_json::from_value(serde_json::Value::String(b)).map_err(|e| e.to_string())?;
+            c.llm.base_url = c.llm.backend.default_url().into();
+        }
+        if let Some(v) = str_at("llm.base_url")           { c.llm.base_url = v.trim_end_matches('/').into(); }
+        if let Some(v) = str_at("llm.api_key")            { c.llm.api_key = Some(v).filter(|k| !k.is_empty()); }
+        if let Some(v) = int_at("llm.gen_timeout_secs")   { c.llm.gen_timeout_secs = v as u64; }
+        if let Some(v) = int_at("llm.embed_timeout_secs") { c.llm.embed_timeout_secs = v as u64; }
+        if let Some(v) = str_at("llm.gen_model")          { c.llm.gen_model = v; }
+        if let Some(v) = str_at("llm.embed_model")        { c.llm.embed_model = v; }
+        if let Some(v) = str_at("rag.chunk_strategy") {
+            c.rag.chunk_strategy = serde_json::from_value(serde_json::Value::String(v)).map_err(|e| e.to_string())?;
+        }
+        if let Some(v) = int_at("rag.chunk_tokens")       { c.rag.chunk_tokens = v as usize; }
+        if let Some(v) = int_at("rag.chunk_size")         { c.rag.chunk_size = v as usize; }
+        if let Some(v) = int_at("rag.chunk_overlap")      { c.rag.chunk_overlap = v as usize; }
+        if let Some(v) = int_at("rag.top_k")              { c.rag.top_k = v as u32; }
+        if let Some(v) = str_at("rag.search") {
+            c.rag.search = serde_json::from_value(serde_json::Value::String(v)).map_err(|e| e.to_string())?;
+        }
+        if let Some(v) = int_at("rag.ef_search")          { c.rag.ef_search = v as usize; }
+        if let Some(v) = str_at("rag.retrieval") {
+            c.rag.retrieval = serde_json::from_value(serde_json::Value::String(v)).map_err(|e| e.to_string())?;
+        }
+        if let Some(v) = num_at("rag.vector_weight")      { c.rag.vector_weight = v as f32; }
+        if let Some(v) = num_at("rag.lexical_weight")     { c.rag.lexical_weight = v as f32; }
+        if let Some(v) = int_at("kdf.memory_kib")         { c.kdf.memory_kib = v as u32; }
+        if let Some(v) = int_at("kdf.iterations")         { c.kdf.iterations = v as u32; }
+        if let Some(v) = int_at("kdf.parallelism")        { c.kdf.parallelism = v as u32; }
+        if let Some(v) = int_at("session.idle_timeout_secs") { c.session.idle_timeout_secs = v as u64; }
+        if let Some(v) = int_at("session.admin_idle_timeout_secs") { c.session.admin_idle_timeout_secs = v as u64; }
+
+        if c.rag.chunk_overlap >= c.rag.chunk_size {
+            return Err("Invalid config: rag.chunk_overlap must be smaller than rag.chunk_size".into());
+        }
+        if c.rag.vector_weight == 0.0 && c.rag.lexical_weight == 0.0 {
+            return Err("Invalid config: rag.vector_weight and rag.lexical_weight cannot both be 0".into());
+        }
+        Ok(c)
+    }
+
+    fn to_yaml_string(&self) -> String {
+        // JSON string literals are valid YAML double-quoted scalars
+        let q = |s: &str| serde_json::to_string(s).unwrap_or_default();
+        let backend = serde_json::to_value(self.llm.backend).ok()
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_default();
+        let strategy = serde_json::to_value(self.rag.chunk_strategy).ok()
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_default();
+        let search = serde_json::to_value(self.rag.search).ok()
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_default();
+        let retrieval = serde_json::to_value(self.rag.retrieval).ok()
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_default();
+        format!(
+            "# PromptPilot configuration — edit while the app is closed, or use Settings.\n\
+             privacy_mode: {privacy}\n\
+             \n\
+             llm:\n\
+             \x20 backend: {backend}            # ollama | llamacpp | openai\n\
+             \x20 base_url: {base_url}\n\
+             \x20 api_key: {api_key}\n\
+             \x20 gen_timeout_secs: {gen_to}\n\
+             \x20 embed_timeout_secs: {embed_to}\n\
+             \x20 gen_model: {gen_model}\n\
+             \x20 embed_model: {embed_model}\n\
+             \n\
+             rag:\n\
+             \x20 chunk_strategy: {strategy}  # structured | sentence | fixed\n\
+             \x20 chunk_tokens: {chunk_tokens}         # token budget per chunk (structured, sentence)\n\
+             \x20 chunk_size: {chunk}           # characters per chunk (fixed)\n\
+             \x20 chunk_overlap: {overlap}\n\
+             \x20 top_k: {top_k}\n\
+             \x20 search: {search}               # ann (HNSW index) | exact\n\
+             \x20 ef_search: {ef_search}            # HNSW search beam; higher is slower and closer to exact\n\
+             \x20 retrieval: {retrieval}          # hybrid (vector + BM25) | vector | lexical (no embedding model needed)\n\
+             \x20 vector_weight: {vw:?}         # weights of the two rankings in hybrid fusion\n\
+             \x20 lexical_weight: {lw:?}\n\
+             \n\
+             kdf:                       # Argon2id cost for profile passphrases\n\
+             \x20 memory_kib: {kdf_m}\n\
+             \x20 iterations: {kdf_t}\n\
+             \x20 parallelism: {kdf_p}\n\
+             \n\
+             session:\n\
+             \x20 idle_timeout_secs: {idle}     # lock the unlocked profile after this long unused\n\
+             \x20 admin_idle_timeout_secs: {admin_idle}  # lock admin mode after this long without admin commands\n",
+            privacy     = self.privacy_mode,
+            base_url    = q(&self.llm.base_url),
+            api_key     = self.llm.api_key.as_deref().map(q).unwrap_or_else(|| "null".into()),
+            gen_to      = self.llm.gen_timeout_secs,
+            embed_to    = self.llm.embed_timeout_secs,
+            gen_model   = q(&self.llm.gen_model),
+            embed_model = q(&self.llm.embed_model),
+            chunk_tokens = self.rag.chunk_tokens,
+            chunk       = self.rag.chunk_size,
+            overlap     = self.rag.chunk_overlap,
+            top_k       = self.rag.top_k,
+            ef_search   = self.rag.ef_search,
+            vw          = self.rag.vector_weight,
+            lw          = self.rag.lexical_weight,
+            kdf_m       = self.kdf.memory_kib,
+            kdf_t       = self.kdf.iterations,
+            kdf_p       = self.kdf.parallelism,
+            idle        = self.session.idle_timeout_secs,
+            admin_idle  = self.session.admin_idle_timeout_secs,
+        )
+    }
+}
+
+pub fn config_path() -> PathBuf { data_dir().join("app.yaml") }
+
+fn parse(text: &str) -> Result<AppConfig, String> {
+    let docs = YamlLoader::load_from_str(text).map_err(|e| format!("app.yaml: {}", e))?;
+    match docs.first() {
+        Some(doc) => AppConfig::from_yaml(doc),
+        None      => Ok(AppConfig::default()),
+    }
+}
+
+fn save(config: &AppConfig) -> Result<(), String> {
+    let path = config_path();
+    std::fs::create_dir_all(path.parent().unwrap()).map_err(|e| e.to_string())?;
+    std::fs::write(path, config.to_yaml_string()).map_err(|e| e.to_string())
+}
+
+/// Load `app.yaml`, writing the defaults on first start.
+pub fn load_config() -> Result<AppConfig, String> {
+    let path = config_path();
+    if !path.exists() {
+        let config = AppConfig::default();
+        save(&config)?;
+        return Ok(config);
+    }
+    let text = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    parse(&text)
+}
+
+#[tauri::command]
+pub fn get_config(state: tauri::State<AppState>) -> Result<AppConfig, String> {
+    Ok(state.config.lock().unwrap().clone())
+}
+
+/// Validate, persist and apply a new configuration. Changing `privacy_mode`, `kdf` or
+/// pointing `llm.base_url` off this machine needs the `policy.manage` admin scope.
+#[tauri::command]
+pub fn set_config(mut config: AppConfig, state: tauri::State<AppState>) -> Result<AppConfig, String> {
+    if config.llm.base_url.trim().is_empty() {
+        config.llm.base_url = config.llm.backend.default_url().into();
+    }
+    // Round-trip through YAML so the frontend goes through the same schema as the file
+    let checked = parse(&config.to_yaml_string())?;
+    // Changes that loosen the privacy posture or the key-stretching cost are policy decisions
+    let current = state.config.lock().unwrap().clone();
+    if checked.privacy_mode != current.privacy_mode
+        || checked.kdf != current.kdf
+        || (checked.llm.base_url != current.llm.base_url && !llm::is_loopback(&checked.llm.base_url))
+    {
+        admin::require(&state, Scope::PolicyManage)?;
+    }
+    save(&checked)?;
+    *state.config.lock().unwrap() = checked.clone();
+    logs::record(&AuditEvent::SettingsChanged { setting: "app.yaml".into(), user_id: session::current_user(&state) })?;
+    Ok(checked)
+}
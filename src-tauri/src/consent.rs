@@ -0,0 +1,164 @@
+
+//! Action-bound user consent.
+//!
+//! The user signs a canonical `ConsentPayload` — action, hash of the action's parameters,
+//! user id, nonce and expiry — rather than the bare nonce, so a signature authorizes exactly
+//! one action with exactly those parameters. A verified signature becomes a `ConsentReceipt`
+//! in `consent_receipts` (admin_logs.db) and in the audit log; each consent-gated admin
+//! command takes the receipt id and redeems it with `redeem` for its own action and
+//! parameters, once, before it expires.
+
+use crate::admin::{self, Scope};
+use crate::logs::{self, AuditEvent};
+use crate::nonce::{self, Nonce};
+use crate::policy;
+use crate::security::{open_db, AppState};
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+const DOMAIN: &str = "promptpilot-user-consent:v1";
+
+/// Consent-gated admin actions, as named in `request_user_consent`.
+pub const CLEAR_USER_HISTORY: &str = "clear_user_history";
+
+/// SHA-256 (hex) of the canonical JSON of `params` (`null` when absent).
+pub fn params_hash(params: Option<&Value>) -> String {
+    let canonical = params.unwrap_or(&Value::Null).to_string();
+    format!("{:x}", Sha256::digest(canonical.as_bytes()))
+}
+
+/// What the user signs. Serialized canonically (sorted keys, no whitespace).
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ConsentPayload {
+    pub domain:      String,
+    pub action:      String,
+    pub params_hash: String,
+    pub user_id:     String,
+    pub nonce:       String,
+    pub expires_at:  u64,
+}
+
+impl ConsentPayload {
+    pub fn for_nonce(n: &Nonce) -> Self {
+        ConsentPayload {
+            domain:      DOMAIN.into(),
+            action:      n.action.clone(),
+            params_hash: n.params_hash.clone(),
+            user_id:     n.subject.clone().unwrap_or_default(),
+            nonce:       n.value.clone(),
+            expires_at:  n.expires_at,
+        }
+    }
+
+    /// The exact bytes that are signed.
+    pub fn canonical(&self) -> String {
+        serde_json::to_value(self).map(|v| v.to_string()).unwrap_or_default()
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct ConsentReceipt {
+    pub id:            String,
+    pub user_id:       String,
+    pub action:        String,
+    pub params_hash:   String,
+    pub expires_at:    u64,
+    /// The signed canonical payload.
+    pub payload:       String,
+    pub signature_b64: String,
+    pub pubkey_b64:    String,
+    pub created_at:    String,
+    pub redeemed_at:   Option<String>,
+}
+
+pub fn ensure_receipts_table(conn: &rusqlite::Connection) -> Result<(), String> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS consent_receipts(id TEXT PRIMARY KEY, user_id TEXT NOT NULL, action TEXT NOT NULL, params_hash TEXT NOT NULL, expires_at INTEGER NOT NULL, payload TEXT NOT NULL, signature TEXT NOT NULL, pubkey TEXT NOT NULL, created_at DATETIME DEFAULT CURRENT_TIMESTAMP, redeemed_at DATETIME)",
+        []
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn row_to_receipt(r: &rusqlite::Row) -> rusqlite::Result<ConsentReceipt> {
+    Ok(ConsentReceipt {
+        id: r.get(0)?, user_id: r.get(1)?, action: r.get(2)?, params_hash: r.get(3)?,
+        expires_at: r.get::<_, i64>(4)? as u64, payload: r.get(5)?, signature_b64: r.get(6)?,
+        pubkey_b64: r.get(7)?, created_at: r.get(8)?, redeemed_at: r.get(9)?,
+    })
+}
+
+const COLUMNS: &str = "id, user_id, action, params_hash, expires_at, payload, signature, pubkey, COALESCE(created_at,''), redeemed_at";
+
+/// Store the receipt for a verified consent signature and record it in the audit log.
+pub fn issue_receipt(payload: &ConsentPayload, signature_b64: &str, pubkey_b64: &str) -> Result<ConsentReceipt, String> {
+    let conn = open_db("admin_logs.db");
+    ensure_receipts_table(&conn)?;
+    let id = nanoid::nanoid!(16);
+    let canonical = payload.canonical();
+    conn.execute(
+        "INSERT INTO consent_receipts(id, user_id, action, params_hash, expires_at, payload, signature, pubkey) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![id, payload.user_id, payload.action, payload.params_hash, payload.expires_at as i64, canonical, signature_b64, pubkey_b64],
+    ).map_err(|e| e.to_string())?;
+    logs::record(&AuditEvent::ConsentVerified {
+        user_id:       payload.user_id.clone(),
+        action:        payload.action.clone(),
+        receipt_id:    id.clone(),
+        params_hash:   payload.params_hash.clone(),
+        signature_b64: signature_b64.to_string(),
+    })?;
+    conn.query_row(&format!("SELECT {COLUMNS} FROM consent_receipts WHERE id=?1"), params![id], row_to_receipt)
+        .map_err(|e| e.to_string())
+}
+
+/// Consume receipt `id` for `action` on `user_id` with `params`: it must exist, have been
+/// signed by that user, match the action and parameters exactly, be unexpired and unused,
+/// and the `ADMIN_APPROVED_ACTION` policy must allow it.
+pub fn redeem(state: &AppState, id: &str, user_id: &str, action: &str, params: Option<&Value>) -> Result<ConsentReceipt, String> {
+    let conn = open_db("admin_logs.db");
+    ensure_receipts_table(&conn)?;
+    let receipt = conn.query_row(&format!("SELECT {COLUMNS} FROM consent_receipts WHERE id=?1"), params![id], row_to_receipt)
+        .optional().map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("no consent receipt {id}"))?;
+    let problem = if receipt.user_id != user_id {
+        Some(format!("receipt was signed by \"{}\", not \"{user_id}\"", receipt.user_id))
+    } else if receipt.action != action {
+        Some(format!("receipt is for \"{}\", not \"{action}\"", receipt.action))
+    } else if receipt.params_hash != params_hash(params) {
+        Some("receipt was signed for different parameters".to_string())
+    } else if receipt.redeemed_at.is_some() {
+        Some("receipt has already been used".to_string())
+    } else if nonce::now_secs() >= receipt.expires_at {
+        Some("receipt has expired".to_string())
+    } else { None };
+
+    let mut input = policy::input_for(state, policy::ADMIN_APPROVED_ACTION);
+    input.user_consent_valid = problem.is_none();
+    policy::enforce_input(state, &input).map_err(|d| match &problem {
+        Some(p) => format!("{d} ({p})"),
+        None    => d.to_string(),
+    })?;
+
+    // Guard on redeemed_at so two concurrent redemptions cannot both succeed
+    let updated = conn.execute(
+        "UPDATE consent_receipts SET redeemed_at=CURRENT_TIMESTAMP WHERE id=?1 AND redeemed_at IS NULL", params![id]
+    ).map_err(|e| e.to_string())?;
+    if updated == 0 { return Err("receipt has already been used".into()); }
+    logs::record(&AuditEvent::ConsentRedeemed { user_id: receipt.user_id.clone(), action: action.to_string(), receipt_id: id.to_string() })?;
+    conn.query_row(&format!("SELECT {COLUMNS} FROM consent_receipts WHERE id=?1"), params![id], row_to_receipt)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn consent_receipts(user_id: Option<String>, state: tauri::State<AppState>) -> Result<Vec<ConsentReceipt>, String> {
+    admin::require(&state, Scope::ConsentManage)?;
+    let conn = open_db("admin_logs.db");
+    ensure_receipts_table(&conn)?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {COLUMNS} FROM consent_receipts WHERE ?1 IS NULL OR user_id=?1 ORDER BY created_at DESC, rowid DESC LIMIT 200"
+    )).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map(params![user_id], row_to_receipt).map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>();
+    rows.map_err(|e| e.to_string())
+}
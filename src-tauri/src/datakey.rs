@@ -0,0 +1,91 @@
+
+//! Encryption at rest for per-profile stores.
+//!
+//! Each profile has a random 256-bit data key, sealed under its passphrase in
+//! `profiles/{user_id}/data.key` (same Argon2id envelope as `priv.enc`). While the
+//! profile is unlocked the key is held in the session; sensitive columns are
+//! XChaCha20-Poly1305 encrypted with it, with `table.column` bound as associated data
+//! so ciphertexts can't be swapped between columns.
+
+use crate::envelope::{self, KdfParams};
+use crate::security::write_atomic;
+use std::path::{Path, PathBuf};
+use xchacha20poly1305::{aead::{Aead, KeyInit, Payload}, XChaCha20Poly1305, XNonce};
+use zeroize::Zeroizing;
+
+const FILE: &str = "data.key";
+const NONCE_LEN: usize = 24;
+/// Bytes `seal_bytes` adds to its input: the nonce and the Poly1305 tag.
+pub const SEAL_OVERHEAD: usize = NONCE_LEN + 16;
+
+#[derive(Clone)]
+pub struct DataKey(Zeroizing<[u8; 32]>);
+
+impl DataKey {
+    fn generate() -> Self { DataKey(Zeroizing::new(rand::random())) }
+
+    fn from_slice(b: &[u8]) -> Result<Self, String> {
+        let k: [u8; 32] = b.try_into().map_err(|_| "corrupt data key")?;
+        Ok(DataKey(Zeroizing::new(k)))
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 { XChaCha20Poly1305::new((&*self.0).into()) }
+
+    /// `nonce || ciphertext`
+    pub fn seal_bytes(&self, aad: &str, plain: &[u8]) -> Result<Vec<u8>, String> {
+        let nonce: [u8; NONCE_LEN] = rand::random();
+        let ct = self.cipher()
+            .encrypt(XNonce::from_slice(&nonce), Payload { msg: plain, aad: aad.as_bytes() })
+            .map_err(|_| "encrypt failed".to_string())?;
+        Ok([nonce.as_slice(), &ct].concat())
+    }
+
+    pub fn open_bytes(&self, aad: &str, blob: &[u8]) -> Result<Vec<u8>, String> {
+        if blob.len() < NONCE_LEN { return Err(format!("{aad}: corrupt ciphertext")); }
+        self.cipher()
+            .decrypt(XNonce::from_slice(&blob[..NONCE_LEN]), Payload { msg: &blob[NONCE_LEN..], aad: aad.as_bytes() })
+            .map_err(|_| format!("{aad}: decrypt failed"))
+    }
+
+    /// Text columns stay TEXT: base64 of `seal_bytes`.
+    pub fn seal_text(&self, aad: &str, plain: &str) -> Result<String, String> {
+        Ok(base64::encode(self.seal_bytes(aad, plain.as_bytes())?))
+    }
+
+    pub fn open_text(&self, aad: &str, b64: &str) -> Result<String, String> {
+        let blob = base64::decode(b64).map_err(|_| format!("{aad}: corrupt ciphertext"))?;
+        String::from_utf8(self.open_bytes(aad, &blob)?).map_err(|e| e.to_string())
+    }
+}
+
+/// Unseal the profile's data key, creating it on first unlock. Re-seals it when the
+/// envelope is outdated, like `priv.enc`.
+pub fn load_or_create(dir: &Path, passphrase: &str, kdf: KdfParams) -> Result<DataKey, String> {
+    let path = dir.join(FILE);
+    if !path.exists() {
+        let key = DataKey::generate();
+        write_atomic(&path, &envelope::seal(&*key.0, passphrase, kdf)?)?;
+        return Ok(key);
+    }
+    let blob = std::fs::read(&path).map_err(|e| e.to_string())?;
+    let opened = envelope::open(&blob, passphrase)?;
+    let key = DataKey::from_slice(&opened.plaintext)?;
+    if opened.needs_upgrade(kdf) {
+        write_atomic(&path, &envelope::seal(&*key.0, passphrase, kdf)?)?;
+    }
+    Ok(key)
+}
+
+/// Path of the profile's sealed data key.
+pub fn file(dir: &Path) -> PathBuf { dir.join(FILE) }
+
+/// Re-wrap the data key under a new passphrase. The key itself (and so the data) is unchanged.
+/// Returns the new contents of `file(dir)`, or `None` if the profile has no data key yet; the
+/// caller writes it together with `priv.enc` so the two never end up under different passphrases.
+pub fn rewrap(dir: &Path, old: &str, new: &str, kdf: KdfParams) -> Result<Option<Vec<u8>>, String> {
+    let path = file(dir);
+    if !path.exists() { return Ok(None); }
+    let blob = std::fs::read(&path).map_err(|e| e.to_string())?;
+    let opened = envelope::open(&blob, old)?;
+    envelope::seal(&opened.plaintext, new, kdf).map(Some)
+}
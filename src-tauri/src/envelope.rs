@@ -0,0 +1,130 @@
+
+//! Passphrase-protected key envelopes for `priv.enc`.
+//!
+//! v1 layout (all integers little-endian):
+//!   "PPKE" | version=1 | kdf=1 (Argon2id) | m_cost KiB u32 | t_cost u32 | p_cost u32
+//!   | salt_len u8 | salt | nonce[24] | XChaCha20-Poly1305 ciphertext
+//! The header (everything before the nonce) is bound as AEAD associated data.
+//!
+//! v0 (legacy) is `nonce[24] | ciphertext` keyed with an unsalted SHA-256 of the passphrase;
+//! it is only ever read, so profiles can be migrated on their next unlock.
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+use xchacha20poly1305::{aead::{Aead, KeyInit, Payload}, XChaCha20Poly1305, XNonce};
+
+const MAGIC: &[u8; 4] = b"PPKE";
+const VERSION_1: u8 = 1;
+const KDF_ARGON2ID: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+/// Refuse headers asking for more than 1 GiB, or more passes and lanes than the config
+/// schema allows (`kdf.*`), so a crafted file can't exhaust memory or hang unlock.
+const MAX_MEMORY_KIB: u32  = 1024 * 1024;
+const MAX_ITERATIONS: u32  = 20;
+const MAX_PARALLELISM: u32 = 16;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct KdfParams {
+    pub memory_kib:  u32,
+    pub iterations:  u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self { KdfParams { memory_kib: 64 * 1024, iterations: 3, parallelism: 1 } }
+}
+
+pub struct Opened {
+    pub plaintext: Zeroizing<Vec<u8>>,
+    pub version:   u8,
+    /// `None` for legacy v0 blobs.
+    pub params:    Option<KdfParams>,
+}
+
+impl Opened {
+    /// True if the blob should be re-sealed: legacy format or different cost parameters.
+    pub fn needs_upgrade(&self, current: KdfParams) -> bool {
+        self.version < VERSION_1 || self.params != Some(current)
+    }
+}
+
+fn argon2_key(pass: &str, salt: &[u8], p: KdfParams) -> Result<[u8; 32], String> {
+    if p.memory_kib > MAX_MEMORY_KIB {
+        return Err(format!("KDF memory cost too large: {} KiB", p.memory_kib));
+    }
+    if !(1..=MAX_ITERATIONS).contains(&p.iterations) || !(1..=MAX_PARALLELISM).contains(&p.parallelism) {
+        return Err(format!("KDF cost out of range: {} iterations, {} lanes", p.iterations, p.parallelism));
+    }
+    let params = Params::new(p.memory_kib, p.iterations, p.parallelism, Some(32)).map_err(|e| e.to_string())?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(pass.as_bytes(), salt, &mut key)
+        .map_err(|e| e.to_string())?;
+    Ok(key)
+}
+
+fn legacy_key(pass: &str) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&Sha256::digest(pass.as_bytes()));
+    key
+}
+
+fn header(p: KdfParams, salt: &[u8]) -> Vec<u8> {
+    let mut h = Vec::with_capacity(19 + salt.len());
+    h.extend_from_slice(MAGIC);
+    h.push(VERSION_1);
+    h.push(KDF_ARGON2ID);
+    h.extend_from_slice(&p.memory_kib.to_le_bytes());
+    h.extend_from_slice(&p.iterations.to_le_bytes());
+    h.extend_from_slice(&p.parallelism.to_le_bytes());
+    h.push(salt.len() as u8);
+    h.extend_from_slice(salt);
+    h
+}
+
+/// Seal `plaintext` under `pass` as a v1 envelope with a fresh salt and nonce.
+pub fn seal(plaintext: &[u8], pass: &str, params: KdfParams) -> Result<Vec<u8>, String> {
+    let salt: [u8; SALT_LEN] = rand::random();
+    let nonce: [u8; NONCE_LEN] = rand::random();
+    let key = argon2_key(pass, &salt, params)?;
+    let head = header(params, &salt);
+    let ct = XChaCha20Poly1305::new(&key.into())
+        .encrypt(XNonce::from_slice(&nonce), Payload { msg: plaintext, aad: &head })
+        .map_err(|_| "encrypt failed".to_string())?;
+    let mut blob = head;
+    blob.extend_from_slice(&nonce);
+    blob.extend_from_slice(&ct);
+    Ok(blob)
+}
+
+fn u32_at(b: &[u8], at: usize) -> u32 { u32::from_le_bytes([b[at], b[at + 1], b[at + 2], b[at + 3]]) }
+
+/// Open a v1 or legacy v0 envelope. Any failure (wrong passphrase, tampering) is "decrypt failed".
+pub fn open(blob: &[u8], pass: &str) -> Result<Opened, String> {
+    if !blob.starts_with(MAGIC) {
+        if blob.len() < NONCE_LEN { return Err("decrypt failed".into()); }
+        let key = legacy_key(pass);
+        let plaintext = XChaCha20Poly1305::new(&key.into())
+            .decrypt(XNonce::from_slice(&blob[..NONCE_LEN]), &blob[NONCE_LEN..])
+            .map_err(|_| "decrypt failed".to_string())?;
+        return Ok(Opened { plaintext: Zeroizing::new(plaintext), version: 0, params: None });
+    }
+
+    if blob.len() < 19 { return Err("corrupt key envelope".into()); }
+    if blob[4] != VERSION_1 { return Err(format!("unsupported key envelope version {}", blob[4])); }
+    if blob[5] != KDF_ARGON2ID { return Err(format!("unsupported KDF id {}", blob[5])); }
+    let params = KdfParams { memory_kib: u32_at(blob, 6), iterations: u32_at(blob, 10), parallelism: u32_at(blob, 14) };
+    let salt_len = blob[18] as usize;
+    let head_len = 19 + salt_len;
+    if blob.len() < head_len + NONCE_LEN { return Err("corrupt key envelope".into()); }
+
+    let (head, rest) = blob.split_at(head_len);
+    let key = argon2_key(pass, &head[19..], params)?;
+    let plaintext = XChaCha20Poly1305::new(&key.into())
+        .decrypt(XNonce::from_slice(&rest[..NONCE_LEN]), Payload { msg: &rest[NONCE_LEN..], aad: head })
+        .map_err(|_| "decrypt failed".to_string())?;
+    Ok(Opened { plaintext: Zeroizing::new(plaintext), version: VERSION_1, params: Some(params) })
+}
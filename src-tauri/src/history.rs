@@ -1,7 +1,19 @@
 
-//! Answer history — save, retrieve, favourite, score, delete
+//! Answer history — save, retrieve, favourite, score, delete.
+//! Stored per profile in `profiles/{user_id}/history.db`; requires an unlocked session.
+//! `prompt_title`, `question`, `answer` and `score_json` are encrypted with the profile's
+//! data key (`enc = 2`). `enc = 1` rows, from before titles were sealed, still have a
+//! plaintext title until `encrypt_plaintext_rows` next runs.
 
-use crate::security::open_db;
+use crate::admin::{self, Scope};
+use crate::admin_keys::{self, ApprovalAction};
+use crate::consent;
+use crate::datakey::DataKey;
+use crate::llm;
+use crate::logs::{self, AuditEvent};
+use crate::security::AppState;
+use crate::session;
+use crate::users;
 use rusqlite::params;
 use serde::{Deserialize, Serialize};
 
@@ -17,7 +29,7 @@ pub struct AnswerRecord {
     pub created_at:   String,
 }
 
-fn ensure_history_table(conn: &rusqlite::Connection) -> Result<(), String> {
+pub fn ensure_history_table(conn: &rusqlite::Connection) -> Result<(), String> {
     conn.execute_batch(
         "CREATE TABLE IF NOT EXISTS answer_history (
             id           INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -27,9 +39,55 @@ fn ensure_history_table(conn: &rusqlite::Connection) -> Result<(), String> {
             answer       TEXT    NOT NULL,
             score_json   TEXT,
             favourite    INTEGER DEFAULT 0,
-            created_at   DATETIME DEFAULT CURRENT_TIMESTAMP
+            created_at   DATETIME DEFAULT CURRENT_TIMESTAMP,
+            enc          INTEGER NOT NULL DEFAULT 0
         );"
-    ).map_err(|e| e.to_string())
+    ).map_err(|e| e.to_string())?;
+    // Tables created before encryption at rest lack the `enc` flag
+    let has_enc = conn.prepare("SELECT enc FROM answer_history LIMIT 0").is_ok();
+    if !has_enc {
+        conn.execute("ALTER TABLE answer_history ADD COLUMN enc INTEGER NOT NULL DEFAULT 0", [])
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// `enc` value once every sensitive column, the title included, is sealed.
+const ENC_ALL: i64 = 2;
+
+const AAD_TITLE: &str    = "answer_history.prompt_title";
+const AAD_QUESTION: &str = "answer_history.question";
+const AAD_ANSWER: &str   = "answer_history.answer";
+const AAD_SCORE: &str    = "answer_history.score_json";
+
+/// Encrypt rows stored in the clear (`enc = 0`) and titles left plaintext (`enc = 1`),
+/// then compact the file.
+pub fn encrypt_plaintext_rows(conn: &rusqlite::Connection, key: &DataKey) -> Result<usize, String> {
+    ensure_history_table(conn)?;
+    let rows: Vec<(i64, String, String, String, Option<String>, i64)> = {
+        let mut stmt = conn.prepare("SELECT id, prompt_title, COALESCE(question,''), answer, score_json, enc FROM answer_history WHERE enc<?1")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt.query_map(params![ENC_ALL], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?, r.get(5)?)))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>();
+        rows.map_err(|e| e.to_string())?
+    };
+    if rows.is_empty() { return Ok(0); }
+    for (id, title, question, answer, score, enc) in &rows {
+        let title = key.seal_text(AAD_TITLE, title)?;
+        if *enc == 0 {
+            let score = score.as_deref().map(|s| key.seal_text(AAD_SCORE, s)).transpose()?;
+            conn.execute(
+                "UPDATE answer_history SET prompt_title=?1, question=?2, answer=?3, score_json=?4, enc=?5 WHERE id=?6",
+                params![title, key.seal_text(AAD_QUESTION, question)?, key.seal_text(AAD_ANSWER, answer)?, score, ENC_ALL, id],
+            ).map_err(|e| e.to_string())?;
+        } else {
+            conn.execute("UPDATE answer_history SET prompt_title=?1, enc=?2 WHERE id=?3", params![title, ENC_ALL, id])
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    conn.execute_batch("VACUUM;").map_err(|e| e.to_string())?;
+    Ok(rows.len())
 }
 
 #[tauri::command]
@@ -38,19 +96,32 @@ pub fn history_save(
     prompt_style: String,
     question: String,
     answer: String,
+    request_id: Option<String>,
+    state: tauri::State<AppState>,
 ) -> Result<i64, String> {
-    let conn = open_db("history.db");
+    // Don't persist the output of a generation the user cancelled
+    if let Some(id) = request_id {
+        if llm::take_cancelled(&state, &id) {
+            return Err(llm::CANCELLED.into());
+        }
+    }
+    let (conn, key) = session::open_encrypted_db(&state, "history.db")?;
     ensure_history_table(&conn)?;
     conn.execute(
-        "INSERT INTO answer_history(prompt_title, prompt_style, question, answer) VALUES(?1,?2,?3,?4)",
-        params![prompt_title, prompt_style, question, answer],
+        "INSERT INTO answer_history(prompt_title, prompt_style, question, answer, enc) VALUES(?1,?2,?3,?4,?5)",
+        params![key.seal_text(AAD_TITLE, &prompt_title)?, prompt_style, key.seal_text(AAD_QUESTION, &question)?, key.seal_text(AAD_ANSWER, &answer)?, ENC_ALL],
     ).map_err(|e| e.to_string())?;
     Ok(conn.last_insert_rowid())
 }
 
 #[tauri::command]
-pub fn history_save_score(id: i64, score_json: String) -> Result<(), String> {
-    let conn = open_db("history.db");
+pub fn history_save_score(id: i64, score_json: String, state: tauri::State<AppState>) -> Result<(), String> {
+    let (conn, key) = session::open_encrypted_db(&state, "history.db")?;
+    ensure_history_table(&conn)?;
+    // Rows still awaiting encryption keep a plaintext score so the row stays consistent
+    let enc: bool = conn.query_row("SELECT enc FROM answer_history WHERE id=?1", params![id], |r| r.get::<_, i64>(0))
+        .map_err(|e| e.to_string())? >= 1;
+    let score_json = if enc { key.seal_text(AAD_SCORE, &score_json)? } else { score_json };
     conn.execute(
         "UPDATE answer_history SET score_json=?1 WHERE id=?2",
         params![score_json, id],
@@ -59,8 +130,8 @@ pub fn history_save_score(id: i64, score_json: String) -> Result<(), String> {
 }
 
 #[tauri::command]
-pub fn history_toggle_fav(id: i64) -> Result<bool, String> {
-    let conn = open_db("history.db");
+pub fn history_toggle_fav(id: i64, state: tauri::State<AppState>) -> Result<bool, String> {
+    let conn = session::open_user_db(&state, "history.db")?;
     ensure_history_table(&conn)?;
     let current: i64 = conn.query_row(
         "SELECT favourite FROM answer_history WHERE id=?1",
@@ -76,20 +147,20 @@ pub fn history_toggle_fav(id: i64) -> Result<bool, String> {
 }
 
 #[tauri::command]
-pub fn history_list(limit: u32, favourites_only: bool) -> Result<Vec<AnswerRecord>, String> {
-    let conn = open_db("history.db");
+pub fn history_list(limit: u32, favourites_only: bool, state: tauri::State<AppState>) -> Result<Vec<AnswerRecord>, String> {
+    let (conn, key) = session::open_encrypted_db(&state, "history.db")?;
     ensure_history_table(&conn)?;
     let sql = if favourites_only {
-        "SELECT id, prompt_title, prompt_style, COALESCE(question,''), answer, score_json, favourite, COALESCE(created_at,'') \
+        "SELECT id, prompt_title, prompt_style, COALESCE(question,''), answer, score_json, favourite, COALESCE(created_at,''), enc \
          FROM answer_history WHERE favourite=1 ORDER BY id DESC LIMIT ?1"
     } else {
-        "SELECT id, prompt_title, prompt_style, COALESCE(question,''), answer, score_json, favourite, COALESCE(created_at,'') \
+        "SELECT id, prompt_title, prompt_style, COALESCE(question,''), answer, score_json, favourite, COALESCE(created_at,''), enc \
          FROM answer_history ORDER BY id DESC LIMIT ?1"
     };
     let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
-    let records: Vec<AnswerRecord> = stmt
+    let rows: Vec<(AnswerRecord, i64)> = stmt
         .query_map(params![limit.min(200)], |row| {
-            Ok(AnswerRecord {
+            Ok((AnswerRecord {
                 id:           row.get(0)?,
                 prompt_title: row.get(1)?,
                 prompt_style: row.get(2)?,
@@ -98,27 +169,73 @@ pub fn history_list(limit: u32, favourites_only: bool) -> Result<Vec<AnswerRecor
                 score_json:   row.get(5)?,
                 favourite:    row.get::<_, i64>(6)? == 1,
                 created_at:   row.get(7)?,
-            })
+            }, row.get::<_, i64>(8)?))
         })
         .map_err(|e| e.to_string())?
         .filter_map(|r| r.ok())
         .collect();
-    Ok(records)
+    rows.into_iter().map(|(mut r, enc)| {
+        if enc >= ENC_ALL {
+            r.prompt_title = key.open_text(AAD_TITLE, &r.prompt_title)?;
+        }
+        if enc >= 1 {
+            r.question   = key.open_text(AAD_QUESTION, &r.question)?;
+            r.answer     = key.open_text(AAD_ANSWER, &r.answer)?;
+            r.score_json = r.score_json.map(|s| key.open_text(AAD_SCORE, &s)).transpose()?;
+        }
+        Ok(r)
+    }).collect()
 }
 
 #[tauri::command]
-pub fn history_delete(id: i64) -> Result<(), String> {
-    let conn = open_db("history.db");
+pub fn history_delete(id: i64, state: tauri::State<AppState>) -> Result<(), String> {
+    let conn = session::open_user_db(&state, "history.db")?;
     conn.execute("DELETE FROM answer_history WHERE id=?1", params![id])
         .map_err(|e| e.to_string())?;
     Ok(())
 }
 
 #[tauri::command]
-pub fn history_clear_all() -> Result<usize, String> {
-    let conn = open_db("history.db");
+pub fn history_clear_all(state: tauri::State<AppState>) -> Result<usize, String> {
+    let conn = session::open_user_db(&state, "history.db")?;
+    ensure_history_table(&conn)?;
+    let n = conn.execute("DELETE FROM answer_history", [])
+        .map_err(|e| e.to_string())?;
+    let user_id = session::current_user(&state).unwrap_or_default();
+    logs::record(&AuditEvent::HistoryCleared { user_id, count: n, by_admin: false })?;
+    Ok(n)
+}
+
+fn clear_profile_history(user_id: &str) -> Result<usize, String> {
+    let conn = session::open_profile_db(user_id, "history.db")?;
     ensure_history_table(&conn)?;
     let n = conn.execute("DELETE FROM answer_history", [])
         .map_err(|e| e.to_string())?;
+    logs::record(&AuditEvent::HistoryCleared { user_id: user_id.to_string(), count: n, by_admin: true })?;
     Ok(n)
 }
+
+/// Admin: wipe another profile's history without unlocking it (rows are deleted, not read).
+/// Needs the user's consent receipt for `clear_user_history` with `{"user_id": …}`, and an
+/// m-of-n approval (`admin_keys::require_approval`) when a quorum is configured.
+#[tauri::command]
+pub fn admin_clear_user_history(user_id: String, receipt_id: String, approval_id: Option<String>, state: tauri::State<AppState>) -> Result<usize, String> {
+    admin::require(&state, Scope::HistoryManage)?;
+    let params = serde_json::json!({ "user_id": user_id });
+    users::require_profile(&user_id)?;
+    admin_keys::require_approval(&state, approval_id.as_deref(), ApprovalAction::ClearUserHistory, Some(&params))?;
+    consent::redeem(&state, &receipt_id, &user_id, consent::CLEAR_USER_HISTORY, Some(&params))?;
+    clear_profile_history(&user_id)
+}
+
+/// Admin: wipe the history of every profile.
+#[tauri::command]
+pub fn admin_clear_all_history(approval_id: Option<String>, state: tauri::State<AppState>) -> Result<usize, String> {
+    admin::require(&state, Scope::HistoryManage)?;
+    admin_keys::require_approval(&state, approval_id.as_deref(), ApprovalAction::ClearAllHistory, None)?;
+    let mut total = 0;
+    for profile in users::list_profiles()? {
+        total += clear_profile_history(&profile.user_id)?;
+    }
+    Ok(total)
+}
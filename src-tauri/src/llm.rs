@@ -1,85 +1,232 @@
 
-//! Ollama local-LLM bridge
-//! Requires: ollama running at http://localhost:11434
-//! Install:  https://ollama.com  →  ollama pull mistral
-//!           ollama pull nomic-embed-text
+//! Local-LLM bridge
+//! Commands talk to a pluggable `LlmBackend`, chosen in Settings:
+//!   - Ollama            (default, http://127.0.0.1:11434)  →  ollama pull mistral && ollama pull nomic-embed-text
+//!   - llama.cpp server  (http://127.0.0.1:8080)            →  llama-server -m model.gguf --embeddings
+//!   - OpenAI-compatible (http://127.0.0.1:1234)            →  LM Studio, vLLM, LocalAI, …
 
-use reqwest::blocking::Client;
+mod llamacpp;
+mod ollama;
+mod openai;
+
+use crate::config::{AppConfig, LlmConfig};
+use crate::policy;
+use crate::security::AppState;
+use reqwest::blocking::{Client, Response};
 use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
 
-const OLLAMA: &str = "http://127.0.0.1:11434";
+/// `gen` bounds a whole streamed generation; `fast` covers embeddings and status calls.
+#[derive(Clone, Copy)]
+struct Timeouts { gen: Duration, fast: Duration }
 
-fn client_gen() -> Client {
+fn client(timeout: Duration) -> Client {
     Client::builder()
-        .timeout(Duration::from_secs(300)) // 5 min for generation
+        .timeout(timeout)
         .build()
         .unwrap()
 }
-fn client_fast() -> Client {
-    Client::builder()
-        .timeout(Duration::from_secs(30))
-        .build()
-        .unwrap()
+
+pub const CANCELLED: &str = "Generation cancelled";
+
+#[derive(Serialize, Clone, Default)]
+pub struct GenStats {
+    pub total_duration_ms: u64,
+    pub prompt_tokens:     u64,
+    pub completion_tokens: u64,
 }
 
-// ── Embedding ────────────────────────────────────────────────────────────────
-#[derive(Serialize)]
-struct EmbedReq { model: String, prompt: String }
-#[derive(Deserialize)]
-struct EmbedResp { embedding: Vec<f32> }
-
-pub fn embed(text: &str, model: &str) -> Result<Vec<f32>, String> {
-    let resp = client_fast()
-        .post(format!("{OLLAMA}/api/embeddings"))
-        .json(&EmbedReq { model: model.into(), prompt: text.into() })
-        .send()
-        .map_err(|e| format!("Ollama unreachable — is Ollama running? {}", e))?;
-    if !resp.status().is_success() {
-        return Err(format!("Embed error {}: {}", resp.status(), resp.text().unwrap_or_default()));
+// ── Backend trait ────────────────────────────────────────────────────────────
+pub trait LlmBackend: Send + Sync {
+    /// Stream a completion, calling `on_token` for every fragment.
+    /// Returns the full text plus stats; must stop early with `CANCELLED` once `cancel` is set.
+    fn generate(
+        &self,
+        prompt: &str,
+        model: &str,
+        cancel: &AtomicBool,
+        on_token: &mut dyn FnMut(&str),
+    ) -> Result<(String, GenStats), String>;
+    fn embed(&self, text: &str, model: &str) -> Result<Vec<f32>, String>;
+    fn list_models(&self) -> Result<Vec<String>, String>;
+    fn health(&self) -> Result<(), String>;
+}
+
+/// Read a `text/event-stream` body, handing each `data:` payload to `on_data`
+/// until it returns `Ok(false)`, the server sends `[DONE]`, or `cancel` is set.
+fn for_each_sse_data(
+    resp: Response,
+    cancel: &AtomicBool,
+    mut on_data: impl FnMut(&str) -> Result<bool, String>,
+) -> Result<(), String> {
+    for line in BufReader::new(resp).lines() {
+        if cancel.load(Ordering::SeqCst) { return Err(CANCELLED.into()); }
+        let line = line.map_err(|e| format!("Stream interrupted: {}", e))?;
+        let Some(data) = line.strip_prefix("data:").map(str::trim) else { continue };
+        if data.is_empty() { continue; }
+        if data == "[DONE]" || !on_data(data)? { break; }
     }
-    let r: EmbedResp = resp.json().map_err(|e| e.to_string())?;
-    Ok(r.embedding)
+    if cancel.load(Ordering::SeqCst) { return Err(CANCELLED.into()); }
+    Ok(())
 }
 
-// ── Generate ────────────────────────────────────────────────────────────────
-#[derive(Serialize)]
-struct GenReq { model: String, prompt: String, stream: bool }
-#[derive(Deserialize)]
-struct GenResp { response: String }
-
-pub fn generate(prompt: &str, model: &str) -> Result<String, String> {
-    let resp = client_gen()
-        .post(format!("{OLLAMA}/api/generate"))
-        .json(&GenReq { model: model.into(), prompt: prompt.into(), stream: false })
-        .send()
-        .map_err(|e| format!("Ollama unreachable: {}", e))?;
-    if !resp.status().is_success() {
-        return Err(format!("Generate error {}: {}", resp.status(), resp.text().unwrap_or_default()));
+// ── Backend selection (from app.yaml) ────────────────────────────────────────
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind { Ollama, LlamaCpp, OpenAi }
+
+impl BackendKind {
+    pub fn default_url(self) -> &'static str {
+        match self {
+            BackendKind::Ollama   => "http://127.0.0.1:11434",
+            BackendKind::LlamaCpp => "http://127.0.0.1:8080",
+            BackendKind::OpenAi   => "http://127.0.0.1:1234",
+        }
     }
-    let r: GenResp = resp.json().map_err(|e| e.to_string())?;
-    Ok(r.response)
+}
+
+pub fn backend_from(cfg: &LlmConfig) -> Box<dyn LlmBackend> {
+    let base_url = cfg.base_url.trim_end_matches('/').to_string();
+    let timeouts = Timeouts {
+        gen:  Duration::from_secs(cfg.gen_timeout_secs),
+        fast: Duration::from_secs(cfg.embed_timeout_secs),
+    };
+    match cfg.backend {
+        BackendKind::Ollama   => Box::new(ollama::Ollama { base_url, timeouts }),
+        BackendKind::LlamaCpp => Box::new(llamacpp::LlamaCpp { base_url, timeouts }),
+        BackendKind::OpenAi   => Box::new(openai::OpenAiCompat { base_url, timeouts, api_key: cfg.api_key.clone() }),
+    }
+}
+
+/// True if `base_url` points at this machine (localhost / 127.0.0.0/8 / ::1).
+pub fn is_loopback(base_url: &str) -> bool {
+    let Ok(url) = reqwest::Url::parse(base_url) else { return false };
+    match url.host_str() {
+        Some("localhost") => true,
+        Some(h) => h.trim_matches(|c| c == '[' || c == ']')
+                    .parse::<std::net::IpAddr>()
+                    .map(|ip| ip.is_loopback())
+                    .unwrap_or(false),
+        None => false,
+    }
+}
+
+/// The configured backend, after checking `NETWORK_CLOUD_LLM` policy for non-loopback endpoints.
+pub fn current_backend(state: &AppState) -> Result<Box<dyn LlmBackend>, String> {
+    let cfg = state.config.lock().unwrap().llm.clone();
+    if !is_loopback(&cfg.base_url) {
+        policy::enforce(state, policy::NETWORK_CLOUD_LLM)?;
+    }
+    Ok(backend_from(&cfg))
 }
 
 // ── Status ───────────────────────────────────────────────────────────────────
-#[derive(Serialize, Deserialize)]
-pub struct OllamaModel { pub name: String }
-#[derive(Deserialize)]
-struct TagsResp { models: Vec<OllamaModel> }
+#[derive(Serialize)]
+pub struct LlmStatus {
+    pub backend:  BackendKind,
+    pub base_url: String,
+    pub models:   Vec<String>,
+}
+
+#[tauri::command]
+pub fn llm_status(state: tauri::State<AppState>) -> Result<LlmStatus, String> {
+    let cfg: AppConfig = state.config.lock().unwrap().clone();
+    let backend = current_backend(&state)?;
+    backend.health()?;
+    Ok(LlmStatus { backend: cfg.llm.backend, base_url: cfg.llm.base_url, models: backend.list_models()? })
+}
+
+// ── Tauri events ─────────────────────────────────────────────────────────────
+/// Emitted for every streamed fragment: `{ request_id, token }`.
+pub const EVT_TOKEN: &str = "llm:token";
+/// Emitted once per request: `{ request_id, ok, error, total_duration_ms, prompt_tokens, completion_tokens }`.
+pub const EVT_DONE: &str = "llm:done";
+
+#[derive(Serialize, Clone)]
+struct TokenEvent { request_id: String, token: String }
+
+#[derive(Serialize, Clone)]
+struct DoneEvent {
+    request_id: String,
+    ok:         bool,
+    error:      Option<String>,
+    #[serde(flatten)]
+    stats:      GenStats,
+}
+
+// ── Request registry / cancellation ──────────────────────────────────────────
+/// Remembered cancellations past which those of finished requests are dropped, so an app
+/// left open for days stays bounded.
+const MAX_CANCELLED: usize = 256;
+
+fn register_request(state: &AppState, request_id: &str) -> Result<Arc<AtomicBool>, String> {
+    let mut reqs = state.llm_requests.lock().unwrap();
+    if reqs.contains_key(request_id) {
+        return Err(format!("Request id already in flight: {}", request_id));
+    }
+    let flag = Arc::new(AtomicBool::new(false));
+    reqs.insert(request_id.to_string(), flag.clone());
+    Ok(flag)
+}
+
+fn finish_request(state: &AppState, request_id: &str) {
+    state.llm_requests.lock().unwrap().remove(request_id);
+}
 
+/// True (once) if `request_id` was cancelled; used to skip writes that would follow it.
+pub fn take_cancelled(state: &AppState, request_id: &str) -> bool {
+    state.llm_cancelled.lock().unwrap().remove(request_id)
+}
+
+/// Cancel an in-flight generation. Returns false if the id is unknown or already finished.
 #[tauri::command]
-pub fn ollama_status() -> Result<Vec<String>, String> {
-    let resp = client_fast()
-        .get(format!("{OLLAMA}/api/tags"))
-        .send()
-        .map_err(|_| "Ollama offline. Install: https://ollama.com  then run: ollama pull mistral && ollama pull nomic-embed-text".to_string())?;
-    let r: TagsResp = resp.json().map_err(|e| e.to_string())?;
-    Ok(r.models.into_iter().map(|m| m.name).collect())
+pub fn llm_cancel(request_id: String, state: tauri::State<AppState>) -> Result<bool, String> {
+    let flag = state.llm_requests.lock().unwrap().get(&request_id).cloned();
+    let Some(flag) = flag else { return Ok(false) };
+    flag.store(true, Ordering::SeqCst);
+    let mut cancelled = state.llm_cancelled.lock().unwrap();
+    if cancelled.len() >= MAX_CANCELLED {
+        // Forget finished requests whose cancellation was never taken, never a streaming one
+        let live = state.llm_requests.lock().unwrap();
+        cancelled.retain(|id| live.contains_key(id));
+    }
+    cancelled.insert(request_id);
+    Ok(true)
+}
+
+/// Run a streaming generation, forwarding tokens to the frontend keyed by `request_id`.
+fn stream_with_events(app: &AppHandle, request_id: &str, prompt: &str, model: &str) -> Result<String, String> {
+    let state = app.state::<AppState>();
+    let backend = current_backend(&state)?;
+    let cancel = register_request(&state, request_id)?;
+    let result = backend.generate(prompt, model, &cancel, &mut |token| {
+        let _ = app.emit(EVT_TOKEN, TokenEvent { request_id: request_id.into(), token: token.into() });
+    });
+    let done = match &result {
+        Ok((_, stats)) => DoneEvent { request_id: request_id.into(), ok: true, error: None, stats: stats.clone() },
+        Err(e)         => DoneEvent { request_id: request_id.into(), ok: false, error: Some(e.clone()), stats: GenStats::default() },
+    };
+    let _ = app.emit(EVT_DONE, done);
+    finish_request(&state, request_id);
+    result.map(|(text, _)| text)
+}
+
+/// Generation blocks for up to `llm.gen_timeout_secs`, so keep it off the main thread.
+async fn run_streaming(app: AppHandle, request_id: String, prompt: String, model: String) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || stream_with_events(&app, &request_id, &prompt, &model))
+        .await
+        .map_err(|e| e.to_string())?
 }
 
 // ── AI Answer Generation (with RAG context) ──────────────────────────────────
 #[tauri::command]
-pub fn generate_answer(
+pub async fn generate_answer(
+    app: AppHandle,
+    request_id: String,
     prompt_title: String,
     prompt_body: String,
     rag_context: String,
@@ -110,12 +257,14 @@ pub fn generate_answer(
          5. Lead with the strongest signal for this role\n\n\
          Generate the answer now:"
     );
-    generate(&prompt, &model)
+    run_streaming(app, request_id, prompt, model).await
 }
 
 // ── Answer Scorer ────────────────────────────────────────────────────────────
 #[tauri::command]
-pub fn score_answer(
+pub async fn score_answer(
+    app: AppHandle,
+    request_id: String,
     question: String,
     answer: String,
     style: String,
@@ -141,12 +290,12 @@ pub fn score_answer(
            \"improved_closing\": \"A stronger 1-2 sentence closing for this answer.\"\n\
          }}"
     );
-    generate(&prompt, &model)
+    run_streaming(app, request_id, prompt, model).await
 }
 
 // ── Job Description Analyzer ─────────────────────────────────────────────────
 #[tauri::command]
-pub fn analyze_job(job_text: String, model: String) -> Result<String, String> {
+pub async fn analyze_job(app: AppHandle, request_id: String, job_text: String, model: String) -> Result<String, String> {
     let prompt = format!(
         "You are a career coach. Analyze this job description for interview preparation.\n\n\
          JOB DESCRIPTION:\n{job_text}\n\n\
@@ -164,5 +313,5 @@ pub fn analyze_job(job_text: String, model: String) -> Result<String, String> {
            \"preparation_tips\": [\"4 specific preparation tips for this exact role\"]\n\
          }}"
     );
-    generate(&prompt, &model)
+    run_streaming(app, request_id, prompt, model).await
 }
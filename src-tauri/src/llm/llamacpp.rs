@@ -0,0 +1,81 @@
+
+//! llama.cpp `llama-server` backend — native `/completion` (SSE stream) and `/health`.
+//! Embeddings and model listing go through the server's OpenAI-compatible `/v1` routes
+//! (start it with `--embeddings` to enable them).
+
+use super::openai::{embed_v1, models_v1};
+use super::{client, for_each_sse_data, GenStats, LlmBackend, Timeouts};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::AtomicBool;
+use std::time::Instant;
+
+pub struct LlamaCpp { pub base_url: String, pub timeouts: Timeouts }
+
+#[derive(Serialize)]
+struct CompletionReq<'a> { prompt: &'a str, stream: bool, cache_prompt: bool }
+
+#[derive(Deserialize, Default)]
+struct Timings {
+    #[serde(default)] prompt_n:    u64,
+    #[serde(default)] predicted_n: u64,
+}
+#[derive(Deserialize)]
+struct CompletionChunk {
+    #[serde(default)] content: String,
+    #[serde(default)] stop:    bool,
+    #[serde(default)] timings: Option<Timings>,
+}
+
+impl LlmBackend for LlamaCpp {
+    /// llama-server serves a single model chosen at launch, so `model` is ignored here.
+    fn generate(
+        &self,
+        prompt: &str,
+        _model: &str,
+        cancel: &AtomicBool,
+        on_token: &mut dyn FnMut(&str),
+    ) -> Result<(String, GenStats), String> {
+        let started = Instant::now();
+        let resp = client(self.timeouts.gen)
+            .post(format!("{}/completion", self.base_url))
+            .json(&CompletionReq { prompt, stream: true, cache_prompt: true })
+            .send()
+            .map_err(|e| format!("llama-server unreachable: {}", e))?;
+        if !resp.status().is_success() {
+            return Err(format!("Generate error {}: {}", resp.status(), resp.text().unwrap_or_default()));
+        }
+
+        let mut text  = String::new();
+        let mut stats = GenStats::default();
+        for_each_sse_data(resp, cancel, |data| {
+            let chunk: CompletionChunk = serde_json::from_str(data).map_err(|e| e.to_string())?;
+            if !chunk.content.is_empty() {
+                on_token(&chunk.content);
+                text.push_str(&chunk.content);
+            }
+            if let Some(t) = chunk.timings {
+                stats.prompt_tokens     = t.prompt_n;
+                stats.completion_tokens = t.predicted_n;
+            }
+            Ok(!chunk.stop)
+        })?;
+        stats.total_duration_ms = started.elapsed().as_millis() as u64;
+        Ok((text, stats))
+    }
+
+    fn embed(&self, text: &str, model: &str) -> Result<Vec<f32>, String> {
+        embed_v1(&self.base_url, None, self.timeouts.fast, text, model)
+    }
+
+    fn list_models(&self) -> Result<Vec<String>, String> {
+        models_v1(&self.base_url, None, self.timeouts.fast)
+    }
+
+    fn health(&self) -> Result<(), String> {
+        let resp = client(self.timeouts.fast)
+            .get(format!("{}/health", self.base_url))
+            .send()
+            .map_err(|e| format!("llama-server unreachable: {}", e))?;
+        if resp.status().is_success() { Ok(()) } else { Err(format!("llama-server not ready ({})", resp.status())) }
+    }
+}
@@ -0,0 +1,109 @@
+
+//! Ollama backend — `/api/generate` (NDJSON stream), `/api/embeddings`, `/api/tags`
+
+use super::{client, GenStats, LlmBackend, Timeouts, CANCELLED};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+pub struct Ollama { pub base_url: String, pub timeouts: Timeouts }
+
+#[derive(Serialize)]
+struct EmbedReq { model: String, prompt: String }
+#[derive(Deserialize)]
+struct EmbedResp { embedding: Vec<f32> }
+
+#[derive(Serialize)]
+struct GenReq { model: String, prompt: String, stream: bool }
+
+/// One NDJSON line from `/api/generate` with `stream: true`.
+/// The final line has `done: true` and carries the timing/token counters.
+#[derive(Deserialize)]
+struct GenChunk {
+    #[serde(default)] response:          String,
+    #[serde(default)] done:              bool,
+    #[serde(default)] total_duration:    u64, // nanoseconds
+    #[serde(default)] prompt_eval_count: u64,
+    #[serde(default)] eval_count:        u64,
+}
+
+#[derive(Deserialize)]
+struct OllamaModel { name: String }
+#[derive(Deserialize)]
+struct TagsResp { models: Vec<OllamaModel> }
+
+impl LlmBackend for Ollama {
+    fn generate(
+        &self,
+        prompt: &str,
+        model: &str,
+        cancel: &AtomicBool,
+        on_token: &mut dyn FnMut(&str),
+    ) -> Result<(String, GenStats), String> {
+        let started = Instant::now();
+        let resp = client(self.timeouts.gen)
+            .post(format!("{}/api/generate", self.base_url))
+            .json(&GenReq { model: model.into(), prompt: prompt.into(), stream: true })
+            .send()
+            .map_err(|e| format!("Ollama unreachable: {}", e))?;
+        if !resp.status().is_success() {
+            return Err(format!("Generate error {}: {}", resp.status(), resp.text().unwrap_or_default()));
+        }
+
+        let mut text  = String::new();
+        let mut stats = GenStats::default();
+        for line in BufReader::new(resp).lines() {
+            if cancel.load(Ordering::SeqCst) { return Err(CANCELLED.into()); }
+            let line = line.map_err(|e| format!("Stream interrupted: {}", e))?;
+            if line.trim().is_empty() { continue; }
+            let chunk: GenChunk = serde_json::from_str(&line).map_err(|e| e.to_string())?;
+            if !chunk.response.is_empty() {
+                on_token(&chunk.response);
+                text.push_str(&chunk.response);
+            }
+            if chunk.done {
+                stats.prompt_tokens     = chunk.prompt_eval_count;
+                stats.completion_tokens = chunk.eval_count;
+                stats.total_duration_ms = chunk.total_duration / 1_000_000;
+                break;
+            }
+        }
+        if cancel.load(Ordering::SeqCst) { return Err(CANCELLED.into()); }
+        if stats.total_duration_ms == 0 {
+            stats.total_duration_ms = started.elapsed().as_millis() as u64;
+        }
+        Ok((text, stats))
+    }
+
+    fn embed(&self, text: &str, model: &str) -> Result<Vec<f32>, String> {
+        let resp = client(self.timeouts.fast)
+            .post(format!("{}/api/embeddings", self.base_url))
+            .json(&EmbedReq { model: model.into(), prompt: text.into() })
+            .send()
+            .map_err(|e| format!("Ollama unreachable — is Ollama running? {}", e))?;
+        if !resp.status().is_success() {
+            return Err(format!("Embed error {}: {}", resp.status(), resp.text().unwrap_or_default()));
+        }
+        let r: EmbedResp = resp.json().map_err(|e| e.to_string())?;
+        Ok(r.embedding)
+    }
+
+    fn list_models(&self) -> Result<Vec<String>, String> {
+        let resp = client(self.timeouts.fast)
+            .get(format!("{}/api/tags", self.base_url))
+            .send()
+            .map_err(|_| "Ollama offline. Install: https://ollama.com  then run: ollama pull mistral && ollama pull nomic-embed-text".to_string())?;
+        let r: TagsResp = resp.json().map_err(|e| e.to_string())?;
+        Ok(r.models.into_iter().map(|m| m.name).collect())
+    }
+
+    fn health(&self) -> Result<(), String> {
+        // Ollama answers "Ollama is running" on its root path
+        let resp = client(self.timeouts.fast)
+            .get(&self.base_url)
+            .send()
+            .map_err(|e| format!("Ollama unreachable: {}", e))?;
+        if resp.status().is_success() { Ok(()) } else { Err(format!("Ollama returned {}", resp.status())) }
+    }
+}
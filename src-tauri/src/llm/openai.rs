@@ -0,0 +1,144 @@
+
+//! OpenAI-compatible backend — `/v1/chat/completions` (SSE stream), `/v1/embeddings`, `/v1/models`
+//! Works with LM Studio, vLLM, LocalAI and anything else speaking the OpenAI wire format.
+
+use super::{client, for_each_sse_data, GenStats, LlmBackend, Timeouts};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
+
+pub struct OpenAiCompat {
+    pub base_url: String,
+    pub timeouts: Timeouts,
+    pub api_key:  Option<String>,
+}
+
+#[derive(Serialize)]
+struct ChatMessage<'a> { role: &'a str, content: &'a str }
+#[derive(Serialize)]
+struct StreamOptions { include_usage: bool }
+#[derive(Serialize)]
+struct ChatReq<'a> {
+    model:          &'a str,
+    messages:       Vec<ChatMessage<'a>>,
+    stream:         bool,
+    stream_options: StreamOptions,
+}
+
+#[derive(Deserialize, Default)]
+struct Delta { #[serde(default)] content: Option<String> }
+#[derive(Deserialize)]
+struct ChunkChoice { #[serde(default)] delta: Delta }
+#[derive(Deserialize)]
+struct Usage { #[serde(default)] prompt_tokens: u64, #[serde(default)] completion_tokens: u64 }
+#[derive(Deserialize)]
+struct ChatChunk {
+    #[serde(default)] choices: Vec<ChunkChoice>,
+    #[serde(default)] usage:   Option<Usage>,
+}
+
+#[derive(Serialize)]
+struct EmbedReq<'a> { model: &'a str, input: &'a str }
+#[derive(Deserialize)]
+struct EmbedData { embedding: Vec<f32> }
+#[derive(Deserialize)]
+struct EmbedResp { data: Vec<EmbedData> }
+
+#[derive(Deserialize)]
+struct ModelEntry { id: String }
+#[derive(Deserialize)]
+struct ModelsResp { data: Vec<ModelEntry> }
+
+/// `/v1` root for a user-supplied base URL; accepts both `http://host:1234` and `http://host:1234/v1`.
+pub(super) fn v1_root(base_url: &str) -> String {
+    let base = base_url.trim_end_matches('/');
+    if base.ends_with("/v1") { base.to_string() } else { format!("{base}/v1") }
+}
+
+fn with_auth(req: reqwest::blocking::RequestBuilder, api_key: Option<&str>) -> reqwest::blocking::RequestBuilder {
+    match api_key {
+        Some(k) if !k.is_empty() => req.bearer_auth(k),
+        _ => req,
+    }
+}
+
+/// Shared with the llama.cpp backend, which serves the same `/v1/embeddings` shape.
+pub(super) fn embed_v1(base_url: &str, api_key: Option<&str>, timeout: Duration, text: &str, model: &str) -> Result<Vec<f32>, String> {
+    let resp = with_auth(client(timeout).post(format!("{}/embeddings", v1_root(base_url))), api_key)
+        .json(&EmbedReq { model, input: text })
+        .send()
+        .map_err(|e| format!("LLM server unreachable: {}", e))?;
+    if !resp.status().is_success() {
+        return Err(format!("Embed error {}: {}", resp.status(), resp.text().unwrap_or_default()));
+    }
+    let r: EmbedResp = resp.json().map_err(|e| e.to_string())?;
+    r.data.into_iter().next().map(|d| d.embedding).ok_or_else(|| "Embed error: empty response".to_string())
+}
+
+/// Shared with the llama.cpp backend, which serves the same `/v1/models` shape.
+pub(super) fn models_v1(base_url: &str, api_key: Option<&str>, timeout: Duration) -> Result<Vec<String>, String> {
+    let resp = with_auth(client(timeout).get(format!("{}/models", v1_root(base_url))), api_key)
+        .send()
+        .map_err(|e| format!("LLM server unreachable: {}", e))?;
+    if !resp.status().is_success() {
+        return Err(format!("Model list error {}: {}", resp.status(), resp.text().unwrap_or_default()));
+    }
+    let r: ModelsResp = resp.json().map_err(|e| e.to_string())?;
+    Ok(r.data.into_iter().map(|m| m.id).collect())
+}
+
+impl LlmBackend for OpenAiCompat {
+    fn generate(
+        &self,
+        prompt: &str,
+        model: &str,
+        cancel: &AtomicBool,
+        on_token: &mut dyn FnMut(&str),
+    ) -> Result<(String, GenStats), String> {
+        let started = Instant::now();
+        let req = ChatReq {
+            model,
+            messages: vec![ChatMessage { role: "user", content: prompt }],
+            stream: true,
+            stream_options: StreamOptions { include_usage: true },
+        };
+        let resp = with_auth(client(self.timeouts.gen).post(format!("{}/chat/completions", v1_root(&self.base_url))), self.api_key.as_deref())
+            .json(&req)
+            .send()
+            .map_err(|e| format!("LLM server unreachable: {}", e))?;
+        if !resp.status().is_success() {
+            return Err(format!("Generate error {}: {}", resp.status(), resp.text().unwrap_or_default()));
+        }
+
+        let mut text  = String::new();
+        let mut stats = GenStats::default();
+        for_each_sse_data(resp, cancel, |data| {
+            let chunk: ChatChunk = serde_json::from_str(data).map_err(|e| e.to_string())?;
+            for choice in chunk.choices {
+                if let Some(t) = choice.delta.content.filter(|t| !t.is_empty()) {
+                    on_token(&t);
+                    text.push_str(&t);
+                }
+            }
+            if let Some(u) = chunk.usage {
+                stats.prompt_tokens     = u.prompt_tokens;
+                stats.completion_tokens = u.completion_tokens;
+            }
+            Ok(true)
+        })?;
+        stats.total_duration_ms = started.elapsed().as_millis() as u64;
+        Ok((text, stats))
+    }
+
+    fn embed(&self, text: &str, model: &str) -> Result<Vec<f32>, String> {
+        embed_v1(&self.base_url, self.api_key.as_deref(), self.timeouts.fast, text, model)
+    }
+
+    fn list_models(&self) -> Result<Vec<String>, String> {
+        models_v1(&self.base_url, self.api_key.as_deref(), self.timeouts.fast)
+    }
+
+    fn health(&self) -> Result<(), String> {
+        self.list_models().map(|_| ())
+    }
+}
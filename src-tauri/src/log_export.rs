@@ -0,0 +1,237 @@
+
+//! Audit log export bundles for offline review.
+//!
+//! A bundle holds every `logs` entry (with its chain hashes) plus the signed checkpoints.
+//!
+//! - JSON Lines: a `header` record, then one `entry` or `checkpoint` record per line.
+//! - CSV: columns `record,id,ts,event,hash_prev,hash_curr,signature_b64,pubkey_b64`.
+//!   Checkpoint rows reuse them: `id` = entry id, `ts` = checkpoint time, `event` = signed
+//!   statement, `hash_curr` = signed head hash.
+//!
+//! Reviewers without PromptPilot recompute `sha256(hash_prev || event)` from `GENESIS`
+//! and verify each checkpoint's Ed25519 signature over its statement.
+
+use crate::admin::{self, Scope};
+use crate::admin_keys;
+use crate::checkpoints::{self, SignedCheckpoint};
+use crate::logs::{self, ChainReport, LogEntry};
+use crate::security::{open_db, AppState};
+use ed25519_dalek::VerifyingKey;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+const BUNDLE_FORMAT: &str = "promptpilot-audit-log";
+const BUNDLE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat { Jsonl, Csv }
+
+impl ExportFormat {
+    fn from_path(path: &Path) -> Option<ExportFormat> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "jsonl" | "ndjson" | "json" => Some(ExportFormat::Jsonl),
+            "csv"                       => Some(ExportFormat::Csv),
+            _                           => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+enum JsonRecord {
+    Header { format: String, version: u32, exported_at: u64, entries: usize, checkpoints: usize },
+    Entry(LogEntry),
+    Checkpoint(SignedCheckpoint),
+}
+
+#[derive(Serialize, Deserialize)]
+struct CsvRow {
+    record:        String,
+    id:            i64,
+    ts:            String,
+    event:         String,
+    hash_prev:     String,
+    hash_curr:     String,
+    signature_b64: String,
+    pubkey_b64:    String,
+}
+
+#[derive(Serialize)]
+pub struct ExportResult {
+    pub path:        String,
+    pub format:      ExportFormat,
+    pub entries:     usize,
+    pub checkpoints: usize,
+}
+
+fn load_all() -> Result<(Vec<LogEntry>, Vec<SignedCheckpoint>), String> {
+    let conn = open_db("admin_logs.db");
+    logs::ensure_logs_table(&conn)?;
+    let mut stmt = conn.prepare(
+        "SELECT id, COALESCE(ts,''), COALESCE(event,''), COALESCE(hash_prev,''), COALESCE(hash_curr,'') FROM logs ORDER BY id"
+    ).map_err(|e| e.to_string())?;
+    let entries = stmt.query_map([], |r| Ok(LogEntry {
+        id: r.get(0)?, ts: r.get(1)?, event: r.get(2)?, hash_prev: r.get(3)?, hash_curr: r.get(4)?,
+    })).map_err(|e| e.to_string())?
+      .collect::<Result<Vec<_>, _>>()
+      .map_err(|e| e.to_string())?;
+    let cps = checkpoints::list(&conn, i64::MIN, i64::MAX)?;
+    Ok((entries, cps))
+}
+
+fn write_jsonl(out: &mut impl Write, entries: &[LogEntry], cps: &[SignedCheckpoint]) -> Result<(), String> {
+    let exported_at = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let header = JsonRecord::Header {
+        format: BUNDLE_FORMAT.into(), version: BUNDLE_VERSION, exported_at,
+        entries: entries.len(), checkpoints: cps.len(),
+    };
+    let mut line = |r: &JsonRecord| -> Result<(), String> {
+        let s = serde_json::to_string(r).map_err(|e| e.to_string())?;
+        writeln!(out, "{s}").map_err(|e| e.to_string())
+    };
+    line(&header)?;
+    for e in entries { line(&JsonRecord::Entry(e.clone()))?; }
+    for c in cps { line(&JsonRecord::Checkpoint(c.clone()))?; }
+    Ok(())
+}
+
+fn write_csv(out: impl Write, entries: &[LogEntry], cps: &[SignedCheckpoint]) -> Result<(), String> {
+    let mut w = csv::Writer::from_writer(out);
+    for e in entries {
+        w.serialize(CsvRow {
+            record: "entry".into(), id: e.id, ts: e.ts.clone(), event: e.event.clone(),
+            hash_prev: e.hash_prev.clone(), hash_curr: e.hash_curr.clone(),
+            signature_b64: String::new(), pubkey_b64: String::new(),
+        }).map_err(|e| e.to_string())?;
+    }
+    for c in cps {
+        w.serialize(CsvRow {
+            record: "checkpoint".into(), id: c.entry_id, ts: c.ts.to_string(), event: c.statement.clone(),
+            hash_prev: String::new(), hash_curr: c.head_hash.clone(),
+            signature_b64: c.signature_b64.clone(), pubkey_b64: c.pubkey_b64.clone(),
+        }).map_err(|e| e.to_string())?;
+    }
+    w.flush().map_err(|e| e.to_string())
+}
+
+/// Write the whole audit log and its checkpoints to `path`.
+#[tauri::command]
+pub fn logs_export(path: String, format: ExportFormat, state: tauri::State<AppState>) -> Result<ExportResult, String> {
+    admin::require(&state, Scope::LogsRead)?;
+    let (entries, cps) = load_all()?;
+    let target = Path::new(&path);
+    if let Some(dir) = target.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let file = std::fs::File::create(target).map_err(|e| format!("{path}: {e}"))?;
+    let mut out = std::io::BufWriter::new(file);
+    match format {
+        ExportFormat::Jsonl => write_jsonl(&mut out, &entries, &cps)?,
+        ExportFormat::Csv   => write_csv(&mut out, &entries, &cps)?,
+    }
+    out.flush().map_err(|e| e.to_string())?;
+    Ok(ExportResult { path, format, entries: entries.len(), checkpoints: cps.len() })
+}
+
+fn read_jsonl(path: &Path) -> Result<(Vec<LogEntry>, Vec<SignedCheckpoint>), String> {
+    let file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+    let (mut entries, mut cps, mut header) = (Vec::new(), Vec::new(), false);
+    for (n, line) in std::io::BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        if line.trim().is_empty() { continue; }
+        match serde_json::from_str(&line).map_err(|e| format!("line {}: {e}", n + 1))? {
+            JsonRecord::Header { format, version, .. } => {
+                if format != BUNDLE_FORMAT || version != BUNDLE_VERSION {
+                    return Err(format!("unsupported bundle: {format} v{version}"));
+                }
+                header = true;
+            }
+            JsonRecord::Entry(e)      => entries.push(e),
+            JsonRecord::Checkpoint(c) => cps.push(c),
+        }
+    }
+    if !header { return Err("not a PromptPilot audit log bundle (missing header)".into()); }
+    Ok((entries, cps))
+}
+
+fn read_csv(path: &Path) -> Result<(Vec<LogEntry>, Vec<SignedCheckpoint>), String> {
+    let mut r = csv::Reader::from_path(path).map_err(|e| e.to_string())?;
+    let (mut entries, mut cps) = (Vec::new(), Vec::new());
+    for (n, row) in r.deserialize::<CsvRow>().enumerate() {
+        let row = row.map_err(|e| format!("row {}: {e}", n + 2))?;
+        match row.record.as_str() {
+            "entry" => entries.push(LogEntry {
+                id: row.id, ts: row.ts, event: row.event, hash_prev: row.hash_prev, hash_curr: row.hash_curr,
+            }),
+            "checkpoint" => cps.push(SignedCheckpoint {
+                id: cps.len() as i64 + 1,
+                entry_id: row.id,
+                ts: row.ts.parse().map_err(|_| format!("row {}: bad checkpoint ts", n + 2))?,
+                statement: row.event,
+                head_hash: row.hash_curr,
+                signature_b64: row.signature_b64,
+                pubkey_b64: row.pubkey_b64,
+            }),
+            other => return Err(format!("row {}: unknown record type {other:?}", n + 2)),
+        }
+    }
+    Ok((entries, cps))
+}
+
+#[derive(Serialize)]
+pub struct ImportReport {
+    /// The chain is intact and a valid checkpoint signs its head. A self-consistent chain
+    /// alone proves nothing — anyone can rebuild one from `GENESIS`.
+    pub ok:                 bool,
+    pub format:             ExportFormat,
+    pub chain:              ChainReport,
+    /// Entry id of the latest checkpoint that verified, if any.
+    pub anchored_to:        Option<i64>,
+    /// Entries after `anchored_to` (all of them when nothing is anchored).
+    pub unanchored_entries: usize,
+    /// Base64 keys the checkpoint signatures were checked against.
+    pub trusted_keys:       Vec<String>,
+    /// Why no trusted keys could be loaded, if so.
+    pub trust_error:        Option<String>,
+}
+
+/// Verify an exported bundle without touching the live database. Checkpoint signatures are
+/// checked against `trusted_pubkey_b64` if given, else this install's admin key registry —
+/// never against the key embedded in the bundle itself.
+#[tauri::command]
+pub fn logs_import_verify(path: String, format: Option<ExportFormat>, trusted_pubkey_b64: Option<String>) -> Result<ImportReport, String> {
+    let p = Path::new(&path);
+    let format = format.or_else(|| ExportFormat::from_path(p))
+        .ok_or("cannot tell the bundle format from the file extension; pass `format`")?;
+    let (mut entries, cps) = match format {
+        ExportFormat::Jsonl => read_jsonl(p)?,
+        ExportFormat::Csv   => read_csv(p)?,
+    };
+    entries.sort_by_key(|e| e.id);
+
+    let trusted: Result<Vec<VerifyingKey>, String> = match trusted_pubkey_b64.as_deref().map(str::trim).filter(|k| !k.is_empty()) {
+        Some(b64) => base64::decode(b64).map_err(|e| e.to_string())
+            .and_then(|b| <[u8; 32]>::try_from(b.as_slice()).map_err(|_| "trusted key must be 32 bytes".to_string()))
+            .and_then(|b| VerifyingKey::from_bytes(&b).map_err(|e| e.to_string()))
+            .map(|k| vec![k]),
+        None => admin_keys::trusted_keys(),
+    };
+
+    let mut chain = logs::walk_chain(None, &entries);
+    let heads: HashMap<i64, &str> = entries.iter().map(|e| (e.id, e.hash_curr.as_str())).collect();
+    chain.checkpoints = cps.iter().map(|c| checkpoints::check(c, &trusted, heads.get(&c.entry_id).copied())).collect();
+    let chain = chain.finish();
+    let anchored_to = chain.checkpoints.iter().filter(|c| c.ok).map(|c| c.entry_id).max();
+    let unanchored_entries = entries.iter().filter(|e| anchored_to.is_none_or(|a| e.id > a)).count();
+    let (trusted_keys, trust_error) = match trusted {
+        Ok(keys) => (keys.iter().map(|k| base64::encode(k.as_bytes())).collect(), None),
+        Err(e)   => (Vec::new(), Some(e)),
+    };
+    Ok(ImportReport {
+        ok: chain.ok && anchored_to.is_some() && unanchored_entries == 0 && trust_error.is_none(),
+        format, chain, anchored_to, unanchored_entries, trusted_keys, trust_error,
+    })
+}
@@ -1,9 +1,52 @@
 
-use crate::security::open_db;
+use crate::admin::{self, Scope};
+use crate::checkpoints::{self, CheckpointCheck};
+use crate::security::{open_db, AppState};
 use rusqlite::params;
 use serde::{Deserialize, Serialize};
 use sha2::{Sha256, Digest};
 
+/// Everything that can be written to the audit log. Only backend code records events;
+/// each is stored as canonical JSON (sorted keys, no whitespace) and that string is hashed.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuditEvent {
+    ProfileCreated      { user_id: String },
+    ProfileUnlocked     { user_id: String },
+    ProfileUnlockFailed { user_id: String, reason: String },
+    PassphraseChanged   { user_id: String },
+    /// `statement` is the JSON the old key signed; `signature_b64` its signature.
+    KeyRotated          { user_id: String, statement: String, signature_b64: String },
+    GlobalDataMigrated  { user_id: String, history: usize, rag_chunks: usize, settings: usize },
+    AdminUnlocked       { #[serde(default)] scopes: Vec<String>, #[serde(default)] key_id: String },
+    AdminLocked         { reason: String },
+    AdminKeyAdded       { key_id: String, label: String, scopes: String },
+    AdminKeyRevoked     { key_id: String, reason: String },
+    /// One admin key signed approval `approval_id` for a destructive `action`.
+    ApprovalGranted     { approval_id: String, action: String, key_id: String },
+    ApprovalQuorumChanged { required: u32 },
+    ConsentRequested    { user_id: String, action: String },
+    /// A consent signature checked out and `receipt_id` was stored (see `consent.rs`).
+    ConsentVerified     { user_id: String, #[serde(default)] action: String, #[serde(default)] receipt_id: String, #[serde(default)] params_hash: String, #[serde(default)] signature_b64: String },
+    ConsentRedeemed     { user_id: String, action: String, receipt_id: String },
+    HistoryCleared      { user_id: String, count: usize, #[serde(default, skip_serializing_if = "std::ops::Not::not")] by_admin: bool },
+    RagDocIngested      { user_id: String, filename: String, chunks: usize },
+    RagDocDeleted       { user_id: String, filename: String, chunks: usize },
+    RagReembedded       { user_id: String, model: String, chunks: usize, failed: usize },
+    /// `user_id` is the profile unlocked at the time, if any (also for `PolicyDenial`).
+    SettingsChanged     { setting: String, #[serde(default, skip_serializing_if = "Option::is_none")] user_id: Option<String> },
+    PolicyDenial        { capability: String, reason: String, #[serde(default, skip_serializing_if = "Option::is_none")] user_id: Option<String> },
+    /// A failed or rate-limited admin unlock / consent attempt (see `nonce.rs`).
+    NonceRejected       { purpose: String, action: String, #[serde(default, skip_serializing_if = "Option::is_none")] user_id: Option<String>, reason: String },
+}
+
+impl AuditEvent {
+    /// Canonical form: `serde_json::Value` objects are key-sorted, and `to_string` is compact.
+    pub fn canonical(&self) -> String {
+        serde_json::to_value(self).map(|v| v.to_string()).unwrap_or_default()
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct LogEntry {
     pub id: i64,
@@ -21,26 +64,36 @@ fn head_hash(conn: &rusqlite::Connection) -> Option<String> {
     } else { None }
 }
 
-fn ensure_logs_table(conn: &rusqlite::Connection) -> Result<(), String> {
+fn chain_hash(prev: &str, event: &str) -> String {
+    let mut hasher = Sha256::new(); hasher.update(prev.as_bytes()); hasher.update(event.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+pub fn ensure_logs_table(conn: &rusqlite::Connection) -> Result<(), String> {
     conn.execute(
         "CREATE TABLE IF NOT EXISTS logs(id INTEGER PRIMARY KEY AUTOINCREMENT, ts DATETIME DEFAULT CURRENT_TIMESTAMP, event TEXT, hash_prev TEXT, hash_curr TEXT)",
         []
     ).map_err(|e| e.to_string())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS logs_ts_idx ON logs(ts)", []).map_err(|e| e.to_string())?;
     Ok(())
 }
 
-#[tauri::command]
-pub fn append_event(event: String) -> Result<String, String> {
+/// Record `event` in the hash chain and return the new head.
+pub fn record(event: &AuditEvent) -> Result<String, String> {
+    append(&event.canonical())
+}
+
+fn append(event: &str) -> Result<String, String> {
     let conn = open_db("admin_logs.db");
     ensure_logs_table(&conn)?;
     let prev = head_hash(&conn).unwrap_or_else(|| "GENESIS".into());
-    let mut hasher = Sha256::new(); hasher.update(prev.as_bytes()); hasher.update(event.as_bytes());
-    let curr = format!("{:x}", hasher.finalize());
+    let curr = chain_hash(&prev, event);
     conn.execute("INSERT INTO logs(event, hash_prev, hash_curr) VALUES(?1, ?2, ?3)",
         params![event, prev, curr]).map_err(|e| e.to_string())?;
     Ok(curr)
 }
 
+
 #[tauri::command]
 pub fn get_admin_log_head() -> Result<String, String> {
     let conn = open_db("admin_logs.db");
@@ -48,7 +101,8 @@ pub fn get_admin_log_head() -> Result<String, String> {
 }
 
 #[tauri::command]
-pub fn get_recent_logs(limit: u32) -> Result<Vec<LogEntry>, String> {
+pub fn get_recent_logs(limit: u32, state: tauri::State<AppState>) -> Result<Vec<LogEntry>, String> {
+    admin::require(&state, Scope::LogsRead)?;
     let conn = open_db("admin_logs.db");
     ensure_logs_table(&conn)?;
     let safe_limit = limit.min(100);
@@ -67,3 +121,185 @@ pub fn get_recent_logs(limit: u32) -> Result<Vec<LogEntry>, String> {
       .collect::<Result<Vec<_>, _>>();
     entries.map_err(|e| e.to_string())
 }
+
+#[derive(Serialize, Clone)]
+pub struct BrokenLink {
+    pub id:       i64,
+    /// `sha256(hash_prev || event)` as recomputed now.
+    pub expected: String,
+    pub stored:   String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct PrevMismatch {
+    pub id:        i64,
+    /// `hash_curr` of the preceding entry (or `GENESIS` for the first one).
+    pub expected:  String,
+    pub hash_prev: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct IdGap { pub after_id: i64, pub next_id: i64 }
+
+#[derive(Serialize, Default)]
+pub struct ChainReport {
+    pub ok:              bool,
+    pub checked:         usize,
+    pub from_id:         Option<i64>,
+    pub to_id:           Option<i64>,
+    /// First entry whose stored `hash_curr` doesn't match its contents.
+    pub first_broken:    Option<BrokenLink>,
+    pub prev_mismatches: Vec<PrevMismatch>,
+    pub gaps:            Vec<IdGap>,
+    /// Signed checkpoints whose entry falls in the range.
+    pub checkpoints:     Vec<CheckpointCheck>,
+}
+
+/// Re-walk the chain over `from_id..=to_id` (default: the whole log).
+/// The entry before `from_id` anchors the range, so a sub-range is checked against the rest.
+#[tauri::command]
+pub fn logs_verify_chain(from_id: Option<i64>, to_id: Option<i64>, state: tauri::State<AppState>) -> Result<ChainReport, String> {
+    admin::require(&state, Scope::LogsRead)?;
+    let conn = open_db("admin_logs.db");
+    ensure_logs_table(&conn)?;
+    let from = from_id.unwrap_or(i64::MIN);
+    let to   = to_id.unwrap_or(i64::MAX);
+    if from > to { return Err(format!("from_id {from} is after to_id {to}")); }
+
+    // (id, hash_curr) of the entry just before the range, if any
+    let prev: Option<(i64, String)> = conn.query_row(
+        "SELECT id, hash_curr FROM logs WHERE id < ?1 ORDER BY id DESC LIMIT 1",
+        params![from], |r| Ok((r.get(0)?, r.get(1)?))
+    ).ok();
+
+    let mut stmt = conn.prepare(
+        "SELECT id, COALESCE(ts,''), COALESCE(event,''), COALESCE(hash_prev,''), COALESCE(hash_curr,'') FROM logs WHERE id BETWEEN ?1 AND ?2 ORDER BY id"
+    ).map_err(|e| e.to_string())?;
+    let rows: Vec<LogEntry> = stmt.query_map(params![from, to], |r| Ok(LogEntry {
+        id: r.get(0)?, ts: r.get(1)?, event: r.get(2)?, hash_prev: r.get(3)?, hash_curr: r.get(4)?,
+    })).map_err(|e| e.to_string())?
+      .collect::<Result<Vec<_>, _>>()
+      .map_err(|e| e.to_string())?;
+
+    let mut report = walk_chain(prev, &rows);
+    report.checkpoints = checkpoints::verify_range(&conn, from, to)?;
+    Ok(report.finish())
+}
+
+impl ChainReport {
+    pub fn finish(mut self) -> Self {
+        self.ok = self.first_broken.is_none() && self.prev_mismatches.is_empty() && self.gaps.is_empty()
+            && self.checkpoints.iter().all(|c| c.ok);
+        self
+    }
+}
+
+/// Check links, `hash_prev` and id continuity over `entries` (ascending id).
+/// `prev` is `(id, hash_curr)` of the entry before the first one; `None` means it must start at `GENESIS`.
+pub fn walk_chain(mut prev: Option<(i64, String)>, entries: &[LogEntry]) -> ChainReport {
+    let mut report = ChainReport::default();
+    for e in entries {
+        let (id, hash_prev, hash_curr) = (e.id, &e.hash_prev, &e.hash_curr);
+        report.checked += 1;
+        report.from_id.get_or_insert(id);
+        report.to_id = Some(id);
+
+        let expected_prev = match &prev {
+            Some((prev_id, prev_hash)) => {
+                if id != prev_id + 1 { report.gaps.push(IdGap { after_id: *prev_id, next_id: id }); }
+                prev_hash.clone()
+            }
+            None => "GENESIS".to_string(),
+        };
+        if *hash_prev != expected_prev {
+            report.prev_mismatches.push(PrevMismatch { id, expected: expected_prev, hash_prev: hash_prev.clone() });
+        }
+        let expected = chain_hash(hash_prev, &e.event);
+        if expected != *hash_curr && report.first_broken.is_none() {
+            report.first_broken = Some(BrokenLink { id, expected, stored: hash_curr.clone() });
+        }
+        prev = Some((id, hash_curr.clone()));
+    }
+    report
+}
+
+/// Filters for `logs_query`. All are optional and combined with AND.
+#[derive(Deserialize, Default)]
+pub struct LogFilter {
+    /// Inclusive bounds on `ts`: RFC 3339 (any offset, converted to UTC) or a date; a date
+    /// as `to_ts` covers that whole day.
+    pub from_ts:    Option<String>,
+    pub to_ts:      Option<String>,
+    /// `AuditEvent` type, e.g. `profile_unlocked`.
+    pub event_type: Option<String>,
+    /// Profile the event concerns or was performed under (`user_id` in the payload).
+    pub actor:      Option<String>,
+    /// Case-insensitive substring of the event payload.
+    pub text:       Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct LogPage {
+    pub entries:     Vec<LogEntry>,
+    /// Pass as `before_id` to fetch the next (older) page; `None` on the last page.
+    pub next_cursor: Option<i64>,
+}
+
+/// An RFC 3339 timestamp or `YYYY-MM-DD` date as UTC `YYYY-MM-DD HH:MM:SS`, the format
+/// SQLite's CURRENT_TIMESTAMP writes; offsets such as `+02:00` are converted. SQLite's
+/// `datetime()` does the parsing. `end_of_day` turns a bare date into the start of the next
+/// day, for an exclusive upper bound. Returns the bound and whether it is exclusive.
+fn normalize_ts(conn: &rusqlite::Connection, ts: &str, end_of_day: bool) -> Result<(String, bool), String> {
+    let ts = ts.trim();
+    let date_only = ts.len() == 10 && ts.as_bytes().iter().enumerate()
+        .all(|(i, b)| if i == 4 || i == 7 { *b == b'-' } else { b.is_ascii_digit() });
+    let next_day = date_only && end_of_day;
+    let utc: Option<String> = conn.query_row(
+        "SELECT datetime(?1, ?2)", params![ts, if next_day { "+1 day" } else { "+0 seconds" }], |r| r.get(0)
+    ).map_err(|e| e.to_string())?;
+    let utc = utc.ok_or_else(|| format!("invalid timestamp {ts:?}: use RFC 3339 (2026-10-18T09:30:00Z) or a date (2026-10-18)"))?;
+    Ok((utc, next_day))
+}
+
+/// Search the log, newest first, paging by id: pass the returned `next_cursor` as `before_id`.
+#[tauri::command]
+pub fn logs_query(filter: Option<LogFilter>, before_id: Option<i64>, limit: Option<u32>, state: tauri::State<AppState>) -> Result<LogPage, String> {
+    admin::require(&state, Scope::LogsRead)?;
+    let filter = filter.unwrap_or_default();
+    let limit = limit.unwrap_or(50).clamp(1, 500);
+    let conn = open_db("admin_logs.db");
+    ensure_logs_table(&conn)?;
+
+    let mut conds: Vec<&str> = Vec::new();
+    let mut args: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+    let nonempty = |o: &Option<String>| o.as_deref().map(str::trim).filter(|s| !s.is_empty()).map(str::to_string);
+    if let Some(id) = before_id                  { conds.push("id < ?");  args.push(Box::new(id)); }
+    if let Some(ts) = nonempty(&filter.from_ts)  { conds.push("ts >= ?"); args.push(Box::new(normalize_ts(&conn, &ts, false)?.0)); }
+    if let Some(ts) = nonempty(&filter.to_ts) {
+        // A bare date includes that whole day
+        let (ts, exclusive) = normalize_ts(&conn, &ts, true)?;
+        conds.push(if exclusive { "ts < ?" } else { "ts <= ?" }); args.push(Box::new(ts));
+    }
+    // Entries from before structured events are free text, so guard json_extract with json_valid
+    if let Some(t) = nonempty(&filter.event_type) {
+        conds.push("CASE WHEN json_valid(event) THEN json_extract(event, '$.type') END = ?"); args.push(Box::new(t));
+    }
+    if let Some(a) = nonempty(&filter.actor) {
+        conds.push("CASE WHEN json_valid(event) THEN json_extract(event, '$.user_id') END = ?"); args.push(Box::new(a));
+    }
+    if let Some(q) = nonempty(&filter.text)      { conds.push("instr(lower(event), lower(?)) > 0"); args.push(Box::new(q)); }
+    args.push(Box::new(limit));
+
+    let sql = format!(
+        "SELECT id, COALESCE(ts,''), COALESCE(event,''), COALESCE(hash_prev,''), COALESCE(hash_curr,'') FROM logs {} ORDER BY id DESC LIMIT ?",
+        if conds.is_empty() { String::new() } else { format!("WHERE {}", conds.join(" AND ")) }
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let entries = stmt.query_map(rusqlite::params_from_iter(args.iter()), |r| Ok(LogEntry {
+        id: r.get(0)?, ts: r.get(1)?, event: r.get(2)?, hash_prev: r.get(3)?, hash_curr: r.get(4)?,
+    })).map_err(|e| e.to_string())?
+      .collect::<Result<Vec<_>, _>>()
+      .map_err(|e| e.to_string())?;
+    let next_cursor = if entries.len() == limit as usize { entries.last().map(|e| e.id) } else { None };
+    Ok(LogPage { entries, next_cursor })
+}
@@ -1,53 +1,104 @@
 
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 mod security;
+mod config;
+mod envelope;
+mod datakey;
+mod session;
 mod users;
 mod admin;
+mod admin_keys;
+mod nonce;
+mod consent;
 mod logs;
+mod checkpoints;
+mod log_export;
 mod policy;
+mod rego;
 mod ui;
 mod llm;
 mod rag;
 mod history;
 
-use security::{AppState, load_config};
+use config::load_config;
+use security::AppState;
+use tauri::Manager;
 
 fn main() {
-    load_config().ok();
+    let config = load_config().unwrap_or_else(|e| {
+        eprintln!("PromptPilot: {e} — using defaults");
+        Default::default()
+    });
     tauri::Builder::default()
-        .manage(AppState::default())
+        .manage(AppState { config: std::sync::Mutex::new(config), ..Default::default() })
         .invoke_handler(tauri::generate_handler![
             // User profiles
             users::create_profile,
             users::list_profiles,
             users::unlock_profile,
             users::get_public_key,
+            users::change_passphrase,
+            users::rotate_profile_key,
+            users::get_key_history,
+            users::migrate_global_data,
+            users::has_global_data,
+            session::sign_consent,
+            session::lock_profile,
+            session::session_user,
             // Admin
             admin::admin_get_nonce,
             admin::admin_unlock,
             admin::admin_status,
+            admin::admin_lock,
+            admin_keys::admin_keys_status,
+            admin_keys::admin_keys_list,
+            admin_keys::admin_keys_add,
+            admin_keys::admin_keys_revoke,
+            admin_keys::admin_keys_set_quorum,
+            admin_keys::admin_approval_request,
+            admin_keys::admin_approval_submit,
             admin::request_user_consent,
             admin::verify_user_consent_and_authorize,
+            consent::consent_receipts,
             // Audit logs
-            logs::append_event,
             logs::get_admin_log_head,
             logs::get_recent_logs,
+            logs::logs_verify_chain,
+            logs::logs_query,
+            checkpoints::logs_checkpoint_prepare,
+            checkpoints::logs_checkpoint_submit,
+            checkpoints::logs_checkpoints,
+            checkpoints::logs_export_checkpoint,
+            log_export::logs_export,
+            log_export::logs_import_verify,
             // Policy
             policy::policy_check,
+            policy::policy_explain,
+            policy::set_cloud_llm_consent,
+            policy::policy_update,
+            // Config (app.yaml)
+            config::get_config,
+            config::set_config,
             // UI / Settings
             ui::set_answer_style,
             ui::get_answer_style,
-            // AI / LLM (Ollama)
-            llm::ollama_status,
+            // AI / LLM (Ollama, llama.cpp, OpenAI-compatible)
+            llm::llm_status,
             llm::generate_answer,
             llm::score_answer,
             llm::analyze_job,
+            llm::llm_cancel,
             // RAG vector store
             rag::rag_ingest,
+            rag::rag_ingest_file,
             rag::rag_retrieve,
             rag::rag_build_context,
             rag::rag_list_docs,
             rag::rag_delete_doc,
+            rag::rag_embedding_status,
+            rag::rag_reembed_all,
+            rag::rag_reembed_status,
+            rag::rag_reembed_cancel,
             // Answer history
             history::history_save,
             history::history_save_score,
@@ -55,8 +106,12 @@ fn main() {
             history::history_list,
             history::history_delete,
             history::history_clear_all,
+            history::admin_clear_user_history,
+            history::admin_clear_all_history,
         ])
         .setup(|app| {
+            // admin_public.pem ships as a resource and seeds the admin key registry
+            if let Ok(dir) = app.path().resource_dir() { admin_keys::init(dir); }
             let _ = tauri::WindowBuilder::new(
                 app,
                 "splash",
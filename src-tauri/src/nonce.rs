@@ -0,0 +1,152 @@
+
+//! Challenge nonces for the admin unlock and user consent flows.
+//!
+//! Each nonce is bound to a purpose, an optional subject (the user asked for consent) and
+//! an action string, expires after a short TTL and is consumed by the first attempt to use
+//! it, whether that attempt succeeds or not, so a captured signature cannot be replayed.
+//! Issuing a new nonce for the same purpose and subject replaces the outstanding one.
+//! Failed attempts are counted per purpose and subject; too many within `FAILURE_WINDOW`
+//! blocks the flow until the window passes. Every rejection is written to the audit log.
+//! Callers check `blocked` before issuing or consuming; a blocked attempt is refused as is,
+//! without counting as another failure.
+
+use crate::logs::{self, AuditEvent};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+pub const ADMIN_UNLOCK_TTL: Duration = Duration::from_secs(120);
+pub const USER_CONSENT_TTL: Duration = Duration::from_secs(300);
+
+const MAX_FAILURES:   usize    = 5;
+const FAILURE_WINDOW: Duration = Duration::from_secs(300);
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Purpose { AdminUnlock, UserConsent }
+
+impl Purpose {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Purpose::AdminUnlock => "admin_unlock",
+            Purpose::UserConsent => "user_consent",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Nonce {
+    pub value:   String,
+    pub subject: Option<String>,
+    pub action:  String,
+    /// SHA-256 of the action's canonical parameters (empty when there are none).
+    pub params_hash: String,
+    /// Unix time the nonce stops being accepted (also signed into consent payloads).
+    pub expires_at:  u64,
+    created:     Instant,
+    ttl:         Duration,
+}
+
+impl Nonce {
+    fn expired(&self) -> bool { self.created.elapsed() >= self.ttl }
+}
+
+pub fn now_secs() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+type Slot = (Purpose, Option<String>);
+
+#[derive(Default)]
+pub struct NonceStore {
+    outstanding: HashMap<Slot, Nonce>,
+    failures:    HashMap<Slot, Vec<Instant>>,
+    /// Slots whose current block has been written to the audit log.
+    block_logged: HashSet<Slot>,
+}
+
+impl NonceStore {
+    /// Refuse the flow while `MAX_FAILURES` failures fall inside `FAILURE_WINDOW`. Return
+    /// the error as is, not through `reject`: it neither extends the block nor is logged
+    /// again after the first refusal.
+    pub fn blocked(&mut self, purpose: Purpose, subject: Option<&str>, action: &str) -> Result<(), String> {
+        let slot = (purpose, subject.map(str::to_string));
+        let Some(times) = self.failures.get_mut(&slot) else { return Ok(()) };
+        times.retain(|t| t.elapsed() < FAILURE_WINDOW);
+        if times.len() < MAX_FAILURES {
+            self.block_logged.remove(&slot);
+            return Ok(());
+        }
+        let wait = FAILURE_WINDOW.saturating_sub(times[0].elapsed()).as_secs() + 1;
+        let reason = format!("too many failed attempts; try again in {wait}s");
+        if self.block_logged.insert(slot) {
+            let _ = logs::record(&AuditEvent::NonceRejected {
+                purpose: purpose.as_str().into(),
+                action:  action.into(),
+                user_id: subject.map(str::to_string),
+                reason:  reason.clone(),
+            });
+        }
+        Err(reason)
+    }
+
+    /// Issue a fresh nonce, replacing any outstanding one for the same purpose and subject.
+    pub fn issue(&mut self, purpose: Purpose, subject: Option<&str>, action: &str, params_hash: &str, ttl: Duration) -> Nonce {
+        let slot = (purpose, subject.map(str::to_string));
+        let nonce = Nonce {
+            value: nanoid::nanoid!(24),
+            subject: slot.1.clone(),
+            action: action.to_string(),
+            params_hash: params_hash.to_string(),
+            expires_at: now_secs() + ttl.as_secs(),
+            created: Instant::now(),
+            ttl,
+        };
+        self.outstanding.insert(slot, nonce.clone());
+        nonce
+    }
+
+    /// Look at the outstanding nonce without consuming it (e.g. to sign it).
+    pub fn peek(&mut self, purpose: Purpose, subject: Option<&str>) -> Result<&Nonce, String> {
+        let slot = (purpose, subject.map(str::to_string));
+        if self.outstanding.get(&slot).is_some_and(Nonce::expired) {
+            self.outstanding.remove(&slot);
+        }
+        self.outstanding.get(&slot).ok_or_else(|| format!("no pending {} nonce", purpose.as_str()))
+    }
+
+    /// Remove and return the outstanding nonce. `value`, when given, must match it.
+    /// The nonce is gone afterwards even if the check fails.
+    pub fn consume(&mut self, purpose: Purpose, subject: Option<&str>, value: Option<&str>) -> Result<Nonce, String> {
+        let slot = (purpose, subject.map(str::to_string));
+        let nonce = self.outstanding.remove(&slot)
+            .ok_or_else(|| format!("no pending {} nonce (already used or never issued)", purpose.as_str()))?;
+        if nonce.expired() { return Err(format!("{} nonce expired", purpose.as_str())); }
+        if value.is_some_and(|v| v != nonce.value) { return Err("nonce mismatch".into()); }
+        Ok(nonce)
+    }
+
+    fn record_failure(&mut self, purpose: Purpose, subject: Option<&str>) {
+        self.failures.entry((purpose, subject.map(str::to_string))).or_default().push(Instant::now());
+    }
+
+    /// Forget the failure history after a successful attempt.
+    pub fn succeeded(&mut self, purpose: Purpose, subject: Option<&str>) {
+        let slot = (purpose, subject.map(str::to_string));
+        self.failures.remove(&slot);
+        self.block_logged.remove(&slot);
+    }
+}
+
+/// Count a failed attempt and write it to the audit log. Returns `reason` so callers can
+/// write `return Err(nonce::reject(...))`.
+pub fn reject(store: &mut NonceStore, purpose: Purpose, subject: Option<&str>, action: &str, reason: String) -> String {
+    store.record_failure(purpose, subject);
+    let _ = logs::record(&AuditEvent::NonceRejected {
+        purpose: purpose.as_str().into(),
+        action:  action.into(),
+        user_id: subject.map(str::to_string),
+        reason:  reason.clone(),
+    });
+    reason
+}
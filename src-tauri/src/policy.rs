@@ -1,20 +1,229 @@
 
-/// Simple built-in policy engine.
-/// Allowlist of permitted capability strings.
-const ALLOWED_CAPABILITIES: &[&str] = &[
-    "admin.unlock",
-    "admin.view_logs",
-    "user.create_profile",
-    "user.unlock_profile",
-    "consent.request",
-    "consent.verify",
-    "logs.append",
-    "logs.read",
-    "settings.write",
-];
+/// Capability policy engine.
+/// Evaluates `data.promptpilot.policy.allow` from the `.rego` files in `<data dir>/policies/`
+/// against a structured `PolicyInput` built from the live `AppState`. The bundled
+/// `policies/policy.rego` is copied there on first use; edits take effect on the next check.
+use crate::admin;
+use crate::logs::{self, AuditEvent};
+use crate::rego;
+use crate::session;
+use crate::security::{data_dir, write_atomic, AppState};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
 
+const PACKAGE: &str = "promptpilot.policy";
+const BUILTIN_POLICY: &str = include_str!("../../policies/policy.rego");
+
+pub const NETWORK_CLOUD_LLM: &str     = "NETWORK_CLOUD_LLM";
+pub const ADMIN_ACTION: &str          = "ADMIN_ACTION";
+pub const LOGS_READ: &str             = "LOGS_READ";
+/// Needs a consent receipt for the specific action; only checked through `consent::redeem`.
+pub const ADMIN_APPROVED_ACTION: &str = "ADMIN_APPROVED_ACTION";
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Role { User, Admin }
+
+/// The `input` document a policy is evaluated against (field names match the Rego).
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PolicyInput {
+    pub capability:         String,
+    pub role:               Role,
+    pub privacy_mode:       bool,
+    pub session_live:       bool,
+    pub consent_cloud_llm:  bool,
+    pub user_consent_valid: bool,
+}
+
+#[derive(Debug, Clone, Serialize, thiserror::Error)]
+#[serde(tag = "reason", content = "capability")]
+pub enum PolicyDenial {
+    #[error("Policy denied {0}: unknown capability")]
+    UnknownCapability(String),
+    #[error("Policy denied {0}: blocked while privacy_mode is on")]
+    PrivacyMode(String),
+    #[error("Policy denied {0}: requires the admin role")]
+    NotAdmin(String),
+    #[error("Policy denied {0}: requires a live (unlocked) profile session")]
+    NoLiveSession(String),
+    #[error("Policy denied {0}: requires consent to use a network LLM")]
+    NoCloudConsent(String),
+    #[error("Policy denied {0}: requires a verified user consent")]
+    NoUserConsent(String),
+    #[error("Policy denied {capability}: `{expr}` is not satisfied")]
+    Denied { capability: String, expr: String },
+    #[error("Policy could not be evaluated: {0}")]
+    PolicyError(String),
+}
+
+impl From<PolicyDenial> for String {
+    fn from(d: PolicyDenial) -> String { d.to_string() }
+}
+
+// ── Loading ──────────────────────────────────────────────────────────────────
+pub fn policy_dir() -> PathBuf { data_dir().join("policies") }
+
+/// Parse every `*.rego` in `policy_dir()` that declares `package promptpilot.policy`.
+fn load_modules() -> Result<Vec<rego::Module>, String> {
+    let dir = policy_dir();
+    if !dir.exists() {
+        std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        std::fs::write(dir.join("policy.rego"), BUILTIN_POLICY).map_err(|e| e.to_string())?;
+    }
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|x| x == "rego"))
+        .collect();
+    paths.sort();
+
+    let mut modules = Vec::new();
+    for path in paths {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let src = std::fs::read_to_string(&path).map_err(|e| format!("{name}: {e}"))?;
+        let module = rego::parse(&name, &src)?;
+        if module.package == PACKAGE { modules.push(module); }
+    }
+    if modules.is_empty() {
+        return Err(format!("no `package {PACKAGE}` policy found in {}", dir.display()));
+    }
+    Ok(modules)
+}
+
+// ── Evaluation ───────────────────────────────────────────────────────────────
+/// `allow` rules that test `input.capability == <capability>`, paired with their traces.
+fn rules_for<'a>(
+    modules: &'a [rego::Module],
+    decision: &'a rego::Decision,
+    capability: &str,
+) -> Vec<(&'a rego::Rule, &'a rego::RuleTrace)> {
+    let cap = Value::String(capability.to_string());
+    modules.iter()
+        .flat_map(|m| m.rules.iter())
+        .filter(|r| r.name == "allow" && !r.is_default)
+        .zip(decision.rules.iter())
+        .filter(|(r, _)| rego::rule_mentions(r, "capability", &cap))
+        .collect()
+}
+
+/// Map the first unmet condition of the capability's first rule to a typed denial.
+fn denial(capability: &str, candidates: &[(&rego::Rule, &rego::RuleTrace)]) -> PolicyDenial {
+    let cap = capability.to_string();
+    let Some((_, trace)) = candidates.first() else { return PolicyDenial::UnknownCapability(cap) };
+    match trace.failed_input.as_deref() {
+        Some("privacy_mode")       => PolicyDenial::PrivacyMode(cap),
+        Some("role")               => PolicyDenial::NotAdmin(cap),
+        Some("session_live")       => PolicyDenial::NoLiveSession(cap),
+        Some("consent_cloud_llm")  => PolicyDenial::NoCloudConsent(cap),
+        Some("user_consent_valid") => PolicyDenial::NoUserConsent(cap),
+        _ => PolicyDenial::Denied { capability: cap, expr: trace.failed_expr.clone().unwrap_or_default() },
+    }
+}
+
+fn decide(input: &Value) -> Result<(Vec<rego::Module>, rego::Decision), PolicyDenial> {
+    let modules  = load_modules().map_err(PolicyDenial::PolicyError)?;
+    let decision = rego::query(&modules, "allow", input);
+    Ok((modules, decision))
+}
+
+/// Evaluate `input` — default deny; the first unmet condition is the denial reason.
+pub fn evaluate(input: &PolicyInput) -> Result<(), PolicyDenial> {
+    let json = serde_json::to_value(input).map_err(|e| PolicyDenial::PolicyError(e.to_string()))?;
+    let (modules, decision) = decide(&json)?;
+    if decision.value == Some(Value::Bool(true)) {
+        return Ok(());
+    }
+    Err(denial(&input.capability, &rules_for(&modules, &decision, &input.capability)))
+}
+
+/// Snapshot the session flags relevant to policy from `AppState`. `user_consent_valid` is
+/// always false here: consent is per action, so only `consent::redeem` sets it.
+pub fn input_for(state: &AppState, capability: &str) -> PolicyInput {
+    PolicyInput {
+        capability:         capability.to_string(),
+        role:               if admin::is_unlocked(state) { Role::Admin } else { Role::User },
+        privacy_mode:       state.config.lock().unwrap().privacy_mode,
+        session_live:       session::current_user(state).is_some(),
+        consent_cloud_llm:  *state.consent_cloud_llm.lock().unwrap(),
+        user_consent_valid: false,
+    }
+}
+
+/// Guard for sensitive commands: `policy::enforce(&state, policy::LOGS_READ)?;`
+/// Denials are recorded in the audit log.
+pub fn enforce(state: &AppState, capability: &str) -> Result<(), PolicyDenial> {
+    enforce_input(state, &input_for(state, capability))
+}
+
+/// `enforce` with a prepared input, for guards that add facts of their own.
+pub fn enforce_input(state: &AppState, input: &PolicyInput) -> Result<(), PolicyDenial> {
+    let result = evaluate(input);
+    if let Err(denial) = &result {
+        // Best effort: a logging failure must not turn a denial into something else
+        let _ = logs::record(&AuditEvent::PolicyDenial {
+            capability: input.capability.clone(), reason: denial.to_string(), user_id: session::current_user(state),
+        });
+    }
+    result
+}
+
+/// UI probe (e.g. whether to offer the mic); not a guarded action, so not logged.
+#[tauri::command]
+pub fn policy_check(capability: String, state: tauri::State<AppState>) -> Result<bool, String> {
+    Ok(evaluate(&input_for(&state, &capability)).is_ok())
+}
+
+#[derive(Serialize)]
+pub struct Explanation {
+    pub capability: String,
+    pub allowed:    bool,
+    pub input:      Value,
+    /// The rule that decided the outcome (`default allow` when nothing matched).
+    pub matched:    Option<rego::RuleTrace>,
+    /// Rules for this capability, each with the first condition that failed.
+    pub rules:      Vec<rego::RuleTrace>,
+    pub denial:     Option<String>,
+}
+
+/// Explain a decision. `input` fields override the live session values,
+/// so "what if" questions (e.g. `{ "privacy_mode": false }`) can be asked.
+#[tauri::command]
+pub fn policy_explain(capability: String, input: Option<Value>, state: tauri::State<AppState>) -> Result<Explanation, String> {
+    let mut json = serde_json::to_value(input_for(&state, &capability)).map_err(|e| e.to_string())?;
+    if let (Some(Value::Object(over)), Value::Object(base)) = (input, &mut json) {
+        for (k, v) in over { if k != "capability" { base.insert(k, v); } }
+    }
+    let (modules, decision) = decide(&json)?;
+    let allowed    = decision.value == Some(Value::Bool(true));
+    let candidates = rules_for(&modules, &decision, &capability);
+    let denial     = (!allowed).then(|| denial(&capability, &candidates).to_string());
+    let rules      = candidates.iter().map(|(_, t)| (*t).clone()).collect();
+    Ok(Explanation { capability, allowed, input: json, matched: decision.matched.clone(), rules, denial })
+}
+
+/// Session-scoped consent to send prompts/resume text to a non-loopback LLM endpoint.
+#[tauri::command]
+pub fn set_cloud_llm_consent(granted: bool, state: tauri::State<AppState>) -> Result<(), String> {
+    let changed = std::mem::replace(&mut *state.consent_cloud_llm.lock().unwrap(), granted) != granted;
+    if changed {
+        logs::record(&AuditEvent::SettingsChanged { setting: format!("consent_cloud_llm={granted}"), user_id: session::current_user(&state) })?;
+    }
+    Ok(())
+}
+
+/// Replace (or add) one policy file. The source must parse and declare this package;
+/// it takes effect on the next check. Needs the `policy.manage` admin scope.
 #[tauri::command]
-pub fn policy_check(capability: String) -> Result<bool, String> {
-    let allowed = ALLOWED_CAPABILITIES.contains(&capability.as_str());
-    Ok(allowed)
+pub fn policy_update(file: String, source: String, state: tauri::State<AppState>) -> Result<(), String> {
+    admin::require(&state, admin::Scope::PolicyManage)?;
+    let valid_name = file.strip_suffix(".rego")
+        .is_some_and(|stem| !stem.is_empty() && stem.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'));
+    if !valid_name { return Err(format!("invalid policy file name {file:?} (expected e.g. \"policy.rego\")")); }
+    let module = rego::parse(&file, &source)?;
+    if module.package != PACKAGE { return Err(format!("{file}: expected `package {PACKAGE}`")); }
+    load_modules()?; // creates the directory and bundled policy on first use
+    write_atomic(&policy_dir().join(&file), source.as_bytes())?;
+    logs::record(&AuditEvent::SettingsChanged { setting: format!("policies/{file}"), user_id: session::current_user(&state) })?;
+    Ok(())
 }
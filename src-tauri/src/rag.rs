@@ -1,11 +1,36 @@
 
 //! RAG (Retrieval-Augmented Generation) vector store
-//! Uses SQLite for storage, pure-Rust cosine similarity (no external extension needed)
+//! Uses SQLite for storage and an in-process HNSW index (`rag/index.rs`) for retrieval, with
+//! an exact pure-Rust cosine scan as the fallback (no external extension needed).
+//! Retrieval is hybrid by default: the vector ranking is fused with an FTS5 BM25 ranking
+//! (`rag/lexical.rs`) so exact terms like "Kafka" are not lost to the embedding.
+//! The store is per profile (`profiles/{user_id}/rag.db`) and requires an unlocked session.
+//! Chunk `content`, `section` and `embedding` are encrypted with the profile's data key
+//! (`enc = 1`); filenames are kept in the clear for listing and deletion.
+//! Each embedding records the model and dimension that produced it (`embed_model`,
+//! `embed_dim`); retrieval only compares vectors from the query's model, and
+//! `rag_reembed_all` moves the whole vault to a new model in the background.
 
-use crate::security::{open_db, db_path};
+use crate::config::AppConfig;
+use crate::datakey::{self, DataKey};
+use crate::security::AppState;
+use crate::session;
 use crate::llm;
-use rusqlite::params;
+use crate::logs::{self, AuditEvent};
+use rusqlite::{params, OptionalExtension};
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+mod chunker;
+mod extract;
+mod hnsw;
+mod index;
+mod lexical;
+mod reembed;
+pub use chunker::ChunkStrategy;
+pub use index::{RagIndex, SearchMode};
+pub use reembed::{ReembedJob, ReembedProgress};
 
 // ── Embedding helpers ────────────────────────────────────────────────────────
 fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
@@ -25,7 +50,7 @@ fn bytes_to_emb(bytes: &[u8]) -> Vec<f32> {
 }
 
 // ── Table ────────────────────────────────────────────────────────────────────
-fn ensure_rag_table(conn: &rusqlite::Connection) -> Result<(), String> {
+pub fn ensure_rag_table(conn: &rusqlite::Connection) -> Result<(), String> {
     conn.execute_batch(
         "CREATE TABLE IF NOT EXISTS rag_docs (
             id          INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -33,34 +58,90 @@ fn ensure_rag_table(conn: &rusqlite::Connection) -> Result<(), String> {
             chunk_idx   INTEGER NOT NULL,
             content     TEXT    NOT NULL,
             embedding   BLOB,
-            created_at  DATETIME DEFAULT CURRENT_TIMESTAMP
+            created_at  DATETIME DEFAULT CURRENT_TIMESTAMP,
+            enc         INTEGER NOT NULL DEFAULT 0,
+            section     TEXT,
+            embed_model TEXT,
+            embed_dim   INTEGER
         );
         CREATE INDEX IF NOT EXISTS rag_filename_idx ON rag_docs(filename);"
-    ).map_err(|e| e.to_string())
+    ).map_err(|e| e.to_string())?;
+    // Tables created before encryption at rest lack the `enc` flag
+    if conn.prepare("SELECT enc FROM rag_docs LIMIT 0").is_err() {
+        conn.execute("ALTER TABLE rag_docs ADD COLUMN enc INTEGER NOT NULL DEFAULT 0", [])
+            .map_err(|e| e.to_string())?;
+    }
+    // Later additions; rows from before them have NULL here (no section, and an unknown
+    // model until `backfill_embed_model` runs at unlock)
+    for (column, decl) in [("section", "TEXT"), ("embed_model", "TEXT"), ("embed_dim", "INTEGER")] {
+        if conn.prepare(&format!("SELECT {column} FROM rag_docs LIMIT 0")).is_err() {
+            conn.execute(&format!("ALTER TABLE rag_docs ADD COLUMN {column} {decl}"), [])
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
 }
 
-// ── Chunking ─────────────────────────────────────────────────────────────────
-fn chunk_text(text: &str, chunk_chars: usize, overlap: usize) -> Vec<String> {
-    let chars: Vec<char> = text.chars().collect();
-    let mut chunks = Vec::new();
-    let mut start = 0;
-    while start < chars.len() {
-        let end = (start + chunk_chars).min(chars.len());
-        let chunk: String = chars[start..end].iter().collect();
-        chunks.push(chunk);
-        if end == chars.len() { break; }
-        start = end.saturating_sub(overlap);
+const AAD_CONTENT: &str   = "rag_docs.content";
+const AAD_SECTION: &str   = "rag_docs.section";
+const AAD_EMBEDDING: &str = "rag_docs.embedding";
+
+/// Encrypt chunks stored in the clear (`enc = 0`), then compact the file.
+pub fn encrypt_plaintext_rows(conn: &rusqlite::Connection, key: &DataKey) -> Result<usize, String> {
+    ensure_rag_table(conn)?;
+    let rows: Vec<(i64, String, Option<Vec<u8>>)> = {
+        let mut stmt = conn.prepare("SELECT id, content, embedding FROM rag_docs WHERE enc=0")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>();
+        rows.map_err(|e| e.to_string())?
+    };
+    if rows.is_empty() { return Ok(0); }
+    for (id, content, emb) in &rows {
+        let emb = emb.as_deref().map(|e| key.seal_bytes(AAD_EMBEDDING, e)).transpose()?;
+        conn.execute(
+            "UPDATE rag_docs SET content=?1, embedding=?2, enc=1 WHERE id=?3",
+            params![key.seal_text(AAD_CONTENT, content)?, emb, id],
+        ).map_err(|e| e.to_string())?;
     }
-    chunks
+    conn.execute_batch("VACUUM;").map_err(|e| e.to_string())?;
+    Ok(rows.len())
+}
+
+/// Label embeddings stored before the model was recorded (`embed_model IS NULL`) with
+/// `model`, the configured one and the best guess at what produced them, and with their
+/// dimension, so retrieval and `rag_reembed_all` treat them like any other chunk.
+pub fn backfill_embed_model(conn: &rusqlite::Connection, model: &str) -> Result<usize, String> {
+    ensure_rag_table(conn)?;
+    conn.execute(
+        "UPDATE rag_docs SET embed_model=?1,
+            embed_dim = (length(embedding) - CASE enc WHEN 1 THEN ?2 ELSE 0 END) / 4
+         WHERE embedding IS NOT NULL AND embed_model IS NULL",
+        params![model, datakey::SEAL_OVERHEAD as i64],
+    ).map_err(|e| e.to_string())
 }
 
 // ── Structs ───────────────────────────────────────────────────────────────────
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum RetrievalMode {
+    /// Vector and BM25 rankings fused with reciprocal rank fusion.
+    Hybrid,
+    /// Embeddings only.
+    Vector,
+    /// BM25 only; needs no embedding model.
+    Lexical,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct DocChunk {
     pub id:         i64,
     pub filename:   String,
     pub chunk_idx:  i64,
     pub content:    String,
+    /// Heading path the chunk sits under, e.g. `Experience › Acme Corp`.
+    pub section:    Option<String>,
     pub score:      Option<f32>,
     pub created_at: String,
 }
@@ -70,89 +151,299 @@ pub struct IngestResult {
     pub filename: String,
     pub chunks:   usize,
     pub embedded: usize,
+    pub sections: usize,
+    pub strategy: ChunkStrategy,
+    /// Detected source format (`rag_ingest_file` only).
+    pub format:   Option<extract::Format>,
+}
+
+fn chunk_ids(conn: &rusqlite::Connection, filename: &str) -> Result<Vec<i64>, String> {
+    let mut stmt = conn.prepare("SELECT id FROM rag_docs WHERE filename = ?1").map_err(|e| e.to_string())?;
+    let ids = stmt.query_map(params![filename], |r| r.get(0)).map_err(|e| e.to_string())?
+        .collect::<Result<Vec<i64>, _>>();
+    ids.map_err(|e| e.to_string())
 }
 
 // ── Commands ──────────────────────────────────────────────────────────────────
 
 /// Ingest a text document into the RAG store.
-/// Chunks the text and embeds each chunk via Ollama.
+/// Chunks the text and embeds each chunk with the configured LLM backend.
+/// `strategy` and `max_tokens` default to `rag.chunk_strategy` / `rag.chunk_tokens`.
 #[tauri::command]
-pub fn rag_ingest(filename: String, content: String, embed_model: String) -> Result<IngestResult, String> {
-    let conn = open_db("rag.db");
-    ensure_rag_table(&conn)?;
+pub fn rag_ingest(
+    filename: String,
+    content: String,
+    embed_model: Option<String>,
+    strategy: Option<ChunkStrategy>,
+    max_tokens: Option<usize>,
+    state: tauri::State<AppState>,
+) -> Result<IngestResult, String> {
+    ingest(&state, filename, &content, embed_model, strategy, max_tokens)
+}
 
-    // Remove old chunks for same filename
-    conn.execute("DELETE FROM rag_docs WHERE filename = ?1", params![filename])
-        .map_err(|e| e.to_string())?;
+/// Ingest a PDF, DOCX, Markdown, HTML or plain-text file from disk.
+/// The format is taken from the extension (or sniffed from the content); headings in the
+/// file become each chunk's `section`. `filename` defaults to the file's name.
+#[tauri::command]
+pub fn rag_ingest_file(
+    path: String,
+    filename: Option<String>,
+    embed_model: Option<String>,
+    strategy: Option<ChunkStrategy>,
+    max_tokens: Option<usize>,
+    state: tauri::State<AppState>,
+) -> Result<IngestResult, String> {
+    let path = std::path::Path::new(&path);
+    let (format, text) = extract::extract(path)?;
+    let filename = filename.filter(|f| !f.trim().is_empty())
+        .or_else(|| path.file_name().map(|n| n.to_string_lossy().into_owned()))
+        .ok_or("cannot tell the document name from the path; pass `filename`")?;
+    let mut result = ingest(&state, filename, &text, embed_model, strategy, max_tokens)?;
+    result.format = Some(format);
+    Ok(result)
+}
+
+fn ingest(
+    state: &AppState,
+    filename: String,
+    content: &str,
+    embed_model: Option<String>,
+    strategy: Option<ChunkStrategy>,
+    max_tokens: Option<usize>,
+) -> Result<IngestResult, String> {
+    let cfg: AppConfig = state.config.lock().unwrap().clone();
+    let embed_model = embed_model.unwrap_or(cfg.llm.embed_model.clone());
+    let params = chunker::ChunkParams {
+        strategy:      strategy.unwrap_or(cfg.rag.chunk_strategy),
+        max_tokens:    max_tokens.unwrap_or(cfg.rag.chunk_tokens),
+        fixed_chars:   cfg.rag.chunk_size,
+        fixed_overlap: cfg.rag.chunk_overlap,
+    };
+    let backend = llm::current_backend(state)?;
+    let (mut conn, key) = session::open_encrypted_db(state, "rag.db")?;
+    ensure_rag_table(&conn)?;
 
-    let chunks = chunk_text(&content, 500, 80);
+    let chunks = chunker::chunk(content, &params);
     let total  = chunks.len();
     let mut embedded = 0;
+    let mut sections = std::collections::HashSet::new();
+    let mut vectors = Vec::with_capacity(total);
+    let mut rows = Vec::with_capacity(total);
 
-    for (i, chunk) in chunks.iter().enumerate() {
-        let emb_opt = llm::embed(chunk, &embed_model).ok().map(|e| emb_to_bytes(&e));
-        conn.execute(
-            "INSERT INTO rag_docs(filename, chunk_idx, content, embedding) VALUES(?1,?2,?3,?4)",
-            params![filename, i as i64, chunk, emb_opt],
+    // Embed and seal everything before touching the table, so the write below is short
+    let mut sealed = Vec::with_capacity(total);
+    for chunk in &chunks {
+        // The section path is embedded with the text so "experience at Acme" finds Acme's bullets
+        let emb = backend.embed(&chunk.with_context(), &embed_model).ok();
+        let emb_opt = emb.as_deref().map(emb_to_bytes);
+        let emb_enc = emb_opt.as_deref().map(|e| key.seal_bytes(AAD_EMBEDDING, e)).transpose()?;
+        let section = chunk.section.as_deref().map(|s| key.seal_text(AAD_SECTION, s)).transpose()?;
+        sealed.push((key.seal_text(AAD_CONTENT, &chunk.text)?, section, emb_enc, emb));
+    }
+
+    // Replace the old chunks for this filename in one transaction: a failure keeps the old version
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let old_ids = chunk_ids(&tx, &filename)?;
+    tx.execute("DELETE FROM rag_docs WHERE filename = ?1", params![filename])
+        .map_err(|e| e.to_string())?;
+    for (i, (chunk, (text, section, emb_enc, emb))) in chunks.iter().zip(sealed).enumerate() {
+        let (model, dim) = match &emb { Some(e) => (Some(embed_model.as_str()), Some(e.len() as i64)), None => (None, None) };
+        tx.execute(
+            "INSERT INTO rag_docs(filename, chunk_idx, content, section, embedding, embed_model, embed_dim, enc) VALUES(?1,?2,?3,?4,?5,?6,?7,1)",
+            params![filename, i as i64, text, section, emb_enc, model, dim],
         ).map_err(|e| e.to_string())?;
-        if emb_opt.is_some() { embedded += 1; }
+        let id = tx.last_insert_rowid();
+        if let Some(e) = emb { vectors.push((id, e)); embedded += 1; }
+        rows.push((id, chunk));
+        sections.extend(chunk.section.as_deref());
     }
+    tx.commit().map_err(|e| e.to_string())?;
+    index::with_index(state, &conn, &key, &embed_model, |ix| {
+        for &id in &old_ids {
+            ix.graph.remove(id);
+            ix.lexical.remove(id)?;
+        }
+        for (id, e) in &vectors { ix.graph.insert(*id, e); }
+        for (id, chunk) in &rows { ix.lexical.insert(*id, &chunk.text, chunk.section.as_deref())?; }
+        Ok::<_, String>(())
+    })??;
 
-    Ok(IngestResult { filename, chunks: total, embedded })
+    let user_id = session::current_user(state).unwrap_or_default();
+    logs::record(&AuditEvent::RagDocIngested { user_id, filename: filename.clone(), chunks: total })?;
+    Ok(IngestResult { filename, chunks: total, embedded, sections: sections.len(), strategy: params.strategy, format: None })
 }
 
 /// Retrieve the top-k most relevant chunks for a query.
+/// `top_k` and `embed_model` default to the `rag.top_k` / `llm.embed_model` config values;
+/// `mode` to `rag.retrieval` and `search` to `rag.search` (`exact` skips the ANN index).
 #[tauri::command]
-pub fn rag_retrieve(query: String, top_k: u32, embed_model: String) -> Result<Vec<DocChunk>, String> {
-    let conn = open_db("rag.db");
-    ensure_rag_table(&conn)?;
+pub fn rag_retrieve(
+    query: String,
+    top_k: Option<u32>,
+    embed_model: Option<String>,
+    search: Option<SearchMode>,
+    mode: Option<RetrievalMode>,
+    state: tauri::State<AppState>,
+) -> Result<Vec<DocChunk>, String> {
+    let cfg: AppConfig = state.config.lock().unwrap().clone();
+    let (conn, key) = session::open_encrypted_db(&state, "rag.db")?;
+    retrieve(&state, &conn, &key, &cfg, &query, top_k, embed_model, search, mode)
+}
 
-    let query_emb = llm::embed(&query, &embed_model)?;
+/// Rank constant for reciprocal rank fusion; 60 is the value from the original paper and
+/// keeps a single top hit in one list from swamping agreement between the two.
+const RRF_K: f32 = 60.0;
 
+/// Reciprocal rank fusion: an id at 0-based rank `r` in a list scores `weight / (RRF_K + r + 1)`,
+/// summed over the lists.
+fn fuse(lists: &[(&[(i64, f32)], f32)], top_k: usize) -> Vec<(i64, f32)> {
+    let mut scores: std::collections::HashMap<i64, f32> = std::collections::HashMap::new();
+    for (hits, weight) in lists {
+        for (rank, (id, _)) in hits.iter().enumerate() {
+            *scores.entry(*id).or_default() += weight / (RRF_K + rank as f32 + 1.0);
+        }
+    }
+    let mut fused: Vec<(i64, f32)> = scores.into_iter().collect();
+    fused.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    fused.truncate(top_k);
+    fused
+}
+
+#[allow(clippy::too_many_arguments)]
+fn retrieve(
+    state: &AppState,
+    conn: &rusqlite::Connection,
+    key: &DataKey,
+    cfg: &AppConfig,
+    query: &str,
+    top_k: Option<u32>,
+    embed_model: Option<String>,
+    search: Option<SearchMode>,
+    mode: Option<RetrievalMode>,
+) -> Result<Vec<DocChunk>, String> {
+    let top_k = top_k.unwrap_or(cfg.rag.top_k).min(20) as usize;
+    let embed_model = embed_model.unwrap_or_else(|| cfg.llm.embed_model.clone());
+    let mode = mode.unwrap_or(cfg.rag.retrieval);
+    ensure_rag_table(conn)?;
+
+    // Fusion needs more than top_k from each list to find the chunks both rank well
+    let depth = if mode == RetrievalMode::Hybrid { (top_k * 4).max(20) } else { top_k };
+
+    let vector_hits = if mode == RetrievalMode::Lexical { None } else {
+        let query_emb = llm::current_backend(state).and_then(|b| b.embed(query, &embed_model));
+        match query_emb {
+            Ok(query_emb) => {
+                // Vectors from other models (or an older build of this one) are skipped;
+                // with nothing comparable left, vector-only retrieval refuses outright
+                let (comparable, other): (i64, i64) = conn.query_row(
+                    "SELECT COUNT(*) FILTER (WHERE embed_model = ?1 AND embed_dim = ?2), COUNT(*) FILTER (WHERE embed_model IS NOT ?1 OR embed_dim IS NOT ?2) FROM rag_docs WHERE embedding IS NOT NULL",
+                    params![embed_model, query_emb.len() as i64], |r| Ok((r.get(0)?, r.get(1)?)),
+                ).map_err(|e| e.to_string())?;
+                if comparable == 0 && other > 0 && mode == RetrievalMode::Vector {
+                    return Err(format!(
+                        "None of the vault's {other} embedded chunks were made with \"{embed_model}\" ({} dimensions); re-embed the vault with this model or switch back to the one used at ingest",
+                        query_emb.len()
+                    ));
+                }
+                // The index answers unless it is disabled, unusable, or built for another dimension
+                let hits = match search.unwrap_or(cfg.rag.search) {
+                    SearchMode::Ann   => index::with_index(state, conn, key, &embed_model, |ix| ix.graph.search(&query_emb, depth, cfg.rag.ef_search)).ok().flatten(),
+                    SearchMode::Exact => None,
+                };
+                Some(match hits {
+                    Some(hits) => hits,
+                    None       => exact_search(conn, key, &query_emb, &embed_model, depth)?,
+                })
+            }
+            // Hybrid carries on with keywords alone when the embedding model is unreachable
+            Err(_) if mode == RetrievalMode::Hybrid => None,
+            Err(e) => return Err(e),
+        }
+    };
+    let lexical_hits = if mode == RetrievalMode::Vector { None } else {
+        Some(index::with_index(state, conn, key, &embed_model, |ix| ix.lexical.search(query, depth))??)
+    };
+
+    let hits = match (vector_hits, lexical_hits) {
+        (Some(v), Some(l)) => fuse(&[(&v, cfg.rag.vector_weight), (&l, cfg.rag.lexical_weight)], top_k),
+        (Some(hits), None) | (None, Some(hits)) => hits.into_iter().take(top_k).collect(),
+        (None, None) => Vec::new(),
+    };
+
+    // Only the chunks actually returned are decrypted
     let mut stmt = conn.prepare(
-        "SELECT id, filename, chunk_idx, content, embedding, COALESCE(created_at,'') FROM rag_docs WHERE embedding IS NOT NULL"
+        "SELECT id, filename, chunk_idx, content, COALESCE(created_at,''), enc, section FROM rag_docs WHERE id = ?1"
     ).map_err(|e| e.to_string())?;
+    let mut out = Vec::with_capacity(hits.len());
+    for (id, score) in hits {
+        let row = stmt.query_row(params![id], |row| Ok((
+            row.get::<_, i64>(5)? == 1,
+            DocChunk {
+                id:         row.get(0)?,
+                filename:   row.get(1)?,
+                chunk_idx:  row.get(2)?,
+                content:    row.get(3)?,
+                section:    row.get(6)?,
+                score:      Some(score),
+                created_at: row.get(4)?,
+            },
+        ))).optional().map_err(|e| e.to_string())?;
+        let Some((enc, mut c)) = row else { continue };
+        if enc {
+            c.content = key.open_text(AAD_CONTENT, &c.content)?;
+            c.section = c.section.map(|s| key.open_text(AAD_SECTION, &s)).transpose()?;
+        }
+        out.push(c);
+    }
+    Ok(out)
+}
 
-    let mut scored: Vec<(f32, DocChunk)> = stmt
-        .query_map([], |row| {
-            let emb_bytes: Vec<u8> = row.get(4)?;
-            Ok((
-                emb_bytes,
-                DocChunk {
-                    id:         row.get(0)?,
-                    filename:   row.get(1)?,
-                    chunk_idx:  row.get(2)?,
-                    content:    row.get(3)?,
-                    score:      None,
-                    created_at: row.get(5)?,
-                },
-            ))
-        })
+/// Linear scan: cosine similarity of `query_emb` against every embedding made by `model`
+/// with the same dimension.
+fn exact_search(conn: &rusqlite::Connection, key: &DataKey, query_emb: &[f32], model: &str, top_k: usize) -> Result<Vec<(i64, f32)>, String> {
+    let mut stmt = conn.prepare(
+        "SELECT id, embedding, enc FROM rag_docs WHERE embedding IS NOT NULL AND embed_model = ?1 AND embed_dim = ?2"
+    ).map_err(|e| e.to_string())?;
+
+    let rows: Vec<(i64, Vec<u8>, bool)> = stmt
+        .query_map(params![model, query_emb.len() as i64], |row| Ok((row.get(0)?, row.get(1)?, row.get::<_, i64>(2)? == 1)))
         .map_err(|e| e.to_string())?
         .filter_map(|r| r.ok())
-        .map(|(bytes, mut chunk)| {
-            let emb   = bytes_to_emb(&bytes);
-            let score = cosine_similarity(&query_emb, &emb);
-            chunk.score = Some(score);
-            (score, chunk)
-        })
         .collect();
 
-    scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    let mut scored: Vec<(i64, f32)> = Vec::with_capacity(rows.len());
+    for (id, bytes, enc) in rows {
+        let bytes = if enc { key.open_bytes(AAD_EMBEDDING, &bytes)? } else { bytes };
+        scored.push((id, cosine_similarity(query_emb, &bytes_to_emb(&bytes))));
+    }
 
-    Ok(scored.into_iter().take(top_k.min(20) as usize).map(|(_, c)| c).collect())
+    scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    scored.truncate(top_k);
+    Ok(scored)
 }
 
 /// Build a combined context string from top-k chunks (used by generate_answer).
 #[tauri::command]
-pub fn rag_build_context(query: String, top_k: u32, embed_model: String) -> Result<String, String> {
-    let chunks = rag_retrieve(query, top_k, embed_model)?;
+pub fn rag_build_context(
+    query: String,
+    top_k: Option<u32>,
+    embed_model: Option<String>,
+    search: Option<SearchMode>,
+    mode: Option<RetrievalMode>,
+    state: tauri::State<AppState>,
+) -> Result<String, String> {
+    let cfg: AppConfig = state.config.lock().unwrap().clone();
+    let (conn, key) = session::open_encrypted_db(&state, "rag.db")?;
+    let chunks = retrieve(&state, &conn, &key, &cfg, &query, top_k, embed_model, search, mode)?;
     if chunks.is_empty() {
         return Ok(String::new());
     }
     let context = chunks.iter()
         .enumerate()
-        .map(|(i, c)| format!("[{}] (from: {})\n{}", i + 1, c.filename, c.content))
+        .map(|(i, c)| match &c.section {
+            Some(s) => format!("[{}] (from: {} › {})\n{}", i + 1, c.filename, s, c.content),
+            None    => format!("[{}] (from: {})\n{}", i + 1, c.filename, c.content),
+        })
         .collect::<Vec<_>>()
         .join("\n\n");
     Ok(context)
@@ -160,8 +451,8 @@ pub fn rag_build_context(query: String, top_k: u32, embed_model: String) -> Resu
 
 /// List all ingested document filenames.
 #[tauri::command]
-pub fn rag_list_docs() -> Result<Vec<String>, String> {
-    let conn = open_db("rag.db");
+pub fn rag_list_docs(state: tauri::State<AppState>) -> Result<Vec<String>, String> {
+    let conn = session::open_user_db(&state, "rag.db")?;
     ensure_rag_table(&conn)?;
     let mut stmt = conn.prepare(
         "SELECT filename, COUNT(*) as n FROM rag_docs GROUP BY filename ORDER BY MAX(created_at) DESC"
@@ -180,9 +471,107 @@ pub fn rag_list_docs() -> Result<Vec<String>, String> {
 
 /// Delete all chunks for a document.
 #[tauri::command]
-pub fn rag_delete_doc(filename: String) -> Result<usize, String> {
-    let conn = open_db("rag.db");
+pub fn rag_delete_doc(filename: String, state: tauri::State<AppState>) -> Result<usize, String> {
+    let (conn, key) = session::open_encrypted_db(&state, "rag.db")?;
+    ensure_rag_table(&conn)?;
+    let ids = chunk_ids(&conn, &filename)?;
     let n = conn.execute("DELETE FROM rag_docs WHERE filename = ?1", params![filename])
         .map_err(|e| e.to_string())?;
+    let model = state.config.lock().unwrap().llm.embed_model.clone();
+    index::with_index(&state, &conn, &key, &model, |ix| {
+        for &id in &ids {
+            ix.graph.remove(id);
+            ix.lexical.remove(id)?;
+        }
+        Ok::<_, String>(())
+    })??;
+    let user_id = session::current_user(&state).unwrap_or_default();
+    logs::record(&AuditEvent::RagDocDeleted { user_id, filename, chunks: n })?;
     Ok(n)
 }
+
+// ── Embedding provenance / re-embedding ──────────────────────────────────────
+
+#[derive(Serialize)]
+pub struct ModelChunks {
+    /// `None` for chunks embedded before the model was recorded.
+    pub model:  Option<String>,
+    pub dim:    Option<i64>,
+    pub chunks: usize,
+}
+
+#[derive(Serialize)]
+pub struct EmbeddingStatus {
+    pub total:    usize,
+    /// Chunks stored without an embedding (the model was unreachable at ingest).
+    pub missing:  usize,
+    pub by_model: Vec<ModelChunks>,
+}
+
+/// Which models the vault's chunks were embedded with. Retrieval only compares vectors from
+/// the query's model; anything else needs `rag_reembed_all`.
+#[tauri::command]
+pub fn rag_embedding_status(state: tauri::State<AppState>) -> Result<EmbeddingStatus, String> {
+    let conn = session::open_user_db(&state, "rag.db")?;
+    ensure_rag_table(&conn)?;
+    let mut stmt = conn.prepare(
+        "SELECT embed_model, embed_dim, COUNT(*) FROM rag_docs WHERE embedding IS NOT NULL
+         GROUP BY embed_model, embed_dim ORDER BY COUNT(*) DESC"
+    ).map_err(|e| e.to_string())?;
+    let by_model = stmt.query_map([], |r| Ok(ModelChunks { model: r.get(0)?, dim: r.get(1)?, chunks: r.get::<_, i64>(2)? as usize }))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let (total, missing): (i64, i64) = conn.query_row(
+        "SELECT COUNT(*), COUNT(*) - COUNT(embedding) FROM rag_docs", [], |r| Ok((r.get(0)?, r.get(1)?)),
+    ).map_err(|e| e.to_string())?;
+    Ok(EmbeddingStatus { total: total as usize, missing: missing as usize, by_model })
+}
+
+/// Start re-embedding every chunk with `model` (default `llm.embed_model`) in the
+/// background. Progress is emitted as `rag:reembed` events; returns the initial progress.
+#[tauri::command]
+pub fn rag_reembed_all(
+    app: tauri::AppHandle,
+    model: Option<String>,
+    state: tauri::State<AppState>,
+) -> Result<ReembedProgress, String> {
+    let model = model.unwrap_or_else(|| state.config.lock().unwrap().llm.embed_model.clone());
+    let (conn, key) = session::open_encrypted_db(&state, "rag.db")?;
+    ensure_rag_table(&conn)?;
+    let user_id = session::current_user(&state).ok_or(session::LOCKED)?;
+
+    let mut slot = state.rag_reembed.lock().unwrap();
+    if slot.as_ref().is_some_and(|job| !job.progress.finished) {
+        return Err("A re-embedding job is already running".into());
+    }
+    let progress = ReembedProgress {
+        job_id: format!("{:016x}", rand::random::<u64>()), model,
+        total: 0, done: 0, failed: 0, finished: false, error: None,
+    };
+    let cancel = Arc::new(AtomicBool::new(false));
+    *slot = Some(ReembedJob { user_id: user_id.clone(), progress: progress.clone(), cancel: cancel.clone() });
+    drop(slot);
+
+    let initial = progress.clone();
+    // Detached: the job outlives this command and reports through events
+    tauri::async_runtime::spawn_blocking(move || reembed::run(&app, &user_id, &key, progress, &cancel));
+    Ok(initial)
+}
+
+/// Progress of the unlocked profile's latest re-embedding job, if any.
+#[tauri::command]
+pub fn rag_reembed_status(state: tauri::State<AppState>) -> Result<Option<ReembedProgress>, String> {
+    let user_id = session::current_user(&state).ok_or(session::LOCKED)?;
+    let job = state.rag_reembed.lock().unwrap();
+    Ok(job.as_ref().filter(|j| j.user_id == user_id).map(|j| j.progress.clone()))
+}
+
+/// Ask the running job to stop after the current chunk. Returns false if none is running.
+#[tauri::command]
+pub fn rag_reembed_cancel(state: tauri::State<AppState>) -> Result<bool, String> {
+    let job = state.rag_reembed.lock().unwrap();
+    let Some(job) = job.as_ref().filter(|j| !j.progress.finished) else { return Ok(false) };
+    job.cancel.store(true, Ordering::SeqCst);
+    Ok(true)
+}
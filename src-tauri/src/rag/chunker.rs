@@ -0,0 +1,398 @@
+//! Structure-aware chunking for RAG ingestion.
+//!
+//! `Structured` (the default) starts a new chunk at every heading, keeps paragraphs and
+//! bullet items whole — including a bullet's wrapped continuation lines, so an
+//! achievement stays with its metrics — and packs them into chunks of at most
+//! `max_tokens`. Only a block larger than the budget is split, at sentence boundaries.
+//! `Sentence` ignores layout and packs sentences; `Fixed` is the original character window.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkStrategy {
+    #[default]
+    Structured,
+    Sentence,
+    Fixed,
+}
+
+pub struct ChunkParams {
+    pub strategy:      ChunkStrategy,
+    /// Token budget per chunk (`Structured` / `Sentence`).
+    pub max_tokens:    usize,
+    /// Window and overlap in characters (`Fixed`).
+    pub fixed_chars:   usize,
+    pub fixed_overlap: usize,
+}
+
+pub struct Chunk {
+    pub text:    String,
+    /// Heading path, e.g. `Experience › Acme Corp`, when the chunk sits under one.
+    pub section: Option<String>,
+}
+
+impl Chunk {
+    /// The text to store and embed: the section path on its own line, then the body.
+    pub fn with_context(&self) -> String {
+        match &self.section {
+            Some(s) => format!("{s}\n{}", self.text),
+            None    => self.text.clone(),
+        }
+    }
+}
+
+pub fn chunk(text: &str, p: &ChunkParams) -> Vec<Chunk> {
+    let max_tokens = p.max_tokens.max(16);
+    match p.strategy {
+        ChunkStrategy::Structured => structured(text, max_tokens),
+        ChunkStrategy::Sentence => {
+            let sents: Vec<String> = paragraphs(text).iter().flat_map(|para| sentences(para)).collect();
+            pack(&sents, max_tokens, " ").into_iter().map(|text| Chunk { text, section: None }).collect()
+        }
+        ChunkStrategy::Fixed => fixed(text, p.fixed_chars, p.fixed_overlap)
+            .into_iter().map(|text| Chunk { text, section: None }).collect(),
+    }
+}
+
+/// Rough BPE-style estimate — about four characters per token, at least one per word.
+/// Close enough for budgeting without shipping a tokenizer for every embedding model.
+pub fn estimate_tokens(s: &str) -> usize {
+    s.split_whitespace().map(|w| w.chars().count().div_ceil(4).max(1)).sum()
+}
+
+// ── Fixed windows ────────────────────────────────────────────────────────────
+fn fixed(text: &str, chunk_chars: usize, overlap: usize) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < chars.len() {
+        let end = (start + chunk_chars).min(chars.len());
+        let chunk: String = chars[start..end].iter().collect();
+        chunks.push(chunk);
+        if end == chars.len() { break; }
+        start = end.saturating_sub(overlap);
+    }
+    chunks
+}
+
+// ── Blocks ───────────────────────────────────────────────────────────────────
+enum Block {
+    Heading { level: usize, text: String },
+    Bullet(String),
+    Paragraph(String),
+}
+
+/// Markdown `#` headings, plus resume-style ones: a short ALL-CAPS line (`EXPERIENCE`)
+/// or a short line ending in a colon (`Skills:`).
+fn heading(line: &str) -> Option<(usize, String)> {
+    let t = line.trim();
+    let hashes = t.chars().take_while(|c| *c == '#').count();
+    if (1..=6).contains(&hashes) && t[hashes..].starts_with(' ') {
+        return Some((hashes, t[hashes..].trim().trim_end_matches('#').trim().to_string()));
+    }
+    let words = t.split_whitespace().count();
+    if t.chars().count() > 60 || words > 6 || t.chars().filter(|c| c.is_alphabetic()).count() < 3 {
+        return None;
+    }
+    if t.chars().all(|c| !c.is_lowercase()) && !t.ends_with(['.', ',', ';']) && !t.contains(',') {
+        return Some((1, t.to_string()));
+    }
+    let stem = t.strip_suffix(':')?;
+    (!stem.contains(':')).then(|| (2, stem.trim().to_string()))
+}
+
+fn bullet(line: &str) -> Option<&str> {
+    let t = line.trim_start();
+    for m in ['•', '◦', '▪', '●', '‣', '–', '—'] {
+        if let Some(rest) = t.strip_prefix(m) { return Some(rest.trim_start()); }
+    }
+    for m in ["- ", "* ", "+ "] {
+        if let Some(rest) = t.strip_prefix(m) { return Some(rest); }
+    }
+    let digits = t.chars().take_while(char::is_ascii_digit).count();
+    if (1..=3).contains(&digits) {
+        let rest = &t[digits..];
+        return rest.strip_prefix(". ").or_else(|| rest.strip_prefix(") "));
+    }
+    None
+}
+
+fn is_rule(t: &str) -> bool {
+    t.len() >= 3 && (t.chars().all(|c| c == '-') || t.chars().all(|c| c == '=') || t.chars().all(|c| c == '*') || t.chars().all(|c| c == '_'))
+}
+
+fn blocks(text: &str) -> Vec<Block> {
+    let mut out = Vec::new();
+    let mut open: Option<Block> = None;
+    for line in text.lines() {
+        let t = line.trim();
+        if t.is_empty() || is_rule(t) {
+            out.extend(open.take());
+            continue;
+        }
+        if let Some(item) = bullet(line) {
+            out.extend(open.take());
+            open = Some(Block::Bullet(item.trim().to_string()));
+            continue;
+        }
+        if let Some((level, text)) = heading(t) {
+            out.extend(open.take());
+            out.push(Block::Heading { level, text });
+            continue;
+        }
+        // A wrapped line continues the open bullet or paragraph
+        match &mut open {
+            Some(Block::Bullet(s) | Block::Paragraph(s)) => { s.push(' '); s.push_str(t); }
+            _ => open = Some(Block::Paragraph(t.to_string())),
+        }
+    }
+    out.extend(open);
+    out
+}
+
+fn paragraphs(text: &str) -> Vec<String> {
+    blocks(text).into_iter().map(|b| match b {
+        Block::Heading { text, .. } | Block::Bullet(text) | Block::Paragraph(text) => text,
+    }).collect()
+}
+
+// ── Sentences ────────────────────────────────────────────────────────────────
+const ABBREVIATIONS: &[&str] = &[
+    "e.g", "i.e", "etc", "vs", "approx", "inc", "ltd", "co", "corp", "dept", "mr", "mrs", "ms", "dr",
+    "jr", "sr", "st", "no", "jan", "feb", "mar", "apr", "jun", "jul", "aug", "sep", "sept", "oct", "nov", "dec",
+];
+
+fn is_abbreviation(word: &str) -> bool {
+    let w = word.trim_start_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+    // Initials ("J. Smith") and dotted forms ("U.S.") are not sentence ends
+    (w.chars().count() == 1 && w.chars().all(char::is_alphabetic)) || w.contains('.') || ABBREVIATIONS.contains(&w.as_str())
+}
+
+/// Split at `.`/`!`/`?` followed by whitespace and a non-lowercase character, except after
+/// abbreviations and initials. Decimals (`3.5x`) never match since no whitespace follows.
+pub fn sentences(text: &str) -> Vec<String> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let (mut out, mut start) = (Vec::new(), 0);
+    for (i, &(pos, c)) in chars.iter().enumerate() {
+        if !matches!(c, '.' | '!' | '?') { continue; }
+        if chars.get(i + 1).is_some_and(|&(_, n)| !n.is_whitespace()) { continue; }
+        let after = chars[i + 1..].iter().map(|&(_, c)| c).find(|c| !c.is_whitespace());
+        if after.is_some_and(char::is_lowercase) { continue; }
+        if c == '.' && text[start..pos].split_whitespace().last().is_some_and(is_abbreviation) { continue; }
+        let end = pos + c.len_utf8();
+        let s = text[start..end].trim();
+        if !s.is_empty() { out.push(s.to_string()); }
+        start = end;
+    }
+    let tail = text[start..].trim();
+    if !tail.is_empty() { out.push(tail.to_string()); }
+    out
+}
+
+// ── Packing ──────────────────────────────────────────────────────────────────
+/// Split one over-long piece into word runs that fit the budget.
+fn split_words(piece: &str, max_tokens: usize) -> Vec<String> {
+    let mut out = Vec::new();
+    let (mut cur, mut tokens) = (String::new(), 0);
+    for w in piece.split_whitespace() {
+        let t = estimate_tokens(w);
+        if tokens + t > max_tokens && !cur.is_empty() {
+            out.push(std::mem::take(&mut cur));
+            tokens = 0;
+        }
+        if !cur.is_empty() { cur.push(' '); }
+        cur.push_str(w);
+        tokens += t;
+    }
+    if !cur.is_empty() { out.push(cur); }
+    out
+}
+
+/// Greedily join `pieces` with `sep` into runs of at most `max_tokens`.
+fn pack(pieces: &[String], max_tokens: usize, sep: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let (mut cur, mut tokens) = (Vec::<String>::new(), 0);
+    for piece in pieces {
+        let t = estimate_tokens(piece);
+        if t > max_tokens {
+            if !cur.is_empty() { out.push(cur.join(sep)); cur.clear(); tokens = 0; }
+            out.extend(split_words(piece, max_tokens));
+            continue;
+        }
+        if tokens + t > max_tokens && !cur.is_empty() {
+            out.push(cur.join(sep));
+            cur.clear();
+            tokens = 0;
+        }
+        cur.push(piece.clone());
+        tokens += t;
+    }
+    if !cur.is_empty() { out.push(cur.join(sep)); }
+    out
+}
+
+/// Keep the end of a heading path, the most specific part, within `max_tokens`, marking
+/// the cut with a leading `…`.
+fn clip_section(path: &str, max_tokens: usize) -> String {
+    if estimate_tokens(path) <= max_tokens { return path.to_string(); }
+    let (mut kept, mut tokens) = (Vec::new(), 1);
+    for w in path.split_whitespace().rev() {
+        let t = estimate_tokens(w);
+        if tokens + t > max_tokens { break; }
+        kept.push(w);
+        tokens += t;
+    }
+    if kept.is_empty() {
+        // A single huge word: keep its tail
+        let mut tail: Vec<char> = path.chars().rev().take_while(|c| !c.is_whitespace()).take((max_tokens - 1) * 4).collect();
+        tail.reverse();
+        return format!("… {}", tail.into_iter().collect::<String>());
+    }
+    kept.reverse();
+    format!("… {}", kept.join(" "))
+}
+
+fn structured(text: &str, max_tokens: usize) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    let mut path: Vec<(usize, String)> = Vec::new();
+    let mut section: Option<String> = None;
+    let mut budget = max_tokens;
+    let mut pending: Vec<String> = Vec::new();
+
+    let flush = |pending: &mut Vec<String>, section: &Option<String>, budget: usize, chunks: &mut Vec<Chunk>| {
+        for text in pack(pending, budget, "\n") {
+            chunks.push(Chunk { text, section: section.clone() });
+        }
+        pending.clear();
+    };
+
+    for block in blocks(text) {
+        match block {
+            Block::Heading { level, text } => {
+                flush(&mut pending, &section, budget, &mut chunks);
+                path.retain(|(l, _)| *l < level);
+                path.push((level, text));
+                let joined = path.iter().map(|(_, t)| t.as_str()).collect::<Vec<_>>().join(" › ");
+                // The section line is stored with every chunk, so it counts against the budget;
+                // a long path is clipped to half of it rather than squeezing the body further
+                let joined = clip_section(&joined, max_tokens / 2);
+                budget = max_tokens - estimate_tokens(&joined);
+                section = Some(joined);
+            }
+            Block::Bullet(s) | Block::Paragraph(s) if estimate_tokens(&s) > budget => {
+                // Too big on its own: fall back to sentence boundaries for this block only
+                flush(&mut pending, &section, budget, &mut chunks);
+                pending = sentences(&s);
+                for text in pack(&pending, budget, " ") {
+                    chunks.push(Chunk { text, section: section.clone() });
+                }
+                pending.clear();
+            }
+            Block::Bullet(s)    => pending.push(format!("- {s}")),
+            Block::Paragraph(s) => pending.push(s),
+        }
+    }
+    flush(&mut pending, &section, budget, &mut chunks);
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(max_tokens: usize) -> ChunkParams {
+        ChunkParams { strategy: ChunkStrategy::Structured, max_tokens, fixed_chars: 0, fixed_overlap: 0 }
+    }
+
+    #[test]
+    fn sentences_skip_abbreviations_initials_and_decimals() {
+        assert_eq!(
+            sentences("Worked with Dr. Smith on e.g. stream processing. Cut p99 latency 3.5x in Q3. J. Doe led QA."),
+            ["Worked with Dr. Smith on e.g. stream processing.", "Cut p99 latency 3.5x in Q3.", "J. Doe led QA."],
+        );
+        assert_eq!(sentences("Shipped v2.0 to prod. then iterated! Done?"), ["Shipped v2.0 to prod. then iterated!", "Done?"]);
+        assert_eq!(sentences("Moved to the U.S. Later joined Acme."), ["Moved to the U.S. Later joined Acme."]);
+    }
+
+    #[test]
+    fn headings_markdown_caps_and_colon() {
+        assert_eq!(heading("## Work History ##"), Some((2, "Work History".into())));
+        assert_eq!(heading("EXPERIENCE"), Some((1, "EXPERIENCE".into())));
+        assert_eq!(heading("Skills:"), Some((2, "Skills".into())));
+        assert_eq!(heading("#hashtag"), None);
+        assert_eq!(heading("AWS, GCP, AZURE"), None);
+        assert_eq!(heading("LED A TEAM OF TWELVE ENGINEERS ACROSS THREE TIME ZONES"), None);
+        assert_eq!(heading("Led migration to Kafka."), None);
+        assert_eq!(heading("Note: this is a sentence:"), None);
+    }
+
+    #[test]
+    fn bullet_markers() {
+        assert_eq!(bullet("- Shipped it"), Some("Shipped it"));
+        assert_eq!(bullet("  • Shipped it"), Some("Shipped it"));
+        assert_eq!(bullet("3. Shipped it"), Some("Shipped it"));
+        assert_eq!(bullet("12) Shipped it"), Some("Shipped it"));
+        assert_eq!(bullet("2024. A year"), None);
+        assert_eq!(bullet("-5% churn"), None);
+    }
+
+    #[test]
+    fn wrapped_bullet_lines_join_their_item() {
+        let b = blocks("- Led migration to Kafka,\n  cutting latency 40%.\n- Mentored 6 engineers.");
+        let items: Vec<&str> = b.iter().map(|b| match b { Block::Bullet(s) => s.as_str(), _ => panic!("expected bullets") }).collect();
+        assert_eq!(items, ["Led migration to Kafka, cutting latency 40%.", "Mentored 6 engineers."]);
+    }
+
+    #[test]
+    fn achievement_stays_with_its_metrics() {
+        let mut text = String::from("EXPERIENCE\nAcme Corp:\n");
+        for i in 0..6 { text.push_str(&format!("- Maintained internal service number {i} for the payments team.\n")); }
+        text.push_str("- Led migration to an event-driven architecture on Kafka,\n  reducing p99 latency by 40% and saving $1.2M per year.\n");
+        for i in 0..6 { text.push_str(&format!("- Reviewed design documents for platform project {i}.\n")); }
+        let chunks = chunk(&text, &params(48));
+        assert!(chunks.len() > 1);
+        let c = chunks.iter().find(|c| c.text.contains("Led migration")).unwrap();
+        assert!(c.text.contains("40%") && c.text.contains("$1.2M"), "{}", c.text);
+        assert_eq!(c.section.as_deref(), Some("EXPERIENCE › Acme Corp"));
+    }
+
+    #[test]
+    fn chunks_fit_the_token_budget_with_their_section() {
+        let mut text = String::from("# Projects\n## Data platform\n");
+        for i in 0..40 { text.push_str(&format!("- Built ingestion job {i} moving events between regional clusters.\n")); }
+        text.push_str(&"word ".repeat(300));
+        for c in chunk(&text, &params(64)) {
+            assert!(estimate_tokens(&c.with_context()) <= 64, "{} tokens: {}", estimate_tokens(&c.with_context()), c.text);
+        }
+    }
+
+    #[test]
+    fn long_heading_paths_are_clipped_to_keep_the_budget() {
+        let mut text = String::new();
+        for (level, title) in ["Professional Experience and Selected Engagements", "Consulting for Enterprise Clients in Regulated Industries",
+                               "Acme Financial Services International Holdings", "Payments Infrastructure Modernization Programme"].iter().enumerate() {
+            text.push_str(&format!("{} {title}\n", "#".repeat(level + 1)));
+        }
+        for i in 0..12 { text.push_str(&format!("- Delivered settlement milestone {i} ahead of the regulatory deadline.\n")); }
+        for c in chunk(&text, &params(32)) {
+            assert!(estimate_tokens(&c.with_context()) <= 32, "{} tokens: {}", estimate_tokens(&c.with_context()), c.with_context());
+            let section = c.section.unwrap();
+            assert!(section.starts_with("… ") && section.ends_with("Modernization Programme"), "{section}");
+        }
+        assert_eq!(clip_section("Experience › Acme", 8), "Experience › Acme");
+        assert_eq!(clip_section(&"x".repeat(40), 3), format!("… {}", "x".repeat(8)));
+    }
+
+    #[test]
+    fn pack_is_greedy_and_splits_oversized_pieces() {
+        let pieces: Vec<String> = ["aaaa bbbb", "cccc", "dddd eeee ffff"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(pack(&pieces, 3, " "), ["aaaa bbbb cccc", "dddd eeee ffff"]);
+        assert_eq!(pack(&["a b c d e".to_string()], 2, " "), ["a b", "c d", "e"]);
+    }
+
+    #[test]
+    fn fixed_windows_overlap() {
+        assert_eq!(fixed("abcdefghij", 4, 1), ["abcd", "defg", "ghij"]);
+    }
+}
@@ -0,0 +1,262 @@
+//! Text extraction for `rag_ingest_file`.
+//!
+//! Every format is reduced to Markdown-flavoured plain text — `#` headings, `- ` bullets,
+//! blank lines between blocks — so the structured chunker sees the same document shape
+//! whether the resume arrived as PDF, DOCX, HTML or text. PDFs carry no heading markup;
+//! their section titles are picked up by the chunker's ALL-CAPS / `Title:` heuristics.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+/// Files larger than this are refused rather than read into memory. DOCX parts are held
+/// to the same limit once decompressed, so a zip bomb cannot exhaust memory either.
+const MAX_FILE_BYTES: u64 = 25 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Format { Pdf, Docx, Markdown, Html, Text }
+
+impl Format {
+    fn from_path(path: &Path) -> Option<Format> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "pdf"                      => Some(Format::Pdf),
+            "docx"                     => Some(Format::Docx),
+            "md" | "markdown"          => Some(Format::Markdown),
+            "html" | "htm" | "xhtml"   => Some(Format::Html),
+            "txt" | "text"             => Some(Format::Text),
+            _                          => None,
+        }
+    }
+
+    /// Fallback when the extension is missing or unknown.
+    fn sniff(bytes: &[u8]) -> Option<Format> {
+        if bytes.starts_with(b"%PDF-") { return Some(Format::Pdf); }
+        if bytes.starts_with(b"PK\x03\x04") { return Some(Format::Docx); }
+        let head = std::str::from_utf8(&bytes[..bytes.len().min(512)]).ok()?.trim_start().to_ascii_lowercase();
+        if head.starts_with("<!doctype html") || head.starts_with("<html") { return Some(Format::Html); }
+        std::str::from_utf8(bytes).is_ok().then_some(Format::Text)
+    }
+}
+
+/// Read `path` and return its format and normalized text.
+pub fn extract(path: &Path) -> Result<(Format, String), String> {
+    let size = std::fs::metadata(path).map_err(|e| format!("{}: {e}", path.display()))?.len();
+    if size > MAX_FILE_BYTES {
+        return Err(format!("{}: file is larger than {} MB", path.display(), MAX_FILE_BYTES / (1024 * 1024)));
+    }
+    let bytes = std::fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let format = Format::from_path(path).or_else(|| Format::sniff(&bytes))
+        .ok_or_else(|| format!("{}: unsupported file type (expected PDF, DOCX, Markdown, HTML or text)", path.display()))?;
+    let text = match format {
+        Format::Pdf  => pdf_text(&bytes)?,
+        Format::Docx => docx_text(&bytes)?,
+        Format::Html => html_text(&String::from_utf8_lossy(&bytes)),
+        Format::Markdown | Format::Text => String::from_utf8_lossy(&bytes).into_owned(),
+    };
+    let text = normalize(&text);
+    if text.trim().is_empty() {
+        return Err(match format {
+            Format::Pdf => "PDF has no extractable text (scanned image?)".into(),
+            _           => format!("{}: no text found", path.display()),
+        });
+    }
+    Ok((format, text))
+}
+
+/// Unify line endings and spacing, and drop runs of blank lines.
+fn normalize(text: &str) -> String {
+    let text = text.replace("\r\n", "\n").replace(['\r', '\u{c}'], "\n").replace(['\u{a0}', '\t'], " ");
+    let mut out = String::with_capacity(text.len());
+    let mut blank = 0;
+    for line in text.lines().map(str::trim_end) {
+        if line.trim().is_empty() {
+            blank += 1;
+            if blank > 1 { continue; }
+        } else {
+            blank = 0;
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.trim().to_string()
+}
+
+// ── PDF ──────────────────────────────────────────────────────────────────────
+/// `pdf_extract` panics on some malformed files instead of returning an error; contain
+/// that so a bad upload fails the ingest rather than the command thread.
+fn pdf_text(bytes: &[u8]) -> Result<String, String> {
+    match std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem(bytes)) {
+        Ok(text) => text.map_err(|e| format!("PDF: {e}")),
+        Err(_)   => Err("PDF: the file is malformed or uses an unsupported feature".into()),
+    }
+}
+
+// ── DOCX ─────────────────────────────────────────────────────────────────────
+const W: &str = "http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+
+/// Read one archive part, `None` when it is absent. The declared size can lie, so the read
+/// itself is also bounded.
+fn zip_entry(archive: &mut zip::ZipArchive<std::io::Cursor<&[u8]>>, name: &str) -> Result<Option<String>, String> {
+    let Ok(file) = archive.by_name(name) else { return Ok(None) };
+    let too_large = || format!("DOCX: {name} is larger than {} MB uncompressed", MAX_FILE_BYTES / (1024 * 1024));
+    if file.size() > MAX_FILE_BYTES { return Err(too_large()); }
+    let mut xml = String::new();
+    file.take(MAX_FILE_BYTES + 1).read_to_string(&mut xml).map_err(|e| format!("DOCX: {name}: {e}"))?;
+    if xml.len() as u64 > MAX_FILE_BYTES { return Err(too_large()); }
+    Ok(Some(xml))
+}
+
+/// Heading level per paragraph style id, from `word/styles.xml`. Built-in heading styles
+/// are named `heading 1`..`heading 9` (and `Title`) whatever the UI language, while their
+/// ids are localized, so match on the name or the outline level rather than the id.
+fn heading_styles(xml: &str) -> HashMap<String, usize> {
+    let Ok(doc) = roxmltree::Document::parse(xml) else { return HashMap::new() };
+    let mut levels = HashMap::new();
+    for style in doc.descendants().filter(|n| n.has_tag_name((W, "style"))) {
+        let Some(id) = style.attribute((W, "styleId")) else { continue };
+        let child_val = |tag: &str| style.descendants().find(|n| n.has_tag_name((W, tag))).and_then(|n| n.attribute((W, "val")));
+        let name = child_val("name").unwrap_or_default().to_ascii_lowercase();
+        let level = if name == "title" {
+            Some(1)
+        } else if let Some(n) = name.strip_prefix("heading ") {
+            n.parse::<usize>().ok()
+        } else {
+            child_val("outlineLvl").and_then(|v| v.parse::<usize>().ok()).map(|l| l + 1)
+        };
+        if let Some(l) = level { levels.insert(id.to_string(), l.min(6)); }
+    }
+    levels
+}
+
+fn docx_text(bytes: &[u8]) -> Result<String, String> {
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes)).map_err(|e| format!("DOCX: {e}"))?;
+    let xml = zip_entry(&mut archive, "word/document.xml")?.ok_or("DOCX: word/document.xml is missing")?;
+    // Heading styles are a nicety; an unreadable styles part falls back to the built-in ids
+    let styles = zip_entry(&mut archive, "word/styles.xml").ok().flatten().map(|s| heading_styles(&s)).unwrap_or_default();
+    let doc = roxmltree::Document::parse(&xml).map_err(|e| format!("DOCX: {e}"))?;
+
+    let mut out = String::new();
+    for p in doc.descendants().filter(|n| n.has_tag_name((W, "p"))) {
+        let mut text = String::new();
+        for n in p.descendants() {
+            if n.has_tag_name((W, "t")) { text.push_str(n.text().unwrap_or_default()); }
+            else if n.has_tag_name((W, "tab")) || n.has_tag_name((W, "br")) { text.push(' '); }
+        }
+        let text = text.trim();
+        if text.is_empty() { continue; }
+
+        let props = p.children().find(|n| n.has_tag_name((W, "pPr")));
+        let style = props.and_then(|pr| pr.children().find(|n| n.has_tag_name((W, "pStyle"))))
+            .and_then(|s| s.attribute((W, "val")));
+        let level = style.and_then(|s| styles.get(s).copied().or_else(|| {
+            // No styles.xml entry: fall back to the English built-in ids
+            s.strip_prefix("Heading").and_then(|n| n.parse().ok()).or((s == "Title").then_some(1))
+        })).map(|l: usize| l.clamp(1, 6));
+        let listed = props.is_some_and(|pr| pr.children().any(|n| n.has_tag_name((W, "numPr"))))
+            || style.is_some_and(|s| s.starts_with("List"));
+
+        match level {
+            Some(l) => out.push_str(&format!("{} {text}\n\n", "#".repeat(l))),
+            None if listed => out.push_str(&format!("- {text}\n")),
+            None => out.push_str(&format!("{text}\n\n")),
+        }
+    }
+    Ok(out)
+}
+
+// ── HTML ─────────────────────────────────────────────────────────────────────
+fn decode_entities(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('&') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let Some((end, _)) = rest.char_indices().take(12).find(|&(_, c)| c == ';') else {
+            out.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "amp" => Some('&'), "lt" => Some('<'), "gt" => Some('>'), "quot" => Some('"'),
+            "apos" => Some('\''), "nbsp" => Some(' '), "ndash" => Some('–'), "mdash" => Some('—'),
+            "bull" => Some('•'), "hellip" => Some('…'),
+            _ => entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X"))
+                    .and_then(|h| u32::from_str_radix(h, 16).ok())
+                    .or_else(|| entity.strip_prefix('#').and_then(|d| d.parse().ok()))
+                    .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => { out.push(c); rest = &rest[end + 1..]; }
+            None    => { out.push('&'); rest = &rest[1..]; }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// A small tag-stripping converter: headings become `#` lines, list items `- ` lines,
+/// block elements paragraph breaks; `<script>`, `<style>` and `<head>` are dropped.
+fn html_text(html: &str) -> String {
+    let mut out = String::new();
+    let mut rest = html;
+    let mut skip_until: Option<String> = None;
+    while !rest.is_empty() {
+        let Some(lt) = rest.find('<') else {
+            if skip_until.is_none() { push_text(&mut out, rest); }
+            break;
+        };
+        if skip_until.is_none() { push_text(&mut out, &rest[..lt]); }
+        rest = &rest[lt..];
+        if rest.starts_with("<!--") {
+            rest = rest.find("-->").map_or("", |e| &rest[e + 3..]);
+            continue;
+        }
+        let Some(gt) = rest.find('>') else { break };
+        let tag = &rest[1..gt];
+        rest = &rest[gt + 1..];
+
+        let closing = tag.starts_with('/');
+        let name: String = tag.trim_start_matches('/').chars()
+            .take_while(|c| c.is_ascii_alphanumeric()).collect::<String>().to_ascii_lowercase();
+        if let Some(until) = &skip_until {
+            if closing && name == *until { skip_until = None; }
+            continue;
+        }
+        match name.as_str() {
+            "script" | "style" | "head" | "noscript" | "template" if !closing && !tag.ends_with('/') => {
+                skip_until = Some(name);
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                out.push_str("\n\n");
+                if !closing {
+                    let level = (name.as_bytes()[1] - b'0') as usize;
+                    out.push_str(&"#".repeat(level));
+                    out.push(' ');
+                }
+            }
+            "li" => out.push_str(if closing { "\n" } else { "\n- " }),
+            "br" => out.push('\n'),
+            "p" | "div" | "section" | "article" | "header" | "footer" | "ul" | "ol" | "table" | "tr"
+            | "blockquote" | "pre" | "dl" | "dt" | "dd" | "hr" => out.push_str("\n\n"),
+            "td" | "th" => out.push(' '),
+            _ => {}
+        }
+    }
+    out
+}
+
+/// Append an HTML text node: entities decoded, whitespace collapsed as a browser would.
+fn push_text(out: &mut String, raw: &str) {
+    let text = decode_entities(raw);
+    let mut words = text.split_whitespace().peekable();
+    if words.peek().is_none() {
+        if !text.is_empty() && !out.ends_with([' ', '\n']) { out.push(' '); }
+        return;
+    }
+    if text.starts_with(char::is_whitespace) && !out.ends_with([' ', '\n']) { out.push(' '); }
+    out.push_str(&words.collect::<Vec<_>>().join(" "));
+    if text.ends_with(char::is_whitespace) { out.push(' '); }
+}
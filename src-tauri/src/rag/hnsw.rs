@@ -0,0 +1,357 @@
+//! Hierarchical navigable small-world graph (Malkov & Yashunin, 2016) for cosine search
+//! over chunk embeddings.
+//!
+//! Vectors are L2-normalized on insert, so distance is `1 - dot`. Removal leaves a
+//! tombstone that is still traversed but never returned; once tombstones make up a
+//! quarter of the graph it is rebuilt from the live vectors. `to_bytes`/`from_bytes` give
+//! a flat little-endian encoding for `index.rs` to seal and store.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+const MAGIC: &[u8; 8] = b"PPHNSW1\0";
+/// Cap on the random level so a pathological draw cannot build a tall empty tower.
+const MAX_LEVEL: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Dist(f32);
+impl Eq for Dist {}
+impl PartialOrd for Dist {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+impl Ord for Dist {
+    fn cmp(&self, other: &Self) -> Ordering { self.0.total_cmp(&other.0) }
+}
+
+struct Node {
+    id:      i64,
+    vec:     Vec<f32>,
+    /// Neighbour slots per layer, `links[0]` being the densest.
+    links:   Vec<Vec<u32>>,
+    deleted: bool,
+}
+
+pub struct Hnsw {
+    dim:             usize,
+    m:               usize,
+    ef_construction: usize,
+    entry:           Option<u32>,
+    nodes:           Vec<Node>,
+    slots:           HashMap<i64, u32>,
+    deleted:         usize,
+    /// Set by `insert`/`remove`; cleared by the owner once the graph is saved.
+    pub dirty:       bool,
+}
+
+fn normalize(v: &[f32]) -> Option<Vec<f32>> {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    (norm > 0.0 && norm.is_finite()).then(|| v.iter().map(|x| x / norm).collect())
+}
+
+impl Hnsw {
+    pub fn new(m: usize, ef_construction: usize) -> Self {
+        Hnsw {
+            dim: 0, m: m.max(2), ef_construction: ef_construction.max(m),
+            entry: None, nodes: Vec::new(), slots: HashMap::new(), deleted: 0, dirty: false,
+        }
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = i64> + '_ { self.slots.keys().copied() }
+
+    fn dist(&self, q: &[f32], slot: u32) -> Dist {
+        let v = &self.nodes[slot as usize].vec;
+        Dist(1.0 - q.iter().zip(v).map(|(a, b)| a * b).sum::<f32>())
+    }
+
+    fn max_links(&self, layer: usize) -> usize { if layer == 0 { 2 * self.m } else { self.m } }
+
+    fn random_level(&self) -> usize {
+        let ml = 1.0 / (self.m as f64).ln();
+        let u: f64 = rand::random::<f64>().max(f64::MIN_POSITIVE);
+        ((-u.ln() * ml).floor() as usize).min(MAX_LEVEL)
+    }
+
+    /// Add (or replace) vector `id`. Returns false, leaving the graph untouched, for a zero
+    /// vector or one whose dimension differs from the vectors already indexed.
+    pub fn insert(&mut self, id: i64, vec: &[f32]) -> bool {
+        let Some(q) = normalize(vec) else { return false };
+        if self.dim != 0 && q.len() != self.dim { return false; }
+        if self.slots.contains_key(&id) { self.remove(id); }
+        self.dim = q.len();
+        self.dirty = true;
+
+        let level = self.random_level();
+        let slot = self.nodes.len() as u32;
+        self.nodes.push(Node { id, vec: q.clone(), links: vec![Vec::new(); level + 1], deleted: false });
+        self.slots.insert(id, slot);
+        let Some(entry) = self.entry else {
+            self.entry = Some(slot);
+            return true;
+        };
+
+        let top = self.nodes[entry as usize].links.len() - 1;
+        let mut ep = entry;
+        for layer in (level + 1..=top).rev() {
+            ep = self.greedy(&q, ep, layer);
+        }
+        for layer in (0..=level.min(top)).rev() {
+            let found = self.search_layer(&q, ep, self.ef_construction, layer);
+            let neighbours: Vec<u32> = found.iter().take(self.m).map(|&(_, s)| s).collect();
+            for &n in &neighbours { self.link(n, slot, layer); }
+            self.nodes[slot as usize].links[layer] = neighbours;
+            ep = found[0].1;
+        }
+        if level > top { self.entry = Some(slot); }
+        true
+    }
+
+    /// Add `to` to `from`'s neighbours on `layer`, keeping only the closest if over capacity.
+    fn link(&mut self, from: u32, to: u32, layer: usize) {
+        let max = self.max_links(layer);
+        let links = &mut self.nodes[from as usize].links[layer];
+        links.push(to);
+        if links.len() <= max { return; }
+        let mut links = std::mem::take(links);
+        let base = self.nodes[from as usize].vec.clone();
+        links.sort_by_key(|&s| self.dist(&base, s));
+        links.truncate(max);
+        self.nodes[from as usize].links[layer] = links;
+    }
+
+    pub fn remove(&mut self, id: i64) {
+        let Some(slot) = self.slots.remove(&id) else { return };
+        self.nodes[slot as usize].deleted = true;
+        self.deleted += 1;
+        self.dirty = true;
+        if self.slots.is_empty() {
+            *self = Hnsw { dirty: true, ..Hnsw::new(self.m, self.ef_construction) };
+        } else if self.deleted * 4 > self.nodes.len() {
+            self.rebuild();
+        }
+    }
+
+    fn rebuild(&mut self) {
+        let mut fresh = Hnsw::new(self.m, self.ef_construction);
+        for node in self.nodes.iter().filter(|n| !n.deleted) {
+            fresh.insert(node.id, &node.vec);
+        }
+        *self = fresh;
+    }
+
+    fn greedy(&self, q: &[f32], mut cur: u32, layer: usize) -> u32 {
+        let mut cur_d = self.dist(q, cur);
+        loop {
+            let mut changed = false;
+            for &n in &self.nodes[cur as usize].links[layer] {
+                let d = self.dist(q, n);
+                if d < cur_d { cur = n; cur_d = d; changed = true; }
+            }
+            if !changed { return cur; }
+        }
+    }
+
+    /// Best-first search of one layer from `ep`; returns up to `ef` slots, closest first.
+    fn search_layer(&self, q: &[f32], ep: u32, ef: usize, layer: usize) -> Vec<(Dist, u32)> {
+        let mut visited = HashSet::from([ep]);
+        let d = self.dist(q, ep);
+        let mut candidates = BinaryHeap::from([Reverse((d, ep))]);
+        let mut results = BinaryHeap::from([(d, ep)]);
+        while let Some(Reverse((d, c))) = candidates.pop() {
+            if results.len() >= ef && results.peek().is_some_and(|&(worst, _)| d > worst) { break; }
+            for &n in &self.nodes[c as usize].links[layer] {
+                if !visited.insert(n) { continue; }
+                let dn = self.dist(q, n);
+                if results.len() < ef || results.peek().is_some_and(|&(worst, _)| dn < worst) {
+                    candidates.push(Reverse((dn, n)));
+                    results.push((dn, n));
+                    if results.len() > ef { results.pop(); }
+                }
+            }
+        }
+        results.into_sorted_vec()
+    }
+
+    /// The `k` nearest ids with their cosine similarity, best first. `None` when `query`
+    /// does not match the indexed dimension (the caller should search exactly instead).
+    pub fn search(&self, query: &[f32], k: usize, ef: usize) -> Option<Vec<(i64, f32)>> {
+        let Some(entry) = self.entry else { return Some(Vec::new()) };
+        if query.len() != self.dim { return None; }
+        let q = normalize(query)?;
+        let mut ep = entry;
+        for layer in (1..self.nodes[entry as usize].links.len()).rev() {
+            ep = self.greedy(&q, ep, layer);
+        }
+        // Tombstones take result slots, so widen the beam by their share
+        let ef = ef.max(k) + self.deleted.min(ef);
+        Some(self.search_layer(&q, ep, ef, 0).into_iter()
+            .filter(|&(_, s)| !self.nodes[s as usize].deleted)
+            .take(k)
+            .map(|(d, s)| (self.nodes[s as usize].id, 1.0 - d.0))
+            .collect())
+    }
+
+    // ── Encoding ─────────────────────────────────────────────────────────────
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(32 + self.nodes.len() * (self.dim * 4 + 16 + self.m * 12));
+        out.extend_from_slice(MAGIC);
+        for v in [self.dim, self.m, self.ef_construction, self.nodes.len()] {
+            out.extend_from_slice(&(v as u32).to_le_bytes());
+        }
+        out.extend_from_slice(&self.entry.map_or(u32::MAX, |e| e).to_le_bytes());
+        for node in &self.nodes {
+            out.extend_from_slice(&node.id.to_le_bytes());
+            out.push(node.deleted as u8);
+            node.vec.iter().for_each(|x| out.extend_from_slice(&x.to_le_bytes()));
+            out.extend_from_slice(&(node.links.len() as u32).to_le_bytes());
+            for layer in &node.links {
+                out.extend_from_slice(&(layer.len() as u32).to_le_bytes());
+                layer.iter().for_each(|s| out.extend_from_slice(&s.to_le_bytes()));
+            }
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Hnsw, String> {
+        let mut r = Reader { bytes, pos: 0 };
+        if r.take(MAGIC.len())? != MAGIC { return Err("not an index file".into()); }
+        let (dim, m, ef_construction, n) = (r.u32()? as usize, r.u32()? as usize, r.u32()? as usize, r.u32()? as usize);
+        let entry = Some(r.u32()?).filter(|&e| e != u32::MAX);
+        let mut g = Hnsw { dim, entry, ..Hnsw::new(m, ef_construction) };
+        for slot in 0..n {
+            let id = i64::from_le_bytes(r.take(8)?.try_into().map_err(|_| "truncated index")?);
+            let deleted = r.take(1)?[0] == 1;
+            let vec = (0..dim).map(|_| r.u32().map(f32::from_bits)).collect::<Result<Vec<_>, _>>()?;
+            let layers = r.u32()? as usize;
+            if layers == 0 || layers > MAX_LEVEL + 1 { return Err("corrupt index".into()); }
+            let mut links = Vec::with_capacity(layers);
+            for _ in 0..layers {
+                let count = r.u32()? as usize;
+                let layer = (0..count).map(|_| r.u32()).collect::<Result<Vec<_>, _>>()?;
+                if layer.iter().any(|&s| s as usize >= n) { return Err("corrupt index".into()); }
+                links.push(layer);
+            }
+            if deleted { g.deleted += 1; } else { g.slots.insert(id, slot as u32); }
+            g.nodes.push(Node { id, vec, links, deleted });
+        }
+        // Every neighbour must exist on the layers it is linked from
+        for node in &g.nodes {
+            for (layer, links) in node.links.iter().enumerate() {
+                if links.iter().any(|&s| g.nodes[s as usize].links.len() <= layer) {
+                    return Err("corrupt index".into());
+                }
+            }
+        }
+        if g.entry.is_some_and(|e| e as usize >= n) || (g.entry.is_none() && n > 0) {
+            return Err("corrupt index".into());
+        }
+        Ok(g)
+    }
+}
+
+struct Reader<'a> { bytes: &'a [u8], pos: usize }
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(len).filter(|&e| e <= self.bytes.len()).ok_or("truncated index")?;
+        let out = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(out)
+    }
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().map_err(|_| "truncated index")?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic vectors so a recall failure reproduces.
+    fn vectors(n: usize, dim: usize, seed: u64) -> Vec<Vec<f32>> {
+        let mut s = seed;
+        let mut next = move || {
+            s = s.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((s >> 33) as f32 / (1u64 << 31) as f32) - 0.5
+        };
+        (0..n).map(|_| (0..dim).map(|_| next()).collect()).collect()
+    }
+
+    fn brute_force(live: &[(i64, Vec<f32>)], q: &[f32], k: usize) -> Vec<i64> {
+        let q = normalize(q).unwrap();
+        let mut scored: Vec<(f32, i64)> = live.iter()
+            .map(|(id, v)| (normalize(v).unwrap().iter().zip(&q).map(|(a, b)| a * b).sum(), *id))
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored.into_iter().take(k).map(|(_, id)| id).collect()
+    }
+
+    fn recall(g: &Hnsw, live: &[(i64, Vec<f32>)], queries: &[Vec<f32>], k: usize) -> f32 {
+        let hits: usize = queries.iter().map(|q| {
+            let exact = brute_force(live, q, k);
+            g.search(q, k, 64).unwrap().iter().filter(|(id, _)| exact.contains(id)).count()
+        }).sum();
+        hits as f32 / (queries.len() * k) as f32
+    }
+
+    #[test]
+    fn insert_remove_and_rebuild_keep_recall() {
+        let mut g = Hnsw::new(16, 100);
+        let mut live: Vec<(i64, Vec<f32>)> = vectors(600, 24, 7).into_iter().enumerate().map(|(i, v)| (i as i64, v)).collect();
+        for (id, v) in &live { assert!(g.insert(*id, v)); }
+        let queries = vectors(30, 24, 99);
+        assert!(recall(&g, &live, &queries, 10) >= 0.9);
+
+        // A few tombstones, then enough to cross the rebuild threshold
+        let removed: Vec<i64> = live.iter().map(|(id, _)| *id).filter(|id| id % 3 == 0).collect();
+        for (n, id) in removed.iter().enumerate() {
+            g.remove(*id);
+            if n == 10 { assert!(g.deleted > 0); }
+        }
+        // The rebuild dropped the tombstones from the graph
+        assert!(g.nodes.len() < 600 && g.deleted * 4 <= g.nodes.len());
+        live.retain(|(id, _)| id % 3 != 0);
+        assert_eq!(g.ids().count(), live.len());
+        for q in &queries {
+            assert!(g.search(q, 10, 64).unwrap().iter().all(|(id, _)| id % 3 != 0));
+        }
+        assert!(recall(&g, &live, &queries, 10) >= 0.9);
+    }
+
+    #[test]
+    fn replacing_an_id_moves_its_vector() {
+        let mut g = Hnsw::new(8, 32);
+        assert!(g.insert(1, &[1.0, 0.0]));
+        assert!(g.insert(2, &[0.0, 1.0]));
+        assert!(g.insert(1, &[0.0, 2.0]));
+        let hits = g.search(&[0.0, 1.0], 2, 16).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert!(hits.iter().all(|&(_, sim)| (sim - 1.0).abs() < 1e-5));
+    }
+
+    #[test]
+    fn bytes_round_trip_gives_same_results() {
+        let mut g = Hnsw::new(12, 64);
+        for (i, v) in vectors(300, 16, 3).iter().enumerate() { g.insert(i as i64, v); }
+        for id in [4, 40, 140] { g.remove(id); }
+        let back = Hnsw::from_bytes(&g.to_bytes()).unwrap();
+        assert_eq!(back.to_bytes(), g.to_bytes());
+        for q in vectors(10, 16, 5) {
+            assert_eq!(back.search(&q, 8, 32), g.search(&q, 8, 32));
+        }
+        let bytes = g.to_bytes();
+        assert!(Hnsw::from_bytes(&bytes[..bytes.len() - 3]).is_err());
+        assert!(Hnsw::from_bytes(b"not an index").is_err());
+    }
+
+    #[test]
+    fn dimension_mismatch_and_zero_vectors_are_refused() {
+        let mut g = Hnsw::new(8, 32);
+        assert_eq!(g.search(&[1.0, 0.0], 3, 16), Some(Vec::new()));
+        assert!(!g.insert(1, &[0.0, 0.0, 0.0]));
+        assert!(g.insert(1, &[1.0, 0.0, 0.0]));
+        assert!(!g.insert(2, &[1.0, 0.0]));
+        assert_eq!(g.ids().collect::<Vec<_>>(), [1]);
+        assert_eq!(g.search(&[1.0, 0.0], 3, 16), None);
+        assert_eq!(g.search(&[0.0, 0.0, 0.0], 3, 16), None);
+    }
+}
@@ -0,0 +1,157 @@
+//! Per-profile search indexes over `rag_docs`: the HNSW graph of embeddings and the
+//! in-memory FTS5 mirror of chunk text (`lexical.rs`).
+//!
+//! Both are kept in `AppState.rag_index` for the unlocked profile. The graph holds the
+//! vectors of one embedding model — the one being queried — and is also saved to
+//! `profiles/{user_id}/rag.hnsw` with that model's name, sealed with the profile's data key
+//! like the embeddings it is built from; the text mirror is never written to disk. Ingest and delete update
+//! them in place; on first use in a session they are reconciled against the ids in
+//! `rag_docs`, so rows written while they were not loaded (migration, a crash between the
+//! insert and the save) are picked up. A missing, unreadable or stale-key graph file is
+//! rebuilt from the table.
+
+use super::hnsw::Hnsw;
+use super::lexical::Lexical;
+use super::{bytes_to_emb, AAD_CONTENT, AAD_EMBEDDING, AAD_SECTION};
+use crate::datakey::DataKey;
+use crate::security::{db_path, write_atomic, AppState};
+use crate::session;
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+const AAD_INDEX: &str = "rag.hnsw";
+const FILE: &str      = "rag.hnsw";
+/// Graph degree and build beam; the usual defaults for a few hundred to a few hundred
+/// thousand vectors.
+const M: usize               = 16;
+const EF_CONSTRUCTION: usize = 100;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    /// HNSW approximate search, falling back to exact when the index cannot answer.
+    Ann,
+    /// Linear cosine scan over every stored embedding.
+    Exact,
+}
+
+pub struct RagIndex {
+    user_id:     String,
+    /// Embedding model whose vectors are in `graph`.
+    model:       String,
+    pub graph:   Hnsw,
+    pub lexical: Lexical,
+}
+
+/// The saved graph, if there is one for `model`. The file is the model name (length-prefixed)
+/// followed by the graph.
+fn load(user_id: &str, key: &DataKey, model: &str) -> Hnsw {
+    let bytes = std::fs::read(db_path(&session::user_db_name(user_id, FILE))).ok()
+        .and_then(|sealed| key.open_bytes(AAD_INDEX, &sealed).ok());
+    bytes.as_deref()
+        .and_then(|b| {
+            let len = u32::from_le_bytes(b.get(..4)?.try_into().ok()?) as usize;
+            (b.get(4..4 + len)? == model.as_bytes()).then(|| &b[4 + len..])
+        })
+        .and_then(|b| Hnsw::from_bytes(b).ok())
+        .unwrap_or_else(|| Hnsw::new(M, EF_CONSTRUCTION))
+}
+
+fn save(user_id: &str, key: &DataKey, model: &str, graph: &mut Hnsw) -> Result<(), String> {
+    let mut bytes = (model.len() as u32).to_le_bytes().to_vec();
+    bytes.extend_from_slice(model.as_bytes());
+    bytes.extend_from_slice(&graph.to_bytes());
+    let sealed = key.seal_bytes(AAD_INDEX, &bytes)?;
+    write_atomic(&db_path(&session::user_db_name(user_id, FILE)), &sealed)?;
+    graph.dirty = false;
+    Ok(())
+}
+
+/// Drop the cached indexes and the saved graph, e.g. after vectors were replaced in place.
+pub fn invalidate(state: &AppState, user_id: &str) {
+    *state.rag_index.lock().unwrap() = None;
+    let _ = std::fs::remove_file(db_path(&session::user_db_name(user_id, FILE)));
+}
+
+/// Decrypted embeddings for `ids` (rows without one are skipped).
+fn embeddings(conn: &rusqlite::Connection, key: &DataKey, ids: &[i64]) -> Result<Vec<(i64, Vec<f32>)>, String> {
+    let mut stmt = conn.prepare("SELECT embedding, enc FROM rag_docs WHERE id=?1 AND embedding IS NOT NULL")
+        .map_err(|e| e.to_string())?;
+    let mut out = Vec::with_capacity(ids.len());
+    for &id in ids {
+        let row: Option<(Vec<u8>, i64)> = stmt.query_row(params![id], |r| Ok((r.get(0)?, r.get(1)?)))
+            .optional().map_err(|e| e.to_string())?;
+        let Some((bytes, enc)) = row else { continue };
+        let bytes = if enc == 1 { key.open_bytes(AAD_EMBEDDING, &bytes)? } else { bytes };
+        out.push((id, bytes_to_emb(&bytes)));
+    }
+    Ok(out)
+}
+
+/// Bring `graph` in line with the rows of `rag_docs` embedded with `model`.
+fn reconcile_graph(conn: &rusqlite::Connection, key: &DataKey, model: &str, graph: &mut Hnsw) -> Result<(), String> {
+    let live: HashSet<i64> = {
+        let mut stmt = conn.prepare("SELECT id FROM rag_docs WHERE embedding IS NOT NULL AND embed_model = ?1").map_err(|e| e.to_string())?;
+        let ids = stmt.query_map(params![model], |r| r.get(0)).map_err(|e| e.to_string())?
+            .collect::<Result<HashSet<i64>, _>>();
+        ids.map_err(|e| e.to_string())?
+    };
+    let stale: Vec<i64> = graph.ids().filter(|id| !live.contains(id)).collect();
+    stale.into_iter().for_each(|id| graph.remove(id));
+    let indexed: HashSet<i64> = graph.ids().collect();
+    let missing: Vec<i64> = live.into_iter().filter(|id| !indexed.contains(id)).collect();
+    for (id, emb) in embeddings(conn, key, &missing)? {
+        graph.insert(id, &emb);
+    }
+    Ok(())
+}
+
+/// Fill a fresh text mirror from every row of `rag_docs`, embedded or not.
+fn fill_lexical(conn: &rusqlite::Connection, key: &DataKey) -> Result<Lexical, String> {
+    let lexical = Lexical::new()?;
+    let mut stmt = conn.prepare("SELECT id, content, section, enc FROM rag_docs").map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([], |r| Ok((r.get::<_, i64>(0)?, r.get::<_, String>(1)?, r.get::<_, Option<String>>(2)?, r.get::<_, i64>(3)? == 1)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    for (id, content, section, enc) in rows {
+        let (content, section) = if enc {
+            (key.open_text(AAD_CONTENT, &content)?, section.map(|s| key.open_text(AAD_SECTION, &s)).transpose()?)
+        } else {
+            (content, section)
+        };
+        lexical.insert(id, &content, section.as_deref())?;
+    }
+    Ok(lexical)
+}
+
+/// Run `f` on the unlocked profile's indexes with the graph for `model`, loading and
+/// reconciling them first if needed, and save the graph afterwards if `f` changed it.
+pub fn with_index<T>(
+    state: &AppState,
+    conn: &rusqlite::Connection,
+    key: &DataKey,
+    model: &str,
+    f: impl FnOnce(&mut RagIndex) -> T,
+) -> Result<T, String> {
+    let user_id = session::current_user(state).ok_or(session::LOCKED)?;
+    let mut slot = state.rag_index.lock().unwrap();
+    if slot.as_ref().is_none_or(|ix| ix.user_id != user_id) {
+        let mut graph = load(&user_id, key, model);
+        reconcile_graph(conn, key, model, &mut graph)?;
+        let lexical = fill_lexical(conn, key)?;
+        *slot = Some(RagIndex { user_id: user_id.clone(), model: model.to_string(), graph, lexical });
+    }
+    let ix = slot.as_mut().expect("index loaded above");
+    if ix.model != model {
+        // Another model was queried: swap the graph, keep the text mirror
+        let mut graph = load(&user_id, key, model);
+        reconcile_graph(conn, key, model, &mut graph)?;
+        ix.graph = graph;
+        ix.model = model.to_string();
+    }
+    let out = f(ix);
+    if ix.graph.dirty { save(&user_id, key, &ix.model, &mut ix.graph)?; }
+    Ok(out)
+}
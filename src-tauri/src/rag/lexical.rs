@@ -0,0 +1,58 @@
+//! In-memory FTS5 mirror of `rag_docs` for BM25 keyword search.
+//!
+//! Chunk text is encrypted at rest, and an FTS5 table on disk — even a contentless one —
+//! keeps its terms in the clear, so the mirror lives in an in-memory database owned by
+//! `RagIndex`, filled from decrypted chunks when the index is loaded and updated by
+//! ingest and delete alongside the HNSW graph. Row ids are `rag_docs.id`.
+
+use rusqlite::{params, Connection};
+use std::collections::HashSet;
+
+pub struct Lexical {
+    conn: Connection,
+}
+
+/// FTS5 query for free text: every word as a quoted term, any of them may match. Quoting
+/// keeps user input such as `C++`, `AND` or `"` from being read as query syntax.
+fn match_expr(query: &str) -> Option<String> {
+    let mut seen = HashSet::new();
+    let terms: Vec<String> = query.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty() && seen.insert(t.to_lowercase()))
+        .map(|t| format!("\"{t}\""))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" OR "))
+}
+
+impl Lexical {
+    pub fn new() -> Result<Lexical, String> {
+        let conn = Connection::open_in_memory().map_err(|e| e.to_string())?;
+        conn.execute_batch("CREATE VIRTUAL TABLE rag_fts USING fts5(content, section, tokenize = 'porter unicode61');")
+            .map_err(|e| e.to_string())?;
+        Ok(Lexical { conn })
+    }
+
+    pub fn insert(&self, id: i64, content: &str, section: Option<&str>) -> Result<(), String> {
+        self.remove(id)?;
+        self.conn.execute("INSERT INTO rag_fts(rowid, content, section) VALUES(?1, ?2, ?3)", params![id, content, section])
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn remove(&self, id: i64) -> Result<(), String> {
+        self.conn.execute("DELETE FROM rag_fts WHERE rowid = ?1", params![id]).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// The `k` best BM25 matches for `query`, best first, scored as `-bm25` (higher is better).
+    /// Section titles count half as much as the chunk text.
+    pub fn search(&self, query: &str, k: usize) -> Result<Vec<(i64, f32)>, String> {
+        let Some(expr) = match_expr(query) else { return Ok(Vec::new()) };
+        let mut stmt = self.conn.prepare(
+            "SELECT rowid, bm25(rag_fts, 1.0, 0.5) AS rank FROM rag_fts WHERE rag_fts MATCH ?1 ORDER BY rank LIMIT ?2"
+        ).map_err(|e| e.to_string())?;
+        let hits = stmt.query_map(params![expr, k as i64], |r| Ok((r.get(0)?, -(r.get::<_, f64>(1)? as f32))))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>();
+        hits.map_err(|e| e.to_string())
+    }
+}
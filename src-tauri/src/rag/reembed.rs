@@ -0,0 +1,126 @@
+//! Background re-embedding of the resume vault (`rag_reembed_all`).
+//!
+//! Rebuilds the embedding of every chunk not already embedded with the target model at its
+//! current dimension, one row at a time, so a job interrupted by a crash or a lock leaves the
+//! vault consistent and a rerun picks up where it stopped. Progress is kept in
+//! `AppState.rag_reembed` and emitted as `rag:reembed` events. The job stops when the
+//! profile it started under is locked or switched, or when it is cancelled.
+
+use super::chunker::Chunk;
+use super::{emb_to_bytes, index, AAD_CONTENT, AAD_EMBEDDING, AAD_SECTION};
+use crate::datakey::DataKey;
+use crate::llm;
+use crate::logs::{self, AuditEvent};
+use crate::security::AppState;
+use crate::session;
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Emitted after every chunk and once at the end: a `ReembedProgress`.
+pub const EVT_PROGRESS: &str = "rag:reembed";
+pub const CANCELLED: &str = "Re-embedding cancelled";
+
+#[derive(Serialize, Clone)]
+pub struct ReembedProgress {
+    pub job_id:   String,
+    pub model:    String,
+    /// Chunks needing a new embedding; 0 until the model has answered the first request.
+    pub total:    usize,
+    pub done:     usize,
+    pub failed:   usize,
+    pub finished: bool,
+    pub error:    Option<String>,
+}
+
+pub struct ReembedJob {
+    pub user_id:  String,
+    pub progress: ReembedProgress,
+    pub cancel:   Arc<AtomicBool>,
+}
+
+fn publish(app: &AppHandle, state: &AppState, progress: &ReembedProgress) {
+    if let Some(job) = state.rag_reembed.lock().unwrap().as_mut() {
+        if job.progress.job_id == progress.job_id { job.progress = progress.clone(); }
+    }
+    let _ = app.emit(EVT_PROGRESS, progress);
+}
+
+/// Run the job to completion on the calling (blocking) thread, publishing progress as it goes.
+pub fn run(app: &AppHandle, user_id: &str, key: &DataKey, mut progress: ReembedProgress, cancel: &AtomicBool) {
+    let state = app.state::<AppState>();
+    let result = reembed(app, &state, user_id, key, &mut progress, cancel);
+    if progress.done > 0 {
+        // Vectors were replaced in place; rebuild the index from the table on next use
+        index::invalidate(&state, user_id);
+    }
+    progress.finished = true;
+    progress.error = result.err();
+    if progress.done > 0 || progress.failed > 0 {
+        let _ = logs::record(&AuditEvent::RagReembedded {
+            user_id: user_id.to_string(), model: progress.model.clone(), chunks: progress.done, failed: progress.failed,
+        });
+    }
+    publish(app, &state, &progress);
+}
+
+fn reembed(
+    app: &AppHandle,
+    state: &AppState,
+    user_id: &str,
+    key: &DataKey,
+    progress: &mut ReembedProgress,
+    cancel: &AtomicBool,
+) -> Result<(), String> {
+    let conn = session::open_profile_db(user_id, "rag.db")?;
+    let backend = llm::current_backend(state)?;
+    // The model's dimension decides which stored vectors are already current
+    let dim = backend.embed("dimension probe", &progress.model)
+        .map_err(|e| format!("Embedding model \"{}\" is not available: {e}", progress.model))?
+        .len() as i64;
+
+    let ids: Vec<i64> = {
+        let mut stmt = conn.prepare(
+            "SELECT id FROM rag_docs WHERE embedding IS NULL OR embed_model IS NOT ?1 OR embed_dim IS NOT ?2 ORDER BY id"
+        ).map_err(|e| e.to_string())?;
+        let ids = stmt.query_map(params![progress.model, dim], |r| r.get(0)).map_err(|e| e.to_string())?
+            .collect::<Result<Vec<i64>, _>>();
+        ids.map_err(|e| e.to_string())?
+    };
+    progress.total = ids.len();
+    publish(app, state, progress);
+
+    for id in ids {
+        if cancel.load(Ordering::SeqCst) { return Err(CANCELLED.into()); }
+        if session::current_user(state).as_deref() != Some(user_id) {
+            return Err(session::LOCKED.into());
+        }
+        // The row may have been deleted since the job started; a database error ends the job
+        let row: Option<(String, Option<String>, bool)> = conn.query_row(
+            "SELECT content, section, enc FROM rag_docs WHERE id=?1", params![id],
+            |r| Ok((r.get(0)?, r.get(1)?, r.get::<_, i64>(2)? == 1)),
+        ).optional().map_err(|e| e.to_string())?;
+        let Some((content, section, enc)) = row else { continue };
+        let chunk = if enc {
+            Chunk { text: key.open_text(AAD_CONTENT, &content)?, section: section.map(|s| key.open_text(AAD_SECTION, &s)).transpose()? }
+        } else {
+            Chunk { text: content, section }
+        };
+        match backend.embed(&chunk.with_context(), &progress.model) {
+            Ok(emb) if emb.len() as i64 == dim => {
+                let bytes = emb_to_bytes(&emb);
+                let bytes = if enc { key.seal_bytes(AAD_EMBEDDING, &bytes)? } else { bytes };
+                conn.execute(
+                    "UPDATE rag_docs SET embedding=?1, embed_model=?2, embed_dim=?3 WHERE id=?4",
+                    params![bytes, progress.model, dim, id],
+                ).map_err(|e| e.to_string())?;
+                progress.done += 1;
+            }
+            _ => progress.failed += 1,
+        }
+        publish(app, state, progress);
+    }
+    Ok(())
+}
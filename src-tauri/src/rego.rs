@@ -0,0 +1,379 @@
+
+//! Minimal Rego (OPA) subset — enough for `policies/*.rego`.
+//!
+//! Supported:
+//!   package a.b                         import … (rejected)
+//!   default allow = false               default allow := false
+//!   allow { expr; expr }                allow if { expr \n expr }
+//!   allow = true { … }                  helper { … }  (boolean helper rules, referenced by name)
+//!   expr:  term | not expr | term == term | term != term
+//!   term:  input.a.b | "string" | 42 | true | false | null | helper
+//!
+//! Rules with the same name are OR-ed; expressions in a body are AND-ed.
+//! Undefined references make an expression fail (and make `not` succeed), as in OPA.
+
+use serde::Serialize;
+use serde_json::Value;
+
+// ── Lexer ────────────────────────────────────────────────────────────────────
+#[derive(Clone, Debug, PartialEq)]
+enum Tok {
+    Ident(String),
+    Str(String),
+    Num(Value),
+    LBrace,
+    RBrace,
+    Dot,
+    Semi,
+    Newline,
+    Assign,  // = or :=
+    Eq,      // ==
+    Neq,     // !=
+}
+
+fn lex(src: &str) -> Result<Vec<(Tok, usize)>, String> {
+    let mut out = Vec::new();
+    let chars: Vec<char> = src.chars().collect();
+    let (mut i, mut line) = (0, 1);
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '#' => { while i < chars.len() && chars[i] != '\n' { i += 1; } continue; }
+            '\n' => { out.push((Tok::Newline, line)); line += 1; }
+            c if c.is_whitespace() => {}
+            '{' => out.push((Tok::LBrace, line)),
+            '}' => out.push((Tok::RBrace, line)),
+            '.' => out.push((Tok::Dot, line)),
+            ';' => out.push((Tok::Semi, line)),
+            '=' if chars.get(i + 1) == Some(&'=') => { out.push((Tok::Eq, line)); i += 1; }
+            '=' => out.push((Tok::Assign, line)),
+            ':' if chars.get(i + 1) == Some(&'=') => { out.push((Tok::Assign, line)); i += 1; }
+            '!' if chars.get(i + 1) == Some(&'=') => { out.push((Tok::Neq, line)); i += 1; }
+            '"' => {
+                let mut s = String::new();
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    if chars[i] == '\\' && i + 1 < chars.len() { i += 1; }
+                    if chars[i] == '\n' { return Err(format!("line {line}: unterminated string")); }
+                    s.push(chars[i]);
+                    i += 1;
+                }
+                if i >= chars.len() { return Err(format!("line {line}: unterminated string")); }
+                out.push((Tok::Str(s), line));
+            }
+            c if c.is_ascii_digit() || (c == '-' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit())) => {
+                let start = i;
+                i += 1;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') { i += 1; }
+                let text: String = chars[start..i].iter().collect();
+                // Keep integers integral so `input.n == 3` matches a JSON 3, not 3.0
+                let num = match text.parse::<i64>() {
+                    Ok(n)  => Value::from(n),
+                    Err(_) => text.parse::<f64>().ok()
+                                  .and_then(serde_json::Number::from_f64)
+                                  .map(Value::Number)
+                                  .ok_or_else(|| format!("line {line}: bad number {text}"))?,
+                };
+                out.push((Tok::Num(num), line));
+                continue;
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') { i += 1; }
+                out.push((Tok::Ident(chars[start..i].iter().collect()), line));
+                continue;
+            }
+            other => return Err(format!("line {line}: unsupported character '{other}'")),
+        }
+        i += 1;
+    }
+    Ok(out)
+}
+
+// ── AST ──────────────────────────────────────────────────────────────────────
+#[derive(Clone, Debug)]
+pub enum Term {
+    /// `input.a.b` (first segment "input") or a helper rule name (single segment)
+    Ref(Vec<String>),
+    Lit(Value),
+}
+
+#[derive(Clone, Debug)]
+pub enum Expr {
+    Term(Term),
+    Not(Box<Expr>),
+    Eq(Term, Term),
+    Neq(Term, Term),
+}
+
+#[derive(Clone, Debug)]
+pub struct Rule {
+    pub name:       String,
+    pub value:      Value,
+    pub body:       Vec<Expr>,
+    pub is_default: bool,
+    pub line:       usize,
+    pub file:       String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Module {
+    pub package: String,
+    pub rules:   Vec<Rule>,
+}
+
+impl std::fmt::Display for Term {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Term::Ref(path) => write!(f, "{}", path.join(".")),
+            Term::Lit(v)    => write!(f, "{}", v),
+        }
+    }
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expr::Term(t)   => write!(f, "{t}"),
+            Expr::Not(e)    => write!(f, "not {e}"),
+            Expr::Eq(a, b)  => write!(f, "{a} == {b}"),
+            Expr::Neq(a, b) => write!(f, "{a} != {b}"),
+        }
+    }
+}
+
+impl Expr {
+    /// First `input.<field>` referenced by this expression.
+    pub fn input_field(&self) -> Option<&str> {
+        fn field(t: &Term) -> Option<&str> {
+            match t {
+                Term::Ref(p) if p.len() >= 2 && p[0] == "input" => Some(p[1].as_str()),
+                _ => None,
+            }
+        }
+        match self {
+            Expr::Term(t) => field(t),
+            Expr::Not(e)  => e.input_field(),
+            Expr::Eq(a, b) | Expr::Neq(a, b) => field(a).or_else(|| field(b)),
+        }
+    }
+}
+
+// ── Parser ───────────────────────────────────────────────────────────────────
+struct Parser<'a> {
+    toks: Vec<(Tok, usize)>,
+    pos:  usize,
+    file: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Tok> { self.toks.get(self.pos).map(|t| &t.0) }
+    fn line(&self) -> usize { self.toks.get(self.pos).or(self.toks.last()).map(|t| t.1).unwrap_or(0) }
+    fn next(&mut self) -> Option<Tok> { let t = self.toks.get(self.pos).map(|t| t.0.clone()); self.pos += 1; t }
+    fn err<T>(&self, msg: &str) -> Result<T, String> { Err(format!("{}:{}: {}", self.file, self.line(), msg)) }
+    fn skip_newlines(&mut self) { while self.peek() == Some(&Tok::Newline) { self.pos += 1; } }
+
+    fn expect(&mut self, tok: Tok) -> Result<(), String> {
+        if self.peek() == Some(&tok) { self.pos += 1; Ok(()) } else { self.err(&format!("expected {:?}", tok)) }
+    }
+
+    fn ident(&mut self) -> Result<String, String> {
+        match self.next() { Some(Tok::Ident(s)) => Ok(s), _ => { self.pos -= 1; self.err("expected identifier") } }
+    }
+
+    fn dotted(&mut self) -> Result<Vec<String>, String> {
+        let mut path = vec![self.ident()?];
+        while self.peek() == Some(&Tok::Dot) { self.pos += 1; path.push(self.ident()?); }
+        Ok(path)
+    }
+
+    fn term(&mut self) -> Result<Term, String> {
+        match self.peek().cloned() {
+            Some(Tok::Str(s)) => { self.pos += 1; Ok(Term::Lit(Value::String(s))) }
+            Some(Tok::Num(n)) => { self.pos += 1; Ok(Term::Lit(n)) }
+            Some(Tok::Ident(id)) if id == "true" || id == "false" => { self.pos += 1; Ok(Term::Lit(Value::Bool(id == "true"))) }
+            Some(Tok::Ident(id)) if id == "null" => { self.pos += 1; Ok(Term::Lit(Value::Null)) }
+            Some(Tok::Ident(_)) => Ok(Term::Ref(self.dotted()?)),
+            _ => self.err("expected a term"),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        if self.peek() == Some(&Tok::Ident("not".into())) {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.expr()?)));
+        }
+        let lhs = self.term()?;
+        match self.peek() {
+            Some(Tok::Eq)  => { self.pos += 1; Ok(Expr::Eq(lhs, self.term()?)) }
+            Some(Tok::Neq) => { self.pos += 1; Ok(Expr::Neq(lhs, self.term()?)) }
+            Some(Tok::Assign) => self.err("use == for comparison inside rule bodies"),
+            _ => Ok(Expr::Term(lhs)),
+        }
+    }
+
+    fn body(&mut self) -> Result<Vec<Expr>, String> {
+        self.expect(Tok::LBrace)?;
+        let mut exprs = Vec::new();
+        loop {
+            while matches!(self.peek(), Some(Tok::Newline) | Some(Tok::Semi)) { self.pos += 1; }
+            match self.peek() {
+                Some(Tok::RBrace) => { self.pos += 1; break; }
+                None => return self.err("unterminated rule body"),
+                _ => exprs.push(self.expr()?),
+            }
+            match self.peek() {
+                Some(Tok::Newline) | Some(Tok::Semi) | Some(Tok::RBrace) => {}
+                _ => return self.err("expected ';' or newline between expressions"),
+            }
+        }
+        if exprs.is_empty() { return self.err("empty rule body"); }
+        Ok(exprs)
+    }
+
+    fn literal_value(&mut self) -> Result<Value, String> {
+        match self.term()? {
+            Term::Lit(v) => Ok(v),
+            Term::Ref(_) => self.err("rule values must be literals"),
+        }
+    }
+
+    fn module(&mut self) -> Result<Module, String> {
+        let mut m = Module::default();
+        self.skip_newlines();
+        if self.peek() == Some(&Tok::Ident("package".into())) {
+            self.pos += 1;
+            m.package = self.dotted()?.join(".");
+        } else {
+            return self.err("expected 'package'");
+        }
+        loop {
+            self.skip_newlines();
+            let Some(tok) = self.peek().cloned() else { break };
+            let line = self.line();
+            match tok {
+                Tok::Ident(kw) if kw == "import" => return self.err("imports are not supported"),
+                Tok::Ident(kw) if kw == "default" => {
+                    self.pos += 1;
+                    let name = self.ident()?;
+                    self.expect(Tok::Assign)?;
+                    let value = self.literal_value()?;
+                    m.rules.push(Rule { name, value, body: Vec::new(), is_default: true, line, file: self.file.into() });
+                }
+                Tok::Ident(_) => {
+                    let name = self.ident()?;
+                    let value = if self.peek() == Some(&Tok::Assign) { self.pos += 1; self.literal_value()? } else { Value::Bool(true) };
+                    if self.peek() == Some(&Tok::Ident("if".into())) { self.pos += 1; }
+                    let body = self.body()?;
+                    m.rules.push(Rule { name, value, body, is_default: false, line, file: self.file.into() });
+                }
+                _ => return self.err("expected a rule"),
+            }
+        }
+        Ok(m)
+    }
+}
+
+pub fn parse(file: &str, src: &str) -> Result<Module, String> {
+    Parser { toks: lex(src)?, pos: 0, file }.module()
+}
+
+// ── Evaluation ───────────────────────────────────────────────────────────────
+/// Why a single rule did or didn't fire.
+#[derive(Serialize, Clone, Debug)]
+pub struct RuleTrace {
+    pub file:        String,
+    pub line:        usize,
+    pub rule:        String,
+    pub matched:     bool,
+    /// First expression in the body that was false/undefined.
+    pub failed_expr: Option<String>,
+    /// The `input` field that expression tested, e.g. `privacy_mode`.
+    pub failed_input: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Decision {
+    pub value:   Option<Value>,
+    /// The rule that produced `value` (a body rule, or the `default`).
+    pub matched: Option<RuleTrace>,
+    /// Every non-default rule for the queried name, in source order.
+    pub rules:   Vec<RuleTrace>,
+}
+
+const MAX_DEPTH: usize = 16;
+
+struct Eval<'a> { rules: &'a [Rule], input: &'a Value }
+
+impl<'a> Eval<'a> {
+    fn term(&self, t: &Term, depth: usize) -> Option<Value> {
+        match t {
+            Term::Lit(v) => Some(v.clone()),
+            Term::Ref(path) if path[0] == "input" => {
+                path[1..].iter().try_fold(self.input, |node, key| node.get(key)).cloned()
+            }
+            Term::Ref(path) if path.len() == 1 => self.query(&path[0], depth + 1).value,
+            Term::Ref(_) => None,
+        }
+    }
+
+    fn expr(&self, e: &Expr, depth: usize) -> bool {
+        match e {
+            Expr::Term(t)   => !matches!(self.term(t, depth), None | Some(Value::Bool(false))),
+            Expr::Not(inner) => !self.expr(inner, depth),
+            Expr::Eq(a, b)  => matches!((self.term(a, depth), self.term(b, depth)), (Some(x), Some(y)) if x == y),
+            Expr::Neq(a, b) => matches!((self.term(a, depth), self.term(b, depth)), (Some(x), Some(y)) if x != y),
+        }
+    }
+
+    fn trace(&self, r: &Rule, depth: usize) -> RuleTrace {
+        let failed = r.body.iter().find(|e| !self.expr(e, depth));
+        RuleTrace {
+            file:         r.file.clone(),
+            line:         r.line,
+            rule:         r.name.clone(),
+            matched:      failed.is_none(),
+            failed_expr:  failed.map(|e| e.to_string()),
+            failed_input: failed.and_then(|e| e.input_field()).map(str::to_string),
+        }
+    }
+
+    fn query(&self, name: &str, depth: usize) -> Decision {
+        if depth > MAX_DEPTH {
+            return Decision { value: None, matched: None, rules: Vec::new() };
+        }
+        let rules: Vec<RuleTrace> = self.rules.iter()
+            .filter(|r| r.name == name && !r.is_default)
+            .map(|r| self.trace(r, depth))
+            .collect();
+        let hit = self.rules.iter()
+            .filter(|r| r.name == name && !r.is_default)
+            .zip(rules.iter())
+            .find(|(_, t)| t.matched);
+        if let Some((rule, trace)) = hit {
+            return Decision { value: Some(rule.value.clone()), matched: Some(trace.clone()), rules };
+        }
+        let default = self.rules.iter().find(|r| r.name == name && r.is_default);
+        Decision {
+            value:   default.map(|r| r.value.clone()),
+            matched: default.map(|r| RuleTrace { file: r.file.clone(), line: r.line, rule: format!("default {}", r.name), matched: true, failed_expr: None, failed_input: None }),
+            rules,
+        }
+    }
+}
+
+/// Evaluate rule `name` across `modules` against `input`.
+pub fn query(modules: &[Module], name: &str, input: &Value) -> Decision {
+    let rules: Vec<Rule> = modules.iter().flat_map(|m| m.rules.iter().cloned()).collect();
+    Eval { rules: &rules, input }.query(name, 0)
+}
+
+/// True if the rule's body compares `input.<field>` to `value` — used to
+/// narrow an explanation down to the rules that concern one capability.
+pub fn rule_mentions(rule: &Rule, field: &str, value: &Value) -> bool {
+    rule.body.iter().any(|e| match e {
+        Expr::Eq(Term::Ref(p), Term::Lit(v)) | Expr::Eq(Term::Lit(v), Term::Ref(p)) =>
+            p.len() == 2 && p[0] == "input" && p[1] == field && v == value,
+        _ => false,
+    })
+}
@@ -1,13 +1,41 @@
 
+use crate::admin::AdminSession;
+use crate::admin_keys::PendingApproval;
+use crate::checkpoints::Checkpoint;
+use crate::config::AppConfig;
+use crate::nonce::NonceStore;
+use crate::rag::{RagIndex, ReembedJob};
+use crate::session::UnlockedSession;
 use rusqlite::Connection;
+use std::collections::{HashMap, HashSet};
+use std::sync::{atomic::AtomicBool, Arc};
 use std::{fs, path::PathBuf};
 
 #[derive(Default)]
 pub struct AppState {
-    pub admin_unlocked: std::sync::Mutex<bool>,
-    pub admin_nonce: std::sync::Mutex<Option<String>>,
-    pub pending_user_consent: std::sync::Mutex<Option<(String, String)>>,
+    /// Unlocked admin session and its scopes, if any (see `admin::require`).
+    pub admin: std::sync::Mutex<Option<AdminSession>>,
+    /// Outstanding admin-unlock and consent nonces plus failed-attempt counters.
+    pub nonces: std::sync::Mutex<NonceStore>,
+    /// m-of-n approvals being collected for destructive admin actions (see `admin_keys.rs`).
+    pub approvals: std::sync::Mutex<HashMap<String, PendingApproval>>,
+    /// Log head snapshot awaiting the admin's signature (`logs_checkpoint_prepare`).
+    pub pending_checkpoint: std::sync::Mutex<Option<Checkpoint>>,
+    /// Session-scoped opt-in to non-loopback LLM endpoints.
+    pub consent_cloud_llm: std::sync::Mutex<bool>,
+    /// Unlocked profile and its signing key, if any (see `session.rs`).
+    pub session: std::sync::Mutex<Option<UnlockedSession>>,
     pub answer_style: std::sync::Mutex<String>,
+    /// ANN index of the unlocked profile's resume vault, loaded on first retrieval.
+    pub rag_index: std::sync::Mutex<Option<RagIndex>>,
+    /// Latest `rag_reembed_all` job and its progress; kept after it finishes.
+    pub rag_reembed: std::sync::Mutex<Option<ReembedJob>>,
+    /// In-flight LLM generations: request id → cancel flag.
+    pub llm_requests: std::sync::Mutex<HashMap<String, Arc<AtomicBool>>>,
+    /// Request ids cancelled by the user; their results must not be persisted.
+    pub llm_cancelled: std::sync::Mutex<HashSet<String>>,
+    /// Loaded from `app.yaml` at startup; replaced by `set_config`.
+    pub config: std::sync::Mutex<AppConfig>,
 }
 
 pub fn data_dir() -> PathBuf {
@@ -15,6 +43,13 @@ pub fn data_dir() -> PathBuf {
     base.join("promptpilot")
 }
 pub fn db_path(name: &str) -> PathBuf { data_dir().join(name) }
+/// Write via a temp file + rename so a crash never leaves a half-written file.
+pub fn write_atomic(path: &std::path::Path, bytes: &[u8]) -> Result<(), String> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, bytes).map_err(|e| e.to_string())?;
+    fs::rename(&tmp, path).map_err(|e| e.to_string())
+}
 pub fn open_db(name: &str) -> Connection {
     let path = db_path(name);
     fs::create_dir_all(path.parent().unwrap()).ok();
@@ -22,4 +57,3 @@ pub fn open_db(name: &str) -> Connection {
     conn.execute_batch("PRAGMA journal_mode=WAL;").unwrap();
     conn
 }
-pub fn load_config() -> Result<(), String> { Ok(()) }
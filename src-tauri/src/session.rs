@@ -0,0 +1,149 @@
+
+//! Unlocked profile session: the decrypted Ed25519 signing key lives here, and nowhere
+//! else, until `lock_profile` or `session.idle_timeout_secs` without use. Either way the
+//! profile's cached state goes with it (`forget_profile`).
+//! `SigningKey` zeroizes itself on drop, so replacing or clearing the session wipes the key.
+//! Per-profile stores (history, RAG, settings) live under `profiles/{user_id}/` and are
+//! only reachable through `open_user_db` while that profile is unlocked.
+
+use crate::consent::ConsentPayload;
+use crate::datakey::DataKey;
+use crate::nonce::Purpose;
+use crate::security::{open_db, AppState};
+use ed25519_dalek::{Signer, SigningKey};
+use rusqlite::Connection;
+use serde::Serialize;
+use std::sync::atomic::Ordering;
+use std::sync::MutexGuard;
+use std::time::{Duration, Instant};
+
+pub struct UnlockedSession {
+    pub user_id: String,
+    key:         SigningKey,
+    data_key:    DataKey,
+    last_used:   Instant,
+}
+
+impl UnlockedSession {
+    pub fn new(user_id: String, key: SigningKey, data_key: DataKey) -> Self {
+        UnlockedSession { user_id, key, data_key, last_used: Instant::now() }
+    }
+}
+
+fn idle_timeout(state: &AppState) -> Duration {
+    Duration::from_secs(state.config.lock().unwrap().session.idle_timeout_secs)
+}
+
+/// The session slot, after dropping the session if it has been idle too long. An expired
+/// session is forgotten like `lock_profile`; that cleanup runs with the slot released, so
+/// the session lock is never held while taking another.
+fn live_session(state: &AppState) -> MutexGuard<'_, Option<UnlockedSession>> {
+    let timeout = idle_timeout(state);
+    let mut slot = state.session.lock().unwrap();
+    if slot.as_ref().is_some_and(|s| s.last_used.elapsed() >= timeout) {
+        *slot = None;
+        drop(slot);
+        forget_profile(state);
+        slot = state.session.lock().unwrap();
+    }
+    slot
+}
+
+/// Drop what the last unlocked profile left in memory besides its keys: the answer style,
+/// the RAG index, and any running re-embedding job. Call without the session lock held.
+pub fn forget_profile(state: &AppState) {
+    state.answer_style.lock().unwrap().clear();
+    *state.rag_index.lock().unwrap() = None;
+    if let Some(job) = state.rag_reembed.lock().unwrap().as_ref() {
+        job.cancel.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Swap in a rotated signing key if `user_id` is the unlocked profile. The old key is
+/// zeroized as it is dropped.
+pub fn replace_key(state: &AppState, user_id: &str, key: SigningKey) {
+    if let Some(session) = state.session.lock().unwrap().as_mut().filter(|s| s.user_id == user_id) {
+        session.key = key;
+    }
+}
+
+pub const LOCKED: &str = "Profile is locked — unlock it in Settings to access its history, resume vault and settings";
+
+/// User id of the live session, if any. Does not count as activity.
+pub fn current_user(state: &AppState) -> Option<String> {
+    live_session(state).as_ref().map(|s| s.user_id.clone())
+}
+
+/// Run `f` with the live session's signing key and reset the idle timer.
+pub fn with_key<T>(state: &AppState, f: impl FnOnce(&str, &SigningKey) -> T) -> Result<T, String> {
+    let mut slot = live_session(state);
+    let session = slot.as_mut().ok_or(LOCKED)?;
+    session.last_used = Instant::now();
+    Ok(f(&session.user_id, &session.key))
+}
+
+/// Path of a per-profile store, relative to the data dir.
+pub fn user_db_name(user_id: &str, name: &str) -> String { format!("profiles/{user_id}/{name}") }
+
+/// Open a profile's store without its session. Only for admin maintenance that does not
+/// need to read the (encrypted) contents.
+pub fn open_profile_db(user_id: &str, name: &str) -> Result<Connection, String> {
+    let conn = open_db(&user_db_name(user_id, name));
+    // Overwrite deleted/updated content instead of leaving it in free pages
+    conn.execute_batch("PRAGMA secure_delete=ON;").map_err(|e| e.to_string())?;
+    Ok(conn)
+}
+
+/// Open `name` (e.g. `"settings.db"`) for the unlocked profile. Counts as activity.
+pub fn open_user_db(state: &AppState, name: &str) -> Result<Connection, String> {
+    let user_id = with_key(state, |user_id, _| user_id.to_string())?;
+    open_profile_db(&user_id, name)
+}
+
+/// Like `open_user_db`, plus the profile's data key for encrypted columns.
+pub fn open_encrypted_db(state: &AppState, name: &str) -> Result<(Connection, DataKey), String> {
+    let mut slot = live_session(state);
+    let session = slot.as_mut().ok_or(LOCKED)?;
+    session.last_used = Instant::now();
+    Ok((open_profile_db(&session.user_id, name)?, session.data_key.clone()))
+}
+
+#[derive(Serialize)]
+pub struct ConsentSignature {
+    pub user_id:       String,
+    /// What was signed: the canonical form of this payload.
+    pub payload:       ConsentPayload,
+    pub signature_b64: String,
+    pub pubkey_b64:    String,
+}
+
+/// Sign the pending consent request with the unlocked profile's key. Only the payload the
+/// admin issued for this user (action, parameters hash, nonce, expiry) is signed, never
+/// arbitrary data. Signing does not consume the nonce; the admin's verification does.
+#[tauri::command]
+pub fn sign_consent(nonce: String, state: tauri::State<AppState>) -> Result<ConsentSignature, String> {
+    let user_id = current_user(&state).ok_or(LOCKED)?;
+    let payload = state.nonces.lock().unwrap().peek(Purpose::UserConsent, Some(&user_id))
+        .map(ConsentPayload::for_nonce)
+        .map_err(|_| format!("no pending consent request for \"{user_id}\""))?;
+    if nonce != payload.nonce { return Err("nonce mismatch".to_string()); }
+    with_key(&state, |user_id, key| ConsentSignature {
+        user_id:       user_id.to_string(),
+        signature_b64: base64::encode(key.sign(payload.canonical().as_bytes()).to_bytes()),
+        pubkey_b64:    base64::encode(key.verifying_key().as_bytes()),
+        payload,
+    })
+}
+
+/// Forget the unlocked key.
+#[tauri::command]
+pub fn lock_profile(state: tauri::State<AppState>) -> Result<(), String> {
+    *state.session.lock().unwrap() = None;
+    forget_profile(state.inner());
+    Ok(())
+}
+
+#[tauri::command]
+pub fn session_user(state: tauri::State<AppState>) -> Result<Option<String>, String> {
+    Ok(current_user(&state))
+}
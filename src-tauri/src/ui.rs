@@ -1,7 +1,9 @@
 
-use crate::security::{open_db, AppState};
+use crate::security::AppState;
+use crate::logs::{self, AuditEvent};
+use crate::session;
 
-fn ensure_settings_table(conn: &rusqlite::Connection) -> Result<(), String> {
+pub fn ensure_settings_table(conn: &rusqlite::Connection) -> Result<(), String> {
     conn.execute(
         "CREATE TABLE IF NOT EXISTS settings(key TEXT PRIMARY KEY, value TEXT)",
         []
@@ -17,7 +19,7 @@ pub fn set_answer_style(style: String, state: tauri::State<AppState>) -> Result<
         _ => return Err(format!("Unknown style: {}", style)),
     }
     // Persist to SQLite
-    let conn = open_db("settings.db");
+    let conn = session::open_user_db(&state, "settings.db")?;
     ensure_settings_table(&conn)?;
     conn.execute(
         "INSERT OR REPLACE INTO settings(key, value) VALUES('answer_style', ?1)",
@@ -25,18 +27,19 @@ pub fn set_answer_style(style: String, state: tauri::State<AppState>) -> Result<
     ).map_err(|e| e.to_string())?;
     // Update in-memory
     *state.answer_style.lock().unwrap() = style;
+    logs::record(&AuditEvent::SettingsChanged { setting: "answer_style".into(), user_id: session::current_user(&state) })?;
     Ok(())
 }
 
 #[tauri::command]
 pub fn get_answer_style(state: tauri::State<AppState>) -> Result<String, String> {
-    // Prefer in-memory; fall back to DB on startup
+    // Prefer in-memory (cleared on unlock/lock); fall back to the profile's DB
     let mem = state.answer_style.lock().unwrap().clone();
     if !mem.is_empty() {
         return Ok(mem);
     }
     // Load from DB
-    let conn = open_db("settings.db");
+    let conn = session::open_user_db(&state, "settings.db")?;
     ensure_settings_table(&conn)?;
     let val: Option<String> = conn.query_row(
         "SELECT value FROM settings WHERE key='answer_style'",
@@ -1,51 +1,151 @@
 
-use crate::security::{open_db, db_path};
+use crate::admin::{self, Scope};
+use crate::envelope;
+use crate::logs::{self, AuditEvent};
+use crate::datakey;
+use crate::security::{open_db, db_path, write_atomic, AppState};
+use crate::session::{self, UnlockedSession};
+use crate::{history, rag, ui};
 use ed25519_dalek::{SigningKey, VerifyingKey, Signer};
 use rand::rngs::OsRng;
-use rusqlite::params;
+use rusqlite::{params, OptionalExtension};
 use serde::{Deserialize, Serialize};
-use sha2::{Sha256, Digest};
-use xchacha20poly1305::{XChaCha20Poly1305, aead::{Aead, KeyInit}, XNonce};
+
+/// Profile ids name directories under `profiles/`, so they are held to a strict charset.
+fn valid_user_id(user_id: &str) -> bool {
+    (1..=64).contains(&user_id.len()) && user_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
 
 fn profile_dir(user_id: &str) -> std::path::PathBuf { db_path(&format!("profiles/{user_id}")) }
-fn kdf(pass: &str) -> [u8;32] {
-    let mut h = Sha256::new(); h.update(pass.as_bytes());
-    let out = h.finalize(); let mut key=[0u8;32]; key.copy_from_slice(&out[..32]); key
+
+fn write_private(dir: &std::path::Path, blob: &[u8]) -> Result<(), String> {
+    write_atomic(&dir.join("priv.enc"), blob)
+}
+
+/// Write each file's new contents next to it as `*.new`. On error nothing is left behind.
+fn stage(files: Vec<(std::path::PathBuf, Vec<u8>)>) -> Result<Vec<(std::path::PathBuf, std::path::PathBuf)>, String> {
+    let mut staged = Vec::with_capacity(files.len());
+    for (path, bytes) in files {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".new");
+        let tmp = std::path::PathBuf::from(tmp);
+        if let Err(e) = std::fs::write(&tmp, bytes) {
+            let _ = std::fs::remove_file(&tmp);
+            discard(&staged);
+            return Err(e.to_string());
+        }
+        staged.push((tmp, path));
+    }
+    Ok(staged)
 }
-fn encrypt_private(sk: &[u8], pass: &str) -> Vec<u8> {
-    let key = kdf(pass); let cipher = XChaCha20Poly1305::new(&key.into());
-    let nonce_bytes: [u8;24] = rand::random(); let nonce = XNonce::from_slice(&nonce_bytes);
-    let mut blob = Vec::with_capacity(24+sk.len()+16); blob.extend_from_slice(&nonce_bytes);
-    let ct = cipher.encrypt(nonce, sk).expect("encrypt"); blob.extend_from_slice(&ct); blob
+
+fn discard(staged: &[(std::path::PathBuf, std::path::PathBuf)]) {
+    staged.iter().for_each(|(tmp, _)| { let _ = std::fs::remove_file(tmp); });
 }
-fn decrypt_private(blob: &[u8], pass: &str) -> Option<Vec<u8>> {
-    if blob.len()<24 {return None;} let key=kdf(pass);
-    let cipher=XChaCha20Poly1305::new(&key.into()); let nonce=XNonce::from_slice(&blob[..24]);
-    cipher.decrypt(nonce, &blob[24..]).ok()
+
+/// Move staged files over their targets.
+fn swap_in(staged: &[(std::path::PathBuf, std::path::PathBuf)]) -> Result<(), String> {
+    for (tmp, path) in staged {
+        std::fs::rename(tmp, path).map_err(|e| e.to_string())?;
+    }
+    Ok(())
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Profile { pub user_id:String, pub display_name:String, pub pubkey_b64:String }
 
+/// One entry of a profile's key history. `retired_at` is NULL for the current key.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ProfileKey { pub version:i64, pub pubkey_b64:String, pub created_at:String, pub retired_at:Option<String> }
+
+/// Statement signed by the outgoing key when a profile key is rotated.
+#[derive(Serialize)]
+struct KeyTransition<'a> {
+    #[serde(rename = "type")]
+    kind:           &'static str,
+    user_id:        &'a str,
+    from_version:   i64,
+    to_version:     i64,
+    old_pubkey_b64: String,
+    new_pubkey_b64: String,
+    ts:             u64,
+}
+
 fn ensure_profiles_table(conn: &rusqlite::Connection) -> Result<(), String> {
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS profiles(user_id TEXT PRIMARY KEY, display_name TEXT, pubkey BLOB)",
-        []
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS profiles(user_id TEXT PRIMARY KEY, display_name TEXT, pubkey BLOB);
+         CREATE TABLE IF NOT EXISTS profile_keys(
+             user_id TEXT NOT NULL, version INTEGER NOT NULL, pubkey BLOB NOT NULL,
+             created_at DATETIME DEFAULT CURRENT_TIMESTAMP, retired_at DATETIME,
+             PRIMARY KEY(user_id, version));
+         INSERT INTO profile_keys(user_id, version, pubkey)
+             SELECT user_id, 1, pubkey FROM profiles
+             WHERE user_id NOT IN (SELECT user_id FROM profile_keys);"
     ).map_err(|e| e.to_string())?;
     Ok(())
 }
 
+fn current_key_version(conn: &rusqlite::Connection, user_id: &str) -> Result<i64, String> {
+    conn.query_row(
+        "SELECT version FROM profile_keys WHERE user_id=?1 AND retired_at IS NULL ORDER BY version DESC LIMIT 1",
+        params![user_id], |r| r.get(0)
+    ).optional().map_err(|e| e.to_string())?.ok_or_else(|| format!("unknown profile: {user_id}"))
+}
+
+/// Decrypt `priv.enc` for `user_id`; `Err("decrypt failed")` on a wrong passphrase.
+fn open_private(dir: &std::path::Path, passphrase: &str) -> Result<SigningKey, String> {
+    let blob=std::fs::read(dir.join("priv.enc")).map_err(|e|e.to_string())?;
+    let opened=envelope::open(&blob,passphrase)?;
+    let sk:&[u8;32]=opened.plaintext.as_slice().try_into().map_err(|_|"corrupt private key")?;
+    Ok(SigningKey::from_bytes(sk))
+}
+
+/// `Ok` if `user_id` is a registered profile (so its id passed `create_profile`'s charset check).
+pub fn require_profile(user_id: &str) -> Result<(), String> {
+    let conn = open_db("profiles.db");
+    ensure_profiles_table(&conn)?;
+    current_key_version(&conn, user_id).map(|_| ())
+}
+
+/// True if `pubkey` is the current key of `user_id`, or with `include_retired` any key it
+/// ever had — only for checking signatures made before a rotation. Fresh signatures must
+/// come from the current key, so rotating revokes the old one.
+pub fn is_known_key(user_id: &str, pubkey: &[u8], include_retired: bool) -> Result<bool, String> {
+    let conn = open_db("profiles.db");
+    ensure_profiles_table(&conn)?;
+    conn.query_row(
+        "SELECT 1 FROM profile_keys WHERE user_id=?1 AND pubkey=?2 AND (?3 OR retired_at IS NULL)",
+        params![user_id, pubkey, include_retired], |_| Ok(())
+    ).optional().map(|r| r.is_some()).map_err(|e| e.to_string())
+}
+
 #[tauri::command]
-pub fn create_profile(user_id:String, display_name:String, passphrase:String)->Result<Profile,String>{
-    let mut rng=OsRng{}; let signing=SigningKey::generate(&mut rng); let verify:VerifyingKey=signing.verifying_key();
-    let enc=encrypt_private(&signing.to_bytes(), &passphrase);
-    let dir=profile_dir(&user_id); std::fs::create_dir_all(&dir).map_err(|e|e.to_string())?;
-    std::fs::write(dir.join("priv.enc"), &enc).map_err(|e|e.to_string())?;
-    std::fs::write(dir.join("pub.bin"), verify.as_bytes()).map_err(|e|e.to_string())?;
+pub fn create_profile(user_id:String, display_name:String, passphrase:String, state: tauri::State<AppState>)->Result<Profile,String>{
+    if !valid_user_id(&user_id) {
+        return Err("profile id must be 1-64 letters, digits, '_' or '-'".into());
+    }
     let conn=open_db("profiles.db");
     ensure_profiles_table(&conn)?;
-    conn.execute("INSERT OR REPLACE INTO profiles(user_id, display_name, pubkey) VALUES(?1, ?2, ?3)",
+    // Re-creating an id would overwrite its key and strand its data key under the old passphrase
+    let registered:bool=conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM profiles WHERE user_id=?1) OR EXISTS(SELECT 1 FROM profile_keys WHERE user_id=?1)",
+        params![user_id], |r| r.get(0)
+    ).map_err(|e|e.to_string())?;
+    let dir=profile_dir(&user_id);
+    if registered || dir.join("priv.enc").exists() || dir.join("data.key").exists() {
+        return Err(format!("profile \"{user_id}\" already exists"));
+    }
+    let mut rng=OsRng{}; let signing=SigningKey::generate(&mut rng); let verify:VerifyingKey=signing.verifying_key();
+    let kdf=state.config.lock().unwrap().kdf;
+    let enc=envelope::seal(&signing.to_bytes(), &passphrase, kdf)?;
+    std::fs::create_dir_all(&dir).map_err(|e|e.to_string())?;
+    write_private(&dir, &enc)?;
+    std::fs::write(dir.join("pub.bin"), verify.as_bytes()).map_err(|e|e.to_string())?;
+    conn.execute("INSERT INTO profiles(user_id, display_name, pubkey) VALUES(?1, ?2, ?3)",
         params![user_id,display_name,verify.as_bytes()]).map_err(|e|e.to_string())?;
+    conn.execute("INSERT INTO profile_keys(user_id, version, pubkey) VALUES(?1, 1, ?2)",
+        params![user_id,verify.as_bytes()]).map_err(|e|e.to_string())?;
+    logs::record(&AuditEvent::ProfileCreated { user_id: user_id.clone() })?;
     Ok(Profile{ user_id, display_name, pubkey_b64: base64::encode(verify.as_bytes()) })
 }
 
@@ -67,9 +167,45 @@ pub fn list_profiles() -> Result<Vec<Profile>, String> {
 }
 
 #[tauri::command]
-pub fn unlock_profile(user_id:String, passphrase:String)->Result<bool,String>{
-    let dir=profile_dir(&user_id); let blob=std::fs::read(dir.join("priv.enc")).map_err(|e|e.to_string())?;
-    let sk=decrypt_private(&blob,&passphrase).ok_or("decrypt failed")?; Ok(sk.len()==32)
+pub fn unlock_profile(user_id:String, passphrase:String, state: tauri::State<AppState>)->Result<bool,String>{
+    let result=unlock(&user_id, &passphrase, &state);
+    let event=match &result {
+        Ok(true)  => AuditEvent::ProfileUnlocked { user_id },
+        Ok(false) => AuditEvent::ProfileUnlockFailed { user_id, reason: "corrupt private key".into() },
+        Err(e)    => AuditEvent::ProfileUnlockFailed { user_id, reason: e.clone() },
+    };
+    logs::record(&event)?;
+    result
+}
+
+fn unlock(user_id:&str, passphrase:&str, state:&AppState)->Result<bool,String>{
+    let dir=profile_dir(user_id); let blob=std::fs::read(dir.join("priv.enc")).map_err(|e|e.to_string())?;
+    let opened=envelope::open(&blob,passphrase)?;
+    let Ok(sk)=<&[u8;32]>::try_from(opened.plaintext.as_slice()) else { return Ok(false) };
+    // Migrate legacy (v0, SHA-256) or outdated-cost envelopes now that we know the passphrase
+    let kdf=state.config.lock().unwrap().kdf;
+    if opened.needs_upgrade(kdf) {
+        write_private(&dir, &envelope::seal(&opened.plaintext, passphrase, kdf)?)?;
+    }
+    let data_key=datakey::load_or_create(&dir, passphrase, kdf)?;
+    *state.session.lock().unwrap()=Some(UnlockedSession::new(user_id.to_string(), SigningKey::from_bytes(sk), data_key));
+    // The previous profile's style, index and re-embedding job; migrated chunks are picked
+    // up when the index is next loaded
+    session::forget_profile(state);
+    encrypt_plaintext_rows(state)?;
+    Ok(true)
+}
+
+/// Encrypt rows written before encryption at rest (or imported by `migrate_global_data`),
+/// and record the embedding model of chunks stored before it was tracked.
+fn encrypt_plaintext_rows(state: &AppState)->Result<(), String>{
+    let (conn, key)=session::open_encrypted_db(state, "history.db")?;
+    history::encrypt_plaintext_rows(&conn, &key)?;
+    let (conn, key)=session::open_encrypted_db(state, "rag.db")?;
+    rag::encrypt_plaintext_rows(&conn, &key)?;
+    let model=state.config.lock().unwrap().llm.embed_model.clone();
+    rag::backfill_embed_model(&conn, &model)?;
+    Ok(())
 }
 
 #[tauri::command]
@@ -77,3 +213,166 @@ pub fn get_public_key(user_id:String)->Result<String,String>{
     let dir=profile_dir(&user_id); let pk=std::fs::read(dir.join("pub.bin")).map_err(|e|e.to_string())?;
     Ok(base64::encode(pk))
 }
+
+/// Re-seal `priv.enc` under a new passphrase (and the current KDF cost). The keypair is unchanged.
+#[tauri::command]
+pub fn change_passphrase(user_id:String, old:String, new:String, state: tauri::State<AppState>)->Result<bool,String>{
+    if new.is_empty() { return Err("new passphrase must not be empty".into()); }
+    let dir=profile_dir(&user_id);
+    let signing=open_private(&dir,&old)?;
+    let kdf=state.config.lock().unwrap().kdf;
+    // Both files are written in full before either replaces the old one
+    let mut files=vec![(dir.join("priv.enc"), envelope::seal(&signing.to_bytes(), &new, kdf)?)];
+    if let Some(data_key)=datakey::rewrap(&dir, &old, &new, kdf)? { files.push((datakey::file(&dir), data_key)); }
+    swap_in(&stage(files)?)?;
+    logs::record(&AuditEvent::PassphraseChanged { user_id })?;
+    Ok(true)
+}
+
+/// Replace the profile's Ed25519 keypair. The old key signs a statement naming the new
+/// key, which is written to the audit log; the old public key stays in `profile_keys`.
+#[tauri::command]
+pub fn rotate_profile_key(user_id:String, passphrase:String, state: tauri::State<AppState>)->Result<Profile,String>{
+    let dir=profile_dir(&user_id);
+    let old=open_private(&dir,&passphrase)?;
+    let mut conn=open_db("profiles.db");
+    ensure_profiles_table(&conn)?;
+    let from_version=current_key_version(&conn,&user_id)?;
+
+    let mut rng=OsRng{}; let signing=SigningKey::generate(&mut rng); let verify=signing.verifying_key();
+    let statement=KeyTransition {
+        kind: "key_rotation",
+        user_id: &user_id,
+        from_version,
+        to_version: from_version+1,
+        old_pubkey_b64: base64::encode(old.verifying_key().as_bytes()),
+        new_pubkey_b64: base64::encode(verify.as_bytes()),
+        ts: std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+    };
+    let statement_json=serde_json::to_string(&statement).map_err(|e|e.to_string())?;
+    let signature=old.sign(statement_json.as_bytes());
+
+    let kdf=state.config.lock().unwrap().kdf;
+    let enc=envelope::seal(&signing.to_bytes(), &passphrase, kdf)?;
+    let tx=conn.transaction().map_err(|e|e.to_string())?;
+    tx.execute("UPDATE profile_keys SET retired_at=CURRENT_TIMESTAMP WHERE user_id=?1 AND retired_at IS NULL",
+        params![user_id]).map_err(|e|e.to_string())?;
+    tx.execute("INSERT INTO profile_keys(user_id, version, pubkey) VALUES(?1, ?2, ?3)",
+        params![user_id,from_version+1,verify.as_bytes()]).map_err(|e|e.to_string())?;
+    tx.execute("UPDATE profiles SET pubkey=?2 WHERE user_id=?1", params![user_id,verify.as_bytes()]).map_err(|e|e.to_string())?;
+    // Stage the new key files, commit, then swap them in: a failure before the rename
+    // leaves both the table and the files on the old key
+    let staged=stage(vec![(dir.join("priv.enc"), enc), (dir.join("pub.bin"), verify.as_bytes().to_vec())])?;
+    if let Err(e)=tx.commit() { discard(&staged); return Err(e.to_string()); }
+    swap_in(&staged)?;
+    // The old key is retired, so an unlocked session must sign with the new one from now on
+    session::replace_key(&state, &user_id, signing);
+
+    // The rotation has happened; a failed audit write must not report it as failed
+    if let Err(e)=logs::record(&AuditEvent::KeyRotated {
+        user_id: user_id.clone(),
+        statement: statement_json,
+        signature_b64: base64::encode(signature.to_bytes()),
+    }) {
+        eprintln!("PromptPilot: key rotation of \"{user_id}\" not logged: {e}");
+    }
+
+    let display_name:String=conn.query_row("SELECT display_name FROM profiles WHERE user_id=?1", params![user_id], |r| r.get(0))
+        .map_err(|e|e.to_string())?;
+    Ok(Profile{ user_id, display_name, pubkey_b64: base64::encode(verify.as_bytes()) })
+}
+
+#[tauri::command]
+pub fn get_key_history(user_id:String)->Result<Vec<ProfileKey>,String>{
+    let conn=open_db("profiles.db");
+    ensure_profiles_table(&conn)?;
+    let mut stmt=conn.prepare(
+        "SELECT version, pubkey, COALESCE(created_at,''), retired_at FROM profile_keys WHERE user_id=?1 ORDER BY version DESC"
+    ).map_err(|e|e.to_string())?;
+    let keys: Result<Vec<ProfileKey>, _> = stmt.query_map(params![user_id], |row| {
+        let pk: Vec<u8> = row.get(1)?;
+        Ok(ProfileKey { version: row.get(0)?, pubkey_b64: base64::encode(&pk), created_at: row.get(2)?, retired_at: row.get(3)? })
+    }).map_err(|e|e.to_string())?
+      .collect::<Result<Vec<_>, _>>();
+    keys.map_err(|e|e.to_string())
+}
+
+/// Global (pre per-profile) stores and how to copy each into a profile.
+/// `id` columns are left out so rows get fresh ids in the target.
+struct GlobalStore {
+    file:   &'static str,
+    table:  &'static str,
+    cols:   &'static str,
+    ensure: fn(&rusqlite::Connection) -> Result<(), String>,
+}
+
+const GLOBAL_STORES: &[GlobalStore] = &[
+    GlobalStore { file: "history.db",  table: "answer_history", ensure: history::ensure_history_table,
+                  cols: "prompt_title, prompt_style, question, answer, score_json, favourite, created_at" },
+    GlobalStore { file: "rag.db",      table: "rag_docs",       ensure: rag::ensure_rag_table,
+                  cols: "filename, chunk_idx, content, embedding, created_at" },
+    GlobalStore { file: "settings.db", table: "settings",       ensure: ui::ensure_settings_table,
+                  cols: "key, value" },
+];
+
+#[derive(Serialize, Default)]
+pub struct MigrationReport { pub history:usize, pub rag_chunks:usize, pub settings:usize }
+
+/// Move data from the old global `history.db` / `rag.db` / `settings.db` into the
+/// profile `user_id`, which must be the unlocked one. The global files are removed afterwards.
+/// The legacy data belonged to whoever used the app before profiles existed, so only the
+/// first profile created may claim it; any other needs an admin with `history.manage`.
+#[tauri::command]
+pub fn migrate_global_data(user_id:String, state: tauri::State<AppState>)->Result<MigrationReport,String>{
+    if session::current_user(&state).as_deref()!=Some(user_id.as_str()) {
+        return Err(format!("Unlock \"{user_id}\" before assigning global data to it"));
+    }
+    let conn=open_db("profiles.db");
+    ensure_profiles_table(&conn)?;
+    let first:Option<String>=conn.query_row("SELECT user_id FROM profiles ORDER BY rowid ASC LIMIT 1", [], |r| r.get(0))
+        .optional().map_err(|e|e.to_string())?;
+    if first.as_deref()!=Some(user_id.as_str()) {
+        admin::require(&state, Scope::HistoryManage)?;
+    }
+    let mut report=MigrationReport::default();
+    for store in GLOBAL_STORES {
+        let global=db_path(store.file);
+        if !global.exists() { continue; }
+        let conn=session::open_user_db(&state, store.file)?;
+        (store.ensure)(&conn)?;
+        conn.execute("ATTACH DATABASE ?1 AS legacy", params![global.to_string_lossy()]).map_err(|e|e.to_string())?;
+        let has_table: bool=conn.query_row(
+            "SELECT COUNT(*) FROM legacy.sqlite_master WHERE type='table' AND name=?1", params![store.table], |r| r.get::<_,i64>(0)
+        ).map_err(|e|e.to_string())? > 0;
+        let moved=if has_table {
+            // Existing per-profile settings win over the global ones
+            conn.execute(&format!("INSERT OR IGNORE INTO main.{t}({c}) SELECT {c} FROM legacy.{t}", t=store.table, c=store.cols), [])
+                .map_err(|e|e.to_string())?
+        } else { 0 };
+        conn.execute("DETACH DATABASE legacy", []).map_err(|e|e.to_string())?;
+        drop(conn);
+        for suffix in ["", "-wal", "-shm"] {
+            let p=db_path(&format!("{}{suffix}", store.file));
+            if p.exists() { std::fs::remove_file(&p).map_err(|e|e.to_string())?; }
+        }
+        match store.table {
+            "answer_history" => report.history=moved,
+            "rag_docs"       => report.rag_chunks=moved,
+            _                => report.settings=moved,
+        }
+    }
+    // Reload the style and the indexes so the imported settings and chunks are picked up
+    state.answer_style.lock().unwrap().clear();
+    *state.rag_index.lock().unwrap()=None;
+    encrypt_plaintext_rows(&state)?;
+    logs::record(&AuditEvent::GlobalDataMigrated {
+        user_id, history: report.history, rag_chunks: report.rag_chunks, settings: report.settings,
+    })?;
+    Ok(report)
+}
+
+/// True if old global stores are still present in the data dir.
+#[tauri::command]
+pub fn has_global_data()->Result<bool,String>{
+    Ok(GLOBAL_STORES.iter().any(|s| db_path(s.file).exists()))
+}
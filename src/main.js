@@ -8,6 +8,26 @@ function invoke(cmd, args = {}) {
   return Promise.reject('Tauri runtime not found')
 }
 
+function listen(event, handler) {
+  if (window.__TAURI__?.event?.listen) return window.__TAURI__.event.listen(event, handler)
+  return Promise.resolve(() => { })
+}
+
+// ── Streaming LLM calls (llm:token / llm:done events keyed by requestId) ──────
+const newRequestId = () => `req-${Date.now().toString(36)}-${Math.random().toString(36).slice(2, 8)}`
+
+async function invokeStreaming(cmd, args, onToken, requestId = newRequestId()) {
+  const unToken = await listen('llm:token', e => {
+    if (e.payload?.request_id === requestId && onToken) onToken(e.payload.token)
+  })
+  const unDone = await listen('llm:done', e => {
+    if (e.payload?.request_id === requestId && e.payload.ok)
+      console.info(`[llm] ${cmd}: ${e.payload.completion_tokens} tokens in ${e.payload.total_duration_ms} ms`)
+  })
+  try { return await invoke(cmd, { ...args, requestId }) }
+  finally { unToken(); unDone() }
+}
+
 // ── Model settings (live, no save needed) ────────────────────────────────────
 const getGenModel = () => document.getElementById('gen-model')?.value || 'mistral'
 const getEmbedModel = () => document.getElementById('embed-model')?.value || 'nomic-embed-text'
@@ -63,22 +83,26 @@ document.querySelectorAll('.nav-item').forEach(item => {
   })
 })
 
-// ── Ollama status ──────────────────────────────────────────────────────────────
+// ── LLM backend status ─────────────────────────────────────────────────────────
+const BACKEND_LABELS = { ollama: 'Ollama', llamacpp: 'llama.cpp', openai: 'OpenAI-compatible' }
+
 async function checkOllama() {
   const dot = document.getElementById('ollama-dot')
   const label = document.getElementById('ollama-label')
   try {
-    const models = await invoke('ollama_status')
+    const status = await invoke('llm_status')
+    const name = BACKEND_LABELS[status.backend] || status.backend
+    const models = status.models
     dot.style.background = 'var(--green)'
     dot.style.boxShadow = '0 0 8px var(--green)'
-    label.textContent = `Ollama online (${models.length} models)`
+    label.textContent = `${name} online (${models.length} models)`
     label.style.color = 'var(--green)'
     updateOllamaModelList(models)
-    toast(`Ollama ready — ${models.length} model(s) available`, 'success', 2500)
+    toast(`${name} ready — ${models.length} model(s) available`, 'success', 2500)
   } catch (e) {
     dot.style.background = 'var(--red)'
     dot.style.boxShadow = '0 0 8px var(--red)'
-    label.textContent = 'Ollama offline'
+    label.textContent = 'LLM offline'
     label.style.color = 'var(--red)'
   }
 }
@@ -94,6 +118,39 @@ function updateOllamaModelList(models) {
 
 document.getElementById('check-ollama-btn').addEventListener('click', checkOllama)
 
+let appConfig = null
+
+async function loadConfig() {
+  try {
+    appConfig = await invoke('get_config')
+    document.getElementById('llm-backend').value = appConfig.llm.backend
+    document.getElementById('llm-base-url').value = appConfig.llm.base_url
+    document.getElementById('llm-api-key').value = appConfig.llm.api_key || ''
+    document.getElementById('gen-model').value = appConfig.llm.gen_model
+    document.getElementById('embed-model').value = appConfig.llm.embed_model
+  } catch { }
+}
+
+document.getElementById('llm-backend-save').addEventListener('click', async () => {
+  if (!appConfig) { toast('Config not loaded', 'error'); return }
+  const llm = { ...appConfig.llm }
+  const backend = document.getElementById('llm-backend').value
+  const baseUrl = document.getElementById('llm-base-url').value.trim()
+  if (backend !== llm.backend && !baseUrl) llm.base_url = ''
+  llm.backend = backend
+  if (baseUrl) llm.base_url = baseUrl
+  llm.api_key = document.getElementById('llm-api-key').value || null
+  try { await invoke('set_cloud_llm_consent', { granted: document.getElementById('llm-cloud-consent').checked }) } catch { }
+  llm.gen_model = getGenModel()
+  llm.embed_model = getEmbedModel()
+  try {
+    appConfig = await invoke('set_config', { config: { ...appConfig, llm } })
+    toast('LLM backend saved', 'success')
+    loadConfig()
+    checkOllama()
+  } catch (e) { toast(`${e}`, 'error') }
+})
+
 // ═══════════════════════════════════════════════════════════════════
 // PROMPTS panel
 // ═══════════════════════════════════════════════════════════════════
@@ -194,6 +251,7 @@ document.getElementById('goto-ai-btn').addEventListener('click', () => {
 // AI COACH panel
 // ═══════════════════════════════════════════════════════════════════
 let lastSavedHistId = null
+let aiRequestId = null
 
 // Fetch RAG context
 document.getElementById('ai-rag-btn').addEventListener('click', async () => {
@@ -222,20 +280,36 @@ document.getElementById('ai-generate-btn').addEventListener('click', async () =>
   out.textContent = '⏳ Generating… this may take 10-30 seconds depending on your hardware.'
   resultCard.style.display = 'block'
   document.getElementById('ai-score-card').style.display = 'none'
+  aiRequestId = newRequestId()
+  document.getElementById('ai-stop-btn').style.display = 'inline-flex'
   try {
-    const answer = await invoke('generate_answer', {
+    let streamed = false
+    const answer = await invokeStreaming('generate_answer', {
       promptTitle: q,
       promptBody: '',
       ragContext: document.getElementById('ai-context').value,
       style: document.getElementById('ai-style').value,
       model: getGenModel(),
-    })
-    typewriterEffect(out, answer)
+    }, token => {
+      if (!streamed) { out.textContent = ''; streamed = true }
+      out.textContent += token
+    }, aiRequestId)
+    if (!streamed) typewriterEffect(out, answer)
+    else out.textContent = answer
     toast('Answer generated!', 'success')
   } catch (e) {
     out.textContent = `Error: ${e}`
     toast(`Generation failed: ${e}`, 'error')
-  } finally { setLoading(btn, false) }
+  } finally {
+    setLoading(btn, false)
+    document.getElementById('ai-stop-btn').style.display = 'none'
+  }
+})
+
+document.getElementById('ai-stop-btn').addEventListener('click', async () => {
+  if (!aiRequestId) return
+  try { if (await invoke('llm_cancel', { requestId: aiRequestId })) toast('Generation cancelled', 'warn') }
+  catch (e) { toast(`Cancel failed: ${e}`, 'error') }
 })
 
 function typewriterEffect(el, text, speedMs = 8) {
@@ -259,6 +333,7 @@ document.getElementById('ai-save-btn').addEventListener('click', async () => {
       promptStyle: document.getElementById('ai-style').value,
       question: document.getElementById('ai-question').value,
       answer,
+      requestId: aiRequestId,
     })
     lastSavedHistId = id
     toast('Saved to history!', 'success')
@@ -271,7 +346,7 @@ document.getElementById('ai-score-btn').addEventListener('click', async () => {
   const btn = document.getElementById('ai-score-btn')
   setLoading(btn, true)
   try {
-    const raw = await invoke('score_answer', {
+    const raw = await invokeStreaming('score_answer', {
       question: document.getElementById('ai-question').value,
       answer,
       style: document.getElementById('ai-style').value,
@@ -352,8 +427,10 @@ function setupSpeechRecognition() {
   return r
 }
 
-document.getElementById('prac-start-btn').addEventListener('click', () => {
+document.getElementById('prac-start-btn').addEventListener('click', async () => {
   if (isRecording) return
+  let micAllowed = false
+  try { micAllowed = await invoke('policy_check', { capability: 'MIC' }) } catch { }
   isRecording = true
   practiceSeconds = 120
   updateTimerDisplay()
@@ -369,8 +446,9 @@ document.getElementById('prac-start-btn').addEventListener('click', () => {
     if (practiceSeconds <= 0) { document.getElementById('prac-stop-btn').click() }
   }, 1000)
 
-  recognition = setupSpeechRecognition()
-  if (recognition) recognition.start()
+  recognition = micAllowed ? setupSpeechRecognition() : null
+  if (!micAllowed) toast('Microphone blocked by policy (privacy mode or no unlocked profile) — type your answer', 'warn')
+  else if (recognition) recognition.start()
   else toast('Web Speech API not available on this platform — type your answer manually', 'warn')
 })
 
@@ -405,7 +483,7 @@ document.getElementById('prac-score-btn').addEventListener('click', async () =>
   const btn = document.getElementById('prac-score-btn')
   setLoading(btn, true)
   try {
-    const raw = await invoke('score_answer', { question: q, answer: a, style: 'STAR', model: getGenModel() })
+    const raw = await invokeStreaming('score_answer', { question: q, answer: a, style: 'STAR', model: getGenModel() })
     renderScoreCard('prac-score-card', 'prac-score-overall', 'prac-score-dims', 'prac-score-feedback', raw)
   } catch (e) { toast(`Scoring error: ${e}`, 'error') }
   finally { setLoading(btn, false) }
@@ -431,7 +509,7 @@ document.getElementById('jd-analyze-btn').addEventListener('click', async () =>
   setLoading(btn, true)
   document.getElementById('jd-results').style.display = 'none'
   try {
-    const raw = await invoke('analyze_job', { jobText: jd, model: getGenModel() })
+    const raw = await invokeStreaming('analyze_job', { jobText: jd, model: getGenModel() })
     let parsed
     try { parsed = JSON.parse(raw.match(/\{[\s\S]*\}/)?.[0] || raw) } catch {
       toast('Could not parse AI response. Try again.', 'error'); return
@@ -571,8 +649,55 @@ async function loadVaultDocs() {
       })
     })
   } catch (e) { el.innerHTML = `<div style="color:var(--red)">Error: ${e}</div>` }
+  await loadEmbeddingStatus()
+}
+
+async function loadEmbeddingStatus() {
+  const el = document.getElementById('vault-embed-status')
+  try {
+    const s = await invoke('rag_embedding_status')
+    const current = getEmbedModel()
+    const rows = s.by_model.map(m => {
+      const ok = m.model === current
+      return `<div style="color:${ok ? 'var(--text)' : 'var(--text-muted)'}">${ok ? '✅' : '⚠️'} ${m.model ?? 'unknown model'}${m.dim ? ` (${m.dim}d)` : ''} — ${m.chunks} chunks</div>`
+    })
+    if (s.missing) rows.push(`<div style="color:var(--text-muted)">⚠️ not embedded — ${s.missing} chunks</div>`)
+    el.innerHTML = rows.join('') || '<div style="color:var(--text-muted)">No chunks yet</div>'
+    const job = await invoke('rag_reembed_status')
+    if (job) showReembedProgress(job)
+  } catch (e) { el.innerHTML = `<div style="color:var(--red)">Error: ${e}</div>` }
+}
+
+function showReembedProgress(p) {
+  const el = document.getElementById('vault-reembed-progress')
+  el.style.display = ''
+  const count = p.total ? `${p.done + p.failed}/${p.total}` : 'starting'
+  el.textContent = p.finished
+    ? `${p.error ? `⚠️ ${p.error} — ` : '✅ '}${p.done} chunks re-embedded with ${p.model}${p.failed ? `, ${p.failed} failed` : ''}`
+    : `♻️ Re-embedding with ${p.model}: ${count}${p.failed ? ` (${p.failed} failed)` : ''}`
+  document.getElementById('vault-reembed-btn').disabled = !p.finished
+  document.getElementById('vault-reembed-cancel-btn').style.display = p.finished ? 'none' : ''
 }
 
+listen('rag:reembed', e => {
+  showReembedProgress(e.payload)
+  if (e.payload.finished) {
+    toast(e.payload.error ? `Re-embedding stopped: ${e.payload.error}` : `Re-embedded ${e.payload.done} chunks`, e.payload.error ? 'error' : 'success')
+    loadEmbeddingStatus()
+  }
+})
+
+document.getElementById('vault-reembed-btn').addEventListener('click', async () => {
+  const model = getEmbedModel()
+  if (!confirm(`Re-embed every chunk not yet embedded with "${model}"? This runs in the background.`)) return
+  try { showReembedProgress(await invoke('rag_reembed_all', { model })) }
+  catch (e) { toast(`Re-embed failed: ${e}`, 'error') }
+})
+
+document.getElementById('vault-reembed-cancel-btn').addEventListener('click', async () => {
+  await invoke('rag_reembed_cancel').catch(() => false)
+})
+
 document.getElementById('vault-ingest-btn').addEventListener('click', async () => {
   const name = document.getElementById('vault-name').value.trim()
   const content = document.getElementById('vault-content').value.trim()
@@ -580,8 +705,9 @@ document.getElementById('vault-ingest-btn').addEventListener('click', async () =
   const btn = document.getElementById('vault-ingest-btn')
   setLoading(btn, true)
   try {
-    const r = await invoke('rag_ingest', { filename: name, content, embedModel: getEmbedModel() })
-    showOut(document.getElementById('vault-out'), `✅ Ingested "${r.filename}"\n${r.chunks} chunks, ${r.embedded} embedded`)
+    const strategy = document.getElementById('vault-strategy').value || null
+    const r = await invoke('rag_ingest', { filename: name, content, embedModel: getEmbedModel(), strategy })
+    showOut(document.getElementById('vault-out'), `✅ Ingested "${r.filename}"\n${r.chunks} chunks (${r.strategy}), ${r.embedded} embedded`)
     toast(`"${name}" ingested — ${r.embedded} chunks embedded`, 'success')
     document.getElementById('vault-name').value = ''
     document.getElementById('vault-content').value = ''
@@ -592,6 +718,27 @@ document.getElementById('vault-ingest-btn').addEventListener('click', async () =
   } finally { setLoading(btn, false) }
 })
 
+document.getElementById('vault-ingest-file-btn').addEventListener('click', async () => {
+  const path = document.getElementById('vault-path').value.trim()
+  if (!path) { toast('Enter the file path', 'error'); return }
+  const btn = document.getElementById('vault-ingest-file-btn')
+  setLoading(btn, true)
+  try {
+    const strategy = document.getElementById('vault-strategy').value || null
+    const filename = document.getElementById('vault-name').value.trim() || null
+    const r = await invoke('rag_ingest_file', { path, filename, embedModel: getEmbedModel(), strategy })
+    showOut(document.getElementById('vault-out'),
+      `✅ Ingested "${r.filename}" (${r.format})\n${r.chunks} chunks in ${r.sections} sections (${r.strategy}), ${r.embedded} embedded`)
+    toast(`"${r.filename}" ingested — ${r.embedded} chunks embedded`, 'success')
+    document.getElementById('vault-name').value = ''
+    document.getElementById('vault-path').value = ''
+    await loadVaultDocs()
+  } catch (e) {
+    showOut(document.getElementById('vault-out'), `Error: ${e}`)
+    toast(`Ingest failed: ${e}`, 'error')
+  } finally { setLoading(btn, false) }
+})
+
 document.getElementById('vault-refresh-btn').addEventListener('click', () => { loadVaultDocs(); toast('Refreshed', 'info') })
 
 document.getElementById('vault-test-btn').addEventListener('click', async () => {
@@ -600,13 +747,14 @@ document.getElementById('vault-test-btn').addEventListener('click', async () =>
   const btn = document.getElementById('vault-test-btn')
   setLoading(btn, true)
   try {
-    const chunks = await invoke('rag_retrieve', { query: q, topK: 3, embedModel: getEmbedModel() })
+    const mode = document.getElementById('vault-test-mode').value || null
+    const chunks = await invoke('rag_retrieve', { query: q, topK: 3, embedModel: getEmbedModel(), mode })
     const el = document.getElementById('vault-test-out')
     el.innerHTML = chunks.length === 0
       ? '<div style="color:var(--text-muted);font-size:13px">No matching chunks found</div>'
       : chunks.map(c => `
           <div style="background:var(--surface);border:1px solid var(--border);border-radius:8px;padding:12px">
-            <div style="font-size:11px;color:var(--cyan);margin-bottom:6px">${c.filename} — chunk ${c.chunk_idx} — score ${c.score?.toFixed(3)}</div>
+            <div style="font-size:11px;color:var(--cyan);margin-bottom:6px">${c.filename}${c.section ? ' › ' + c.section : ''} — chunk ${c.chunk_idx} — score ${c.score?.toFixed(3)}</div>
             <div style="font-size:12px;color:var(--text-dim)">${c.content}</div>
           </div>`).join('')
     toast(`Retrieved ${chunks.length} chunks`, 'success')
@@ -689,16 +837,49 @@ document.getElementById('create_profile').addEventListener('click', async () =>
 })
 document.getElementById('refresh-profiles').addEventListener('click', () => { loadProfiles(); toast('Refreshed', 'info') })
 
+document.getElementById('change-passphrase').addEventListener('click', async () => {
+  const userId = document.getElementById('key-uid').value.trim()
+  const old = document.getElementById('key-pw').value
+  const nw = document.getElementById('key-new-pw').value
+  if (!userId || !old || !nw) { toast('Fill user ID, current and new passphrase', 'error'); return }
+  try {
+    await invoke('change_passphrase', { userId, old, new: nw })
+    showOut(document.getElementById('key_out'), `✅ Passphrase changed for "${userId}"`)
+    document.getElementById('key-pw').value = ''; document.getElementById('key-new-pw').value = ''
+    toast('Passphrase changed', 'success')
+  } catch (e) { showOut(document.getElementById('key_out'), `Error: ${e}`); toast(`Error: ${e}`, 'error') }
+})
+document.getElementById('rotate-key').addEventListener('click', async () => {
+  const userId = document.getElementById('key-uid').value.trim()
+  const passphrase = document.getElementById('key-pw').value
+  if (!userId || !passphrase) { toast('Fill user ID and current passphrase', 'error'); return }
+  try {
+    const r = await invoke('rotate_profile_key', { userId, passphrase })
+    showOut(document.getElementById('key_out'), JSON.stringify(r, null, 2))
+    toast('Key rotated — transition recorded in the audit log', 'success')
+    await Promise.allSettled([loadProfiles(), loadLogTable()])
+  } catch (e) { showOut(document.getElementById('key_out'), `Error: ${e}`); toast(`Error: ${e}`, 'error') }
+})
+document.getElementById('key-history').addEventListener('click', async () => {
+  const userId = document.getElementById('key-uid').value.trim()
+  if (!userId) { toast('Enter user ID', 'error'); return }
+  try {
+    const keys = await invoke('get_key_history', { userId })
+    showOut(document.getElementById('key_out'), JSON.stringify(keys, null, 2))
+  } catch (e) { showOut(document.getElementById('key_out'), `Error: ${e}`); toast(`Error: ${e}`, 'error') }
+})
+
 // ═══════════════════════════════════════════════════════════════════
 // ADMIN
 // ═══════════════════════════════════════════════════════════════════
 document.getElementById('admin_nonce').addEventListener('click', async () => {
   try {
-    const n = await invoke('admin_get_nonce')
+    const scopes = [document.getElementById('admin_role').value]
+    const c = await invoke('admin_get_nonce', { scopes })
     const d = document.getElementById('nonce-display')
-    d.innerHTML = `${n}<button class="output-copy" id="nonce-copy">Copy</button>`
-    document.getElementById('nonce-copy').addEventListener('click', () => copyText(n, 'Nonce copied!'))
-    toast('Nonce generated', 'info')
+    d.innerHTML = `${c.challenge}<button class="output-copy" id="nonce-copy">Copy</button>`
+    document.getElementById('nonce-copy').addEventListener('click', () => copyText(c.challenge, 'Challenge copied!'))
+    toast(`Challenge for ${c.scopes.join(', ')} — valid for 2 minutes, single use`, 'info')
   } catch (e) { toast(`Error: ${e}`, 'error') }
 })
 document.getElementById('admin_unlock').addEventListener('click', async () => {
@@ -706,31 +887,136 @@ document.getElementById('admin_unlock').addEventListener('click', async () => {
   if (!sig) { toast('Paste signature first', 'error'); return }
   try {
     const r = await invoke('admin_unlock', { signatureB64: sig })
-    showOut(document.getElementById('admin_out'), `Admin unlocked: ${r}`)
-    updateAdminBanner(true); toast('Admin mode unlocked! 🎉', 'success')
-  } catch (e) { showOut(document.getElementById('admin_out'), `Error: ${e}`); toast(`Unlock failed: ${e}`, 'error') }
+    showOut(document.getElementById('admin_out'), `Admin unlocked with scopes: ${r.scopes.join(', ')}`)
+    updateAdminBanner(r); toast('Admin mode unlocked! 🎉', 'success')
+  } catch (e) {
+    showOut(document.getElementById('admin_out'), `Error: ${e}\nThe challenge has been used up — get a new one before retrying.`)
+    toast(`Unlock failed: ${e}`, 'error')
+  }
 })
 document.getElementById('admin_status').addEventListener('click', async () => {
   try {
     const r = await invoke('admin_status'); updateAdminBanner(r)
-    toast(`Admin: ${r ? '🔓 Unlocked' : '🔒 Locked'}`, r ? 'success' : 'info')
+    toast(`Admin: ${r.unlocked ? `🔓 Unlocked, locks in ${Math.ceil(r.idle_lock_in / 60)} min if idle` : '🔒 Locked'}`, r.unlocked ? 'success' : 'info')
   } catch (e) { toast(`Error: ${e}`, 'error') }
 })
-function updateAdminBanner(unlocked) {
+document.getElementById('admin_lock').addEventListener('click', async () => {
+  try {
+    await invoke('admin_lock')
+    updateAdminBanner({ unlocked: false, scopes: [] }); toast('Admin mode locked', 'info')
+  } catch (e) { toast(`Error: ${e}`, 'error') }
+})
+document.getElementById('admin_clear_history').addEventListener('click', async () => {
+  const userId = document.getElementById('admin_clear_user').value.trim()
+  const receiptId = document.getElementById('admin_clear_receipt').value.trim()
+  if (!userId) { toast('Enter user ID', 'error'); return }
+  if (!receiptId) { toast('Enter the user\'s consent receipt for clear_user_history', 'error'); return }
+  if (!confirm(`Delete all answer history of "${userId}"?`)) return
+  try {
+    const n = await invoke('admin_clear_user_history', { userId, receiptId, approvalId: approvalId() })
+    showOut(document.getElementById('admin_out'), `Deleted ${n} history entries of "${userId}"`)
+  } catch (e) { toast(`Error: ${e}`, 'error') }
+})
+// Admin keys & m-of-n approvals. Protected actions pass the approval id field along;
+// its params must match exactly what the approval was requested for.
+const approvalId = () => document.getElementById('approval_id').value.trim() || null
+const keysOut = text => showOut(document.getElementById('keys_out'), text)
+const approvalParams = {
+  clear_user_history: () => ({ user_id: document.getElementById('admin_clear_user').value.trim() }),
+  clear_all_history:  () => null,
+  add_key: () => ({
+    label: document.getElementById('key_label').value.trim(),
+    pubkey_b64: document.getElementById('key_pub').value.trim(),
+    scopes: [document.getElementById('key_role').value],
+    valid_from: null, valid_until: null,
+  }),
+  revoke_key: () => ({ key_id: document.getElementById('key_revoke_id').value.trim(), reason: 'revoked from admin panel' }),
+  set_quorum: () => ({ required: Number(document.getElementById('keys_quorum').value) }),
+}
+document.getElementById('keys_status').addEventListener('click', async () => {
+  try {
+    const r = await invoke('admin_keys_status')
+    const fmt = t => t ? new Date(t * 1000).toLocaleDateString() : '—'
+    const lines = r.keys.map(k => `${k.id}  ${k.label}  [${k.scopes.join(', ')}]  ${fmt(k.valid_from)} → ${fmt(k.valid_until)}${k.revoked_at ? `  REVOKED (${k.revoke_reason || ''})` : ''}`)
+    keysOut(`Quorum for destructive actions: ${r.quorum}\n\n${lines.join('\n') || 'No keys registered.'}`)
+  } catch (e) { toast(`Error: ${e}`, 'error') }
+})
+document.getElementById('keys_set_quorum').addEventListener('click', async () => {
+  const required = Number(document.getElementById('keys_quorum').value)
+  if (!required) { toast('Enter a quorum', 'error'); return }
+  try {
+    const q = await invoke('admin_keys_set_quorum', { required, approvalId: approvalId() })
+    keysOut(`Quorum set to ${q}`); toast('Quorum updated', 'success')
+  } catch (e) { toast(`Error: ${e}`, 'error') }
+})
+document.getElementById('key_add').addEventListener('click', async () => {
+  const p = approvalParams.add_key()
+  if (!p.label || !p.pubkey_b64) { toast('Enter label and public key', 'error'); return }
+  try {
+    const k = await invoke('admin_keys_add', { label: p.label, pubkeyB64: p.pubkey_b64, scopes: p.scopes, validFrom: null, validUntil: null, approvalId: approvalId() })
+    keysOut(`Added key ${k.id} (${k.label}) with scopes ${k.scopes.join(', ')}`); toast('Admin key added', 'success')
+  } catch (e) { toast(`Error: ${e}`, 'error') }
+})
+document.getElementById('key_revoke').addEventListener('click', async () => {
+  const p = approvalParams.revoke_key()
+  if (!p.key_id) { toast('Enter key id', 'error'); return }
+  if (!confirm(`Revoke admin key ${p.key_id}?`)) return
+  try {
+    await invoke('admin_keys_revoke', { keyId: p.key_id, reason: p.reason, approvalId: approvalId() })
+    keysOut(`Revoked ${p.key_id}`); toast('Admin key revoked', 'success')
+  } catch (e) { toast(`Error: ${e}`, 'error') }
+})
+document.getElementById('approval_request').addEventListener('click', async () => {
+  const action = document.getElementById('approval_action').value
+  try {
+    const a = await invoke('admin_approval_request', { action, params: approvalParams[action]() })
+    document.getElementById('approval_id').value = a.id
+    keysOut(`Approval ${a.id} needs ${a.required} signature(s). Each approver signs:\n\n${a.statement}`)
+  } catch (e) { toast(`Error: ${e}`, 'error') }
+})
+document.getElementById('approval_submit').addEventListener('click', async () => {
+  const id = approvalId()
+  const signatureB64 = document.getElementById('approval_sig').value.trim()
+  if (!id || !signatureB64) { toast('Enter approval id and signature', 'error'); return }
+  try {
+    const a = await invoke('admin_approval_submit', { id, signatureB64 })
+    document.getElementById('approval_sig').value = ''
+    keysOut(`Approval ${a.id}: ${a.approved_by.length} of ${a.required} (${a.approved_by.join(', ')})`)
+  } catch (e) { toast(`Error: ${e}`, 'error') }
+})
+function updateAdminBanner(status) {
   const el = document.getElementById('admin-status-banner')
-  el.className = unlocked ? 'admin-unlocked-banner' : 'admin-locked'
-  el.innerHTML = unlocked ? '🔓 &nbsp;Admin mode is <strong>unlocked</strong>.' : '🔒 &nbsp;Admin mode is <strong>locked</strong>.'
+  el.className = status.unlocked ? 'admin-unlocked-banner' : 'admin-locked'
+  el.innerHTML = status.unlocked
+    ? `🔓 &nbsp;Admin mode is <strong>unlocked</strong> (${status.scopes.join(', ')}).`
+    : '🔒 &nbsp;Admin mode is <strong>locked</strong>.'
 }
 document.getElementById('req_consent').addEventListener('click', async () => {
   const userId = document.getElementById('c_user').value.trim()
-  if (!userId) { toast('Enter user ID', 'error'); return }
+  const action = document.getElementById('c_action').value.trim()
+  const paramsText = document.getElementById('c_params').value.trim()
+  if (!userId || !action) { toast('Enter user ID and action', 'error'); return }
+  let params = null
+  if (paramsText) {
+    try { params = JSON.parse(paramsText) } catch { toast('Parameters must be valid JSON', 'error'); return }
+  }
   try {
-    const r = await invoke('request_user_consent', { userId, action: 'DEMO_ACTION' })
-    document.getElementById('consent_out').textContent = JSON.stringify({ userId: r[0], nonce: r[1] }, null, 2)
-    document.getElementById('consent_nonce').value = r[1]
-    toast('Consent requested', 'info')
+    const r = await invoke('request_user_consent', { userId, action, params })
+    document.getElementById('consent_out').textContent = JSON.stringify(r.payload, null, 2)
+    document.getElementById('consent_nonce').value = r.nonce
+    toast('Consent requested — the user signs exactly this payload', 'info')
   } catch (e) { toast(`Error: ${e}`, 'error') }
 })
+document.getElementById('sign_consent').addEventListener('click', async () => {
+  const nonce = document.getElementById('consent_nonce').value.trim()
+  if (!nonce) { toast('Request consent first', 'error'); return }
+  try {
+    const r = await invoke('sign_consent', { nonce })
+    document.getElementById('user_sig').value = r.signature_b64
+    document.getElementById('user_pub').value = r.pubkey_b64
+    toast(`Signed "${r.payload.action}" as "${r.user_id}"`, 'success')
+  } catch (e) { toast(`Sign failed: ${e}`, 'error') }
+})
 document.getElementById('verify_consent').addEventListener('click', async () => {
   const userId = document.getElementById('c_user').value.trim()
   const consentNonce = document.getElementById('consent_nonce').value.trim()
@@ -738,43 +1024,154 @@ document.getElementById('verify_consent').addEventListener('click', async () =>
   const userPubkeyB64 = document.getElementById('user_pub').value.trim()
   if (!userId || !consentNonce || !userSignatureB64 || !userPubkeyB64) { toast('Fill all fields', 'error'); return }
   try {
-    const ok = await invoke('verify_user_consent_and_authorize', { userId, consentNonce, userSignatureB64, userPubkeyB64 })
-    showOut(document.getElementById('verify_out'), `✅ Authorized: ${ok}`)
+    const receipt = await invoke('verify_user_consent_and_authorize', { userId, consentNonce, userSignatureB64, userPubkeyB64 })
+    const expires = new Date(receipt.expires_at * 1000).toLocaleTimeString()
+    showOut(document.getElementById('verify_out'), `✅ Receipt ${receipt.id}\nAction: ${receipt.action}\nValid until ${expires}, single use`)
+    if (receipt.action === 'clear_user_history') {
+      document.getElementById('admin_clear_user').value = receipt.user_id
+      document.getElementById('admin_clear_receipt').value = receipt.id
+    }
     toast('Consent verified!', 'success')
-  } catch (e) { showOut(document.getElementById('verify_out'), `❌ ${e}`); toast(`Verify failed: ${e}`, 'error') }
+  } catch (e) {
+    showOut(document.getElementById('verify_out'), `❌ ${e}\nThe consent nonce has been used up — request consent again.`)
+    toast(`Verify failed: ${e}`, 'error')
+  }
+})
+document.getElementById('consent_receipts').addEventListener('click', async () => {
+  const userId = document.getElementById('c_user').value.trim() || null
+  try {
+    const rows = await invoke('consent_receipts', { userId })
+    const lines = rows.map(r => `${r.id}  ${r.user_id}  ${r.action}  ${r.redeemed_at ? `used ${r.redeemed_at}` : 'unused'}`)
+    showOut(document.getElementById('verify_out'), lines.length ? lines.join('\n') : 'No consent receipts.')
+  } catch (e) { toast(`Error: ${e}`, 'error') }
 })
 
 // ═══════════════════════════════════════════════════════════════════
 // LOGS
 // ═══════════════════════════════════════════════════════════════════
-async function loadLogTable() {
+// Events are canonical JSON ({"type": ..., ...}); show the type followed by its fields
+function formatEvent(raw) {
+  try {
+    const { type, ...rest } = JSON.parse(raw)
+    const fields = Object.entries(rest).map(([k, v]) => `${k}=${typeof v === 'string' ? v : JSON.stringify(v)}`).join(' ')
+    return `<strong>${type}</strong> ${fields}`
+  } catch { return raw }
+}
+let logCursor = null
+
+// datetime-local is local time; the log stores UTC
+function logFilter() {
+  const utc = id => { const v = document.getElementById(id).value; return v ? new Date(v).toISOString() : null }
+  const text = id => document.getElementById(id).value.trim() || null
+  return { from_ts: utc('lq_from'), to_ts: utc('lq_to'), event_type: text('lq_type'), actor: text('lq_actor'), text: text('lq_text') }
+}
+
+async function loadLogTable(append = false) {
   const tbody = document.getElementById('log-table-body')
+  const more = document.getElementById('lq_more')
   try {
-    const entries = await invoke('get_recent_logs', { limit: 20 })
-    if (!entries?.length) {
+    const page = await invoke('logs_query', { filter: logFilter(), beforeId: append ? logCursor : null, limit: 25 })
+    logCursor = page.next_cursor
+    more.style.display = logCursor ? '' : 'none'
+    if (!append && !page.entries.length) {
       tbody.innerHTML = '<tr><td colspan="5" style="text-align:center;color:var(--text-muted);padding:20px">No entries</td></tr>'; return
     }
-    tbody.innerHTML = entries.map(e => `<tr>
-      <td>${e.id}</td><td class="ts-cell">${e.ts}</td><td class="event-cell">${e.event}</td>
+    const rows = page.entries.map(e => `<tr>
+      <td>${e.id}</td><td class="ts-cell">${e.ts}</td><td class="event-cell">${formatEvent(e.event)}</td>
       <td class="hash-cell">${e.hash_prev.slice(0, 12)}…</td>
       <td class="hash-cell">${e.hash_curr.slice(0, 12)}…</td></tr>`).join('')
-  } catch { tbody.innerHTML = '<tr><td colspan="5" style="text-align:center;color:var(--text-muted);padding:20px">Not available</td></tr>' }
+    if (append) tbody.insertAdjacentHTML('beforeend', rows); else tbody.innerHTML = rows
+  } catch (e) {
+    more.style.display = 'none'
+    tbody.innerHTML = `<tr><td colspan="5" style="text-align:center;color:var(--text-muted);padding:20px">${e || 'Not available'}</td></tr>`
+  }
 }
-document.getElementById('append_log').addEventListener('click', async () => {
-  const event = document.getElementById('log_event').value.trim()
-  if (!event) { toast('Enter event text', 'error'); return }
-  try {
-    const h = await invoke('append_event', { event })
-    showOut(document.getElementById('log_out'), `Head: ${h}`)
-    document.getElementById('log_event').value = ''
-    toast('Event logged!', 'success'); await loadLogTable()
-  } catch (e) { toast(`Error: ${e}`, 'error') }
+document.getElementById('lq_search').addEventListener('click', () => loadLogTable())
+document.getElementById('lq_more').addEventListener('click', () => loadLogTable(true))
+document.getElementById('lq_clear').addEventListener('click', () => {
+  ['lq_from', 'lq_to', 'lq_type', 'lq_actor', 'lq_text'].forEach(id => { document.getElementById(id).value = '' })
+  loadLogTable()
 })
 document.getElementById('log_head').addEventListener('click', async () => {
   try { const h = await invoke('get_admin_log_head'); showOut(document.getElementById('log_out'), `Head: ${h}`) }
   catch (e) { toast(`Error: ${e}`, 'error') }
 })
-document.getElementById('refresh-logs').addEventListener('click', () => { loadLogTable(); toast('Refreshed', 'info') })
+function describeChainReport(r) {
+  const lines = [r.ok ? `✅ Chain intact — ${r.checked} entries checked` : `❌ Chain broken — ${r.checked} entries checked`]
+  if (r.first_broken) lines.push(`First broken link: #${r.first_broken.id} (stored ${r.first_broken.stored.slice(0, 12)}…, expected ${r.first_broken.expected.slice(0, 12)}…)`)
+  r.prev_mismatches.forEach(m => lines.push(`hash_prev mismatch at #${m.id}`))
+  r.gaps.forEach(g => lines.push(`Gap: #${g.after_id} → #${g.next_id}`))
+  r.checkpoints.forEach(c => lines.push(`Checkpoint @#${c.entry_id}: ${c.ok ? '✅ signed head matches' : `❌ ${c.reason}`}`))
+  return lines.join('\n')
+}
+document.getElementById('log_verify').addEventListener('click', async () => {
+  try {
+    const r = await invoke('logs_verify_chain', { fromId: null, toId: null })
+    showOut(document.getElementById('log_out'), describeChainReport(r))
+    toast(r.ok ? 'Audit log verified' : 'Audit log has been modified!', r.ok ? 'success' : 'error')
+  } catch (e) { toast(`Error: ${e}`, 'error') }
+})
+document.getElementById('export_logs').addEventListener('click', async () => {
+  const path = document.getElementById('export_path').value.trim()
+  const format = document.getElementById('export_format').value
+  if (!path) { toast('Enter a file path', 'error'); return }
+  try {
+    const r = await invoke('logs_export', { path, format })
+    showOut(document.getElementById('export_out'), `✅ Wrote ${r.entries} entries and ${r.checkpoints} checkpoints to ${r.path}`)
+    toast('Audit log exported', 'success')
+  } catch (e) { showOut(document.getElementById('export_out'), `Error: ${e}`); toast(`Export failed: ${e}`, 'error') }
+})
+document.getElementById('verify_bundle').addEventListener('click', async () => {
+  const path = document.getElementById('export_path').value.trim()
+  if (!path) { toast('Enter the exported file path', 'error'); return }
+  const trustedPubkeyB64 = document.getElementById('export_trusted_key').value.trim() || null
+  try {
+    const r = await invoke('logs_import_verify', { path, format: document.getElementById('export_format').value, trustedPubkeyB64 })
+    const key = r.trusted_keys.length ? `Signatures checked against ${r.trusted_keys.map(k => `${k.slice(0, 16)}…`).join(', ')}` : `No trusted key — signatures not checked${r.trust_error ? ` (${r.trust_error})` : ''}`
+    const anchor = r.anchored_to == null
+      ? '⚠️ No valid checkpoint — the chain is self-consistent but not anchored'
+      : r.unanchored_entries ? `⚠️ Anchored to #${r.anchored_to}; ${r.unanchored_entries} later entries are not covered by a checkpoint` : `✅ Head anchored by checkpoint at #${r.anchored_to}`
+    showOut(document.getElementById('export_out'), `${describeChainReport(r.chain)}\n${anchor}\n${key}`)
+    toast(r.ok ? 'Bundle verified' : 'Bundle failed verification', r.ok ? 'success' : 'error')
+  } catch (e) { showOut(document.getElementById('export_out'), `Error: ${e}`); toast(`Verify failed: ${e}`, 'error') }
+})
+async function loadCheckpoints() {
+  const el = document.getElementById('cp_list')
+  try {
+    const cps = await invoke('logs_checkpoints')
+    el.innerHTML = cps.length === 0
+      ? '<div style="color:var(--text-muted);font-size:13px">No checkpoints yet</div>'
+      : cps.map(c => `<div style="display:flex;justify-content:space-between;align-items:center;padding:10px 12px;background:var(--surface);border:1px solid var(--border);border-radius:8px">
+          <span style="font-size:13px">#${c.entry_id} — <span class="hash-cell">${c.head_hash.slice(0, 12)}…</span> — ${new Date(c.ts * 1000).toLocaleString()}</span>
+          <button class="btn-sm cp-export-btn" data-id="${c.id}">📤 Export</button>
+        </div>`).join('')
+    el.querySelectorAll('.cp-export-btn').forEach(btn => {
+      btn.addEventListener('click', async () => {
+        try { copyText(await invoke('logs_export_checkpoint', { id: +btn.dataset.id }), 'Checkpoint JSON copied!') }
+        catch (e) { toast(`Error: ${e}`, 'error') }
+      })
+    })
+  } catch (e) { el.innerHTML = `<div style="color:var(--text-muted);font-size:13px">${e || 'Not available'}</div>` }
+}
+document.getElementById('cp_prepare').addEventListener('click', async () => {
+  try {
+    const r = await invoke('logs_checkpoint_prepare')
+    const out = document.getElementById('cp_out')
+    showOut(out, `Sign with the admin key (${r.entries_since_last} entries since the last checkpoint):\n${r.statement}`)
+    copyText(r.statement, 'Statement copied — sign it with the admin key')
+  } catch (e) { toast(`Error: ${e}`, 'error') }
+})
+document.getElementById('cp_submit').addEventListener('click', async () => {
+  const signatureB64 = document.getElementById('cp_sig').value.trim()
+  if (!signatureB64) { toast('Paste the signature first', 'error'); return }
+  try {
+    const r = await invoke('logs_checkpoint_submit', { signatureB64 })
+    showOut(document.getElementById('cp_out'), `✅ Checkpoint stored at entry #${r.entry_id}`)
+    document.getElementById('cp_sig').value = ''
+    toast('Checkpoint signed', 'success'); await loadCheckpoints()
+  } catch (e) { showOut(document.getElementById('cp_out'), `❌ ${e}`); toast(`Submit failed: ${e}`, 'error') }
+})
+document.getElementById('refresh-logs').addEventListener('click', () => { loadLogTable(); loadCheckpoints(); toast('Refreshed', 'info') })
 
 // ═══════════════════════════════════════════════════════════════════
 // SETTINGS
@@ -796,8 +1193,37 @@ document.getElementById('unlock-btn').addEventListener('click', async () => {
     const ok = await invoke('unlock_profile', { userId, passphrase })
     showOut(document.getElementById('unlock_out'), ok ? `✅ Unlocked "${userId}"` : '❌ Wrong passphrase')
     toast(ok ? 'Profile unlocked!' : 'Wrong passphrase', ok ? 'success' : 'error')
+    document.getElementById('unlock-pw').value = ''
+    if (ok) await reloadProfileData()
   } catch (e) { showOut(document.getElementById('unlock_out'), `Error: ${e}`); toast(`Error: ${e}`, 'error') }
 })
+document.getElementById('lock-btn').addEventListener('click', async () => {
+  try {
+    await invoke('lock_profile')
+    showOut(document.getElementById('unlock_out'), '🔒 Profile locked — signing key cleared from memory')
+    toast('Profile locked', 'info')
+    await reloadProfileData()
+  } catch (e) { toast(`Error: ${e}`, 'error') }
+})
+document.getElementById('migrate-btn').addEventListener('click', async () => {
+  const userId = await invoke('session_user').catch(() => null)
+  if (!userId) { toast('Unlock a profile first', 'error'); return }
+  if (!confirm(`Move all shared history, resume documents and settings into "${userId}"? Other profiles will no longer see them.`)) return
+  try {
+    const r = await invoke('migrate_global_data', { userId })
+    showOut(document.getElementById('unlock_out'), `📦 Imported into "${userId}": ${r.history} answers, ${r.rag_chunks} resume chunks, ${r.settings} settings`)
+    toast('Shared data imported', 'success')
+    await reloadProfileData()
+  } catch (e) { toast(`Import failed: ${e}`, 'error') }
+})
+
+// History, vault and answer style are per profile — refresh them when the session changes
+async function reloadProfileData() {
+  const hasGlobal = await invoke('has_global_data').catch(() => false)
+  const unlocked = await invoke('session_user').catch(() => null)
+  document.getElementById('migrate-btn').style.display = hasGlobal && unlocked ? '' : 'none'
+  await Promise.allSettled([loadAnswerStyle(), loadHistory(), loadVaultDocs()])
+}
 
 async function loadAnswerStyle() {
   try { const s = await invoke('get_answer_style'); if (s) document.getElementById('style').value = s } catch { }
@@ -812,8 +1238,10 @@ async function init() {
   renderDatasets()
   await Promise.allSettled([
     loadAnswerStyle(),
+    loadConfig(),
     loadProfiles(),
     loadLogTable(),
+    loadCheckpoints(),
     loadHistory(),
     loadVaultDocs(),
     checkOllama(),